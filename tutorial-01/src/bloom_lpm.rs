@@ -0,0 +1,211 @@
+use std::cell::Cell;
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use crate::route_table::{Route, RouteTable};
+use crate::utils::prefix_mask;
+
+struct BloomFilter{
+    bits: Vec<u64>,
+    num_bits: usize,
+    hashes: u32,
+}
+
+impl BloomFilter{
+    fn new(num_bits: usize, hashes: u32) -> Self{
+        let num_bits = num_bits.max(64);
+        BloomFilter{
+            bits: vec![0; num_bits.div_ceil(64)],
+            num_bits,
+            hashes,
+        }
+    }
+
+    //double hashing, index_i = h1 + i * h2
+    fn indexes(&self, prefix: u32, prefix_len: u8) -> impl Iterator<Item = usize> + '_{
+        let h = mix(((prefix as u64) << 8) | prefix_len as u64);
+        let h1 = h as u32 as usize;
+        let h2 = ((h >> 32) as usize) | 1;
+        (0..self.hashes as usize).map(move |i| h1.wrapping_add(i.wrapping_mul(h2)) % self.num_bits)
+    }
+
+    fn add(&mut self, prefix: u32, prefix_len: u8){
+        let idx: Vec<usize> = self.indexes(prefix, prefix_len).collect();
+        for i in idx{
+            self.bits[i / 64] |= 1 << (i % 64);
+        }
+    }
+
+    fn contains(&self, prefix: u32, prefix_len: u8) -> bool{
+        self.indexes(prefix, prefix_len).all(|i| self.bits[i / 64] & (1 << (i % 64)) != 0)
+    }
+
+    fn bytes(&self) -> usize{
+        self.bits.len() * 8
+    }
+}
+
+//splitmix64 finalizer
+fn mix(mut x: u64) -> u64{
+    x = x.wrapping_add(0x9E37_79B9_7F4A_7C15);
+    x = (x ^ (x >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+    x = (x ^ (x >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+    x ^ (x >> 31)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct BloomLpmStats{
+    pub lookups: usize,
+    //lengths whose filter said "maybe"
+    pub filter_matches: usize,
+    pub table_probes: usize,
+    //table probes that found nothing, i.e. caused by a false positive
+    pub false_probes: usize,
+}
+
+//dharmapurikar et al: one bloom filter and one hash table per prefix length
+pub struct BloomLpm{
+    filters: Vec<BloomFilter>,
+    tables: Vec<HashMap<u32, String>>,
+    bits_per_prefix: usize,
+    hashes: u32,
+    lookups: Cell<usize>,
+    filter_matches: Cell<usize>,
+    table_probes: Cell<usize>,
+    false_probes: Cell<usize>,
+}
+
+impl BloomLpm{
+    pub fn new(bits_per_prefix: usize, hashes: u32) -> Self{
+        BloomLpm{
+            filters: (0..=32).map(|_| BloomFilter::new(64, hashes)).collect(),
+            tables: (0..=32).map(|_| HashMap::new()).collect(),
+            bits_per_prefix,
+            hashes,
+            lookups: Cell::new(0),
+            filter_matches: Cell::new(0),
+            table_probes: Cell::new(0),
+            false_probes: Cell::new(0),
+        }
+    }
+
+    pub fn stats(&self) -> BloomLpmStats{
+        BloomLpmStats{
+            lookups: self.lookups.get(),
+            filter_matches: self.filter_matches.get(),
+            table_probes: self.table_probes.get(),
+            false_probes: self.false_probes.get(),
+        }
+    }
+
+    pub fn filter_bytes(&self) -> usize{
+        self.filters.iter().map(|f| f.bytes()).sum()
+    }
+
+    //key + hop string header + hop bytes, ignoring hash map slack
+    pub fn table_bytes(&self) -> usize{
+        self.tables
+            .iter()
+            .flat_map(|t| t.values())
+            .map(|hop| size_of::<u32>() + size_of::<String>() + hop.len())
+            .sum()
+    }
+
+    //filters are resized to keep bits_per_prefix bits for every stored prefix
+    fn rebuild_filter(&mut self, len: usize){
+        let entries = self.tables[len].len();
+        let mut filter = BloomFilter::new((entries * self.bits_per_prefix).next_power_of_two(), self.hashes);
+        for &prefix in self.tables[len].keys(){
+            filter.add(prefix, len as u8);
+        }
+        self.filters[len] = filter;
+    }
+}
+
+impl RouteTable for BloomLpm{
+    fn name(&self) -> &'static str{
+        "bloom"
+    }
+
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        let len = prefix_len as usize;
+        let prefix = prefix & prefix_mask(prefix_len);
+        self.tables[len].insert(prefix, next_hop);
+
+        if self.tables[len].len() * self.bits_per_prefix > self.filters[len].num_bits{
+            self.rebuild_filter(len);
+        }else{
+            self.filters[len].add(prefix, prefix_len);
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        let len = prefix_len as usize;
+        let removed = self.tables[len].remove(&(prefix & prefix_mask(prefix_len)));
+        //plain bloom filters cannot delete, so rebuild this length from its table
+        if removed.is_some(){
+            self.rebuild_filter(len);
+        }
+        removed
+    }
+
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        self.lookups.set(self.lookups.get() + 1);
+
+        //in hardware all 33 filters are queried in parallel
+        let candidates: Vec<u8> = (0..=32u8)
+            .rev()
+            .filter(|&len| !self.tables[len as usize].is_empty())
+            .filter(|&len| self.filters[len as usize].contains(ip & prefix_mask(len), len))
+            .collect();
+        self.filter_matches.set(self.filter_matches.get() + candidates.len());
+
+        for len in candidates{
+            self.table_probes.set(self.table_probes.get() + 1);
+            let prefix = ip & prefix_mask(len);
+            match self.tables[len as usize].get(&prefix){
+                Some(hop) => return Some(Route::new(prefix, len, hop.clone())),
+                None => self.false_probes.set(self.false_probes.get() + 1),
+            }
+        }
+        None
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        self.tables
+            .iter()
+            .enumerate()
+            .flat_map(|(len, table)| table.iter().map(move |(&prefix, hop)| Route::new(prefix, len as u8, hop.clone())))
+            .collect()
+    }
+
+    fn node_count(&self) -> usize{
+        self.tables.iter().map(|t| t.len()).sum()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::route_table::reference::check_against_trie;
+
+    //false positives cost extra probes, never a wrong answer
+    #[test]
+    fn matches_trie_even_with_a_tiny_filter(){
+        for (bits, hashes) in [(1, 1), (4, 3), (10, 7)]{
+            let mut bloom = BloomLpm::new(bits, hashes);
+            check_against_trie(&mut bloom, 3_000, 29);
+            if bits == 1{
+                //one bit per prefix is all but useless, the table has to absorb the misses
+                let before = bloom.stats();
+                for ip in (0..u32::MAX).step_by(1 << 16){
+                    bloom.lookup_route(ip);
+                }
+                let after = bloom.stats();
+                assert_eq!(after.lookups - before.lookups, 1 << 16);
+                assert!(after.false_probes > before.false_probes);
+            }
+        }
+    }
+}
@@ -0,0 +1,355 @@
+use std::fmt;
+
+use crate::utils::{parse_prefix, prefix_mask, u32_to_ip, Rng};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PacketHeader{
+    pub src: u32,
+    pub dst: u32,
+    pub proto: u8,
+    pub sport: u16,
+    pub dport: u16,
+}
+
+impl fmt::Display for PacketHeader{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(
+            f,
+            "{}:{} -> {}:{} proto {}",
+            u32_to_ip(self.src),
+            self.sport,
+            u32_to_ip(self.dst),
+            self.dport,
+            self.proto
+        )
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Rule{
+    //lower value wins, like the order of an acl
+    pub priority: u32,
+    pub src: (u32, u8),
+    pub dst: (u32, u8),
+    //None matches any protocol
+    pub proto: Option<u8>,
+    pub sport: (u16, u16),
+    pub dport: (u16, u16),
+    pub action: String,
+}
+
+impl Rule{
+    pub fn matches(&self, pkt: &PacketHeader) -> bool{
+        self.matches_src(pkt.src) && self.matches_dst(pkt.dst) && self.matches_rest(pkt)
+    }
+
+    pub fn matches_src(&self, ip: u32) -> bool{
+        ip & prefix_mask(self.src.1) == self.src.0
+    }
+
+    pub fn matches_dst(&self, ip: u32) -> bool{
+        ip & prefix_mask(self.dst.1) == self.dst.0
+    }
+
+    //the fields that are not covered by the two tries
+    fn matches_rest(&self, pkt: &PacketHeader) -> bool{
+        self.proto.is_none_or(|p| p == pkt.proto)
+            && (self.sport.0..=self.sport.1).contains(&pkt.sport)
+            && (self.dport.0..=self.dport.1).contains(&pkt.dport)
+    }
+}
+
+impl fmt::Display for Rule{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        let proto = self.proto.map_or("*".to_string(), |p| p.to_string());
+        write!(
+            f,
+            "#{} {}/{} -> {}/{} proto {} sport {}-{} dport {}-{} => {}",
+            self.priority,
+            u32_to_ip(self.src.0),
+            self.src.1,
+            u32_to_ip(self.dst.0),
+            self.dst.1,
+            proto,
+            self.sport.0,
+            self.sport.1,
+            self.dport.0,
+            self.dport.1,
+            self.action
+        )
+    }
+}
+
+//reference answer: check every rule in priority order
+pub fn linear_classify<'a>(rules: &'a [Rule], pkt: &PacketHeader) -> Option<&'a Rule>{
+    rules.iter().filter(|r| r.matches(pkt)).min_by_key(|r| r.priority)
+}
+
+#[derive(Debug, Default)]
+struct DstNode{
+    left: Option<Box<DstNode>>,
+    right: Option<Box<DstNode>>,
+    //indexes into HierTrie::rules whose dst prefix ends here
+    rules: Vec<usize>,
+}
+
+#[derive(Debug, Default)]
+struct SrcNode{
+    left: Option<Box<SrcNode>>,
+    right: Option<Box<SrcNode>>,
+    //dst trie for the rules whose src prefix ends here
+    dst: Option<Box<DstNode>>,
+}
+
+//source trie whose prefix nodes point at destination tries, searched with backtracking
+pub struct HierTrie{
+    root: SrcNode,
+    rules: Vec<Rule>,
+    src_nodes: usize,
+    dst_nodes: usize,
+}
+
+impl HierTrie{
+    pub fn new() -> Self{
+        HierTrie{
+            root: SrcNode::default(),
+            rules: Vec::new(),
+            src_nodes: 1,
+            dst_nodes: 0,
+        }
+    }
+
+    pub fn build(rules: &[Rule]) -> Self{
+        let mut trie = HierTrie::new();
+        for rule in rules{
+            trie.insert(rule.clone());
+        }
+        trie
+    }
+
+    pub fn insert(&mut self, rule: Rule){
+        let (src, src_len) = rule.src;
+        let (dst, dst_len) = rule.dst;
+
+        let mut curr = &mut self.root;
+        for i in (32 - src_len as u32..32).rev(){
+            let child = if (src >> i) & 1 == 0 { &mut curr.left } else { &mut curr.right };
+            if child.is_none(){
+                self.src_nodes += 1;
+            }
+            curr = child.get_or_insert_with(Box::default);
+        }
+
+        if curr.dst.is_none(){
+            self.dst_nodes += 1;
+        }
+        let mut node = curr.dst.get_or_insert_with(Box::default).as_mut();
+        for i in (32 - dst_len as u32..32).rev(){
+            let child = if (dst >> i) & 1 == 0 { &mut node.left } else { &mut node.right };
+            if child.is_none(){
+                self.dst_nodes += 1;
+            }
+            node = child.get_or_insert_with(Box::default);
+        }
+
+        node.rules.push(self.rules.len());
+        self.rules.push(rule);
+    }
+
+    pub fn classify(&self, pkt: &PacketHeader) -> Option<&Rule>{
+        let mut best: Option<&Rule> = None;
+        let mut curr = &self.root;
+
+        //every source prefix on the path may hold the winner, so each dst trie on the way is searched
+        for i in (0..=32).rev(){
+            if let Some(ref dst) = curr.dst{
+                self.search_dst(dst, pkt, &mut best);
+            }
+            if i == 0{
+                break;
+            }
+
+            let next = if (pkt.src >> (i - 1)) & 1 == 0 { &curr.left } else { &curr.right };
+            match next{
+                Some(node) => curr = node,
+                None => break,
+            }
+        }
+        best
+    }
+
+    fn search_dst<'a>(&'a self, root: &DstNode, pkt: &PacketHeader, best: &mut Option<&'a Rule>){
+        let mut node = root;
+        for i in (0..=32).rev(){
+            for &idx in &node.rules{
+                let rule = &self.rules[idx];
+                if rule.matches_rest(pkt) && best.is_none_or(|b| rule.priority < b.priority){
+                    *best = Some(rule);
+                }
+            }
+            if i == 0{
+                break;
+            }
+
+            let next = if (pkt.dst >> (i - 1)) & 1 == 0 { &node.left } else { &node.right };
+            match next{
+                Some(child) => node = child,
+                None => break,
+            }
+        }
+    }
+
+    pub fn rule_count(&self) -> usize{
+        self.rules.len()
+    }
+
+    pub fn node_count(&self) -> (usize, usize){
+        (self.src_nodes, self.dst_nodes)
+    }
+}
+
+const PROTOCOLS: [u8; 3] = [6, 17, 1];
+const WELL_KNOWN_PORTS: [u16; 8] = [22, 25, 53, 80, 123, 443, 3306, 8080];
+
+//acl-like rules: mostly /16-/32 sources and destinations, a few wildcards and port ranges
+pub fn generate_rules(count: usize, seed: u64) -> Vec<Rule>{
+    let mut rng = Rng::new(seed);
+    let mut rules = Vec::with_capacity(count);
+
+    for priority in 0..count as u32{
+        let src_len = [0u8, 8, 16, 24, 32][rng.below(5) as usize];
+        let dst_len = [0u8, 16, 24, 32][rng.below(4) as usize];
+        let proto = if rng.below(4) == 0 { None } else { Some(PROTOCOLS[rng.below(3) as usize]) };
+        let dport = match rng.below(3){
+            0 => (0, u16::MAX),
+            1 => {
+                let port = WELL_KNOWN_PORTS[rng.below(8) as usize];
+                (port, port)
+            }
+            _ => (1024, u16::MAX),
+        };
+        let sport = if rng.below(5) == 0 { (1024, u16::MAX) } else { (0, u16::MAX) };
+
+        rules.push(Rule{
+            priority,
+            src: (small_space(&mut rng) & prefix_mask(src_len), src_len),
+            dst: (small_space(&mut rng) & prefix_mask(dst_len), dst_len),
+            proto,
+            sport,
+            dport,
+            action: if rng.below(3) == 0 { "deny".to_string() } else { "permit".to_string() },
+        });
+    }
+    rules
+}
+
+//packets built from random rules so that a good share of them match something,
+//so there has to be at least one rule
+pub fn generate_packets(rules: &[Rule], count: usize, seed: u64) -> Vec<PacketHeader>{
+    let mut rng = Rng::new(seed);
+    (0..count)
+        .map(|_| {
+            let rule = &rules[rng.below(rules.len() as u64) as usize];
+            PacketHeader{
+                src: rule.src.0 | (small_space(&mut rng) & !prefix_mask(rule.src.1)),
+                dst: rule.dst.0 | (small_space(&mut rng) & !prefix_mask(rule.dst.1)),
+                proto: rule.proto.unwrap_or(PROTOCOLS[rng.below(3) as usize]),
+                sport: rng.next_u32() as u16,
+                dport: if rng.below(2) == 0 { rule.dport.0 } else { rng.next_u32() as u16 },
+            }
+        })
+        .collect()
+}
+
+//addresses drawn from a handful of /8s so rules overlap like they do in real acls
+fn small_space(rng: &mut Rng) -> u32{
+    let first = [10u32, 172, 192][rng.below(3) as usize];
+    (first << 24) | (rng.next_u32() & 0x00FF_FFFF)
+}
+
+//classbench filter format, one rule per line in priority order:
+//@src/len<tab>dst/len<tab>sport_lo : sport_hi<tab>dport_lo : dport_hi<tab>proto/mask
+pub fn parse_classbench(text: &str) -> Result<Vec<Rule>, String>{
+    let mut rules = Vec::new();
+
+    for (n, line) in text.lines().enumerate(){
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+        let rule = parse_classbench_line(line, rules.len() as u32)
+            .ok_or_else(|| format!("line {}: cannot parse '{}'", n + 1, line))?;
+        rules.push(rule);
+    }
+    Ok(rules)
+}
+
+fn parse_classbench_line(line: &str, priority: u32) -> Option<Rule>{
+    let fields: Vec<&str> = line.strip_prefix('@')?.split('\t').map(str::trim).collect();
+    if fields.len() < 5{
+        return None;
+    }
+
+    let port_range = |field: &str| -> Option<(u16, u16)>{
+        let (lo, hi) = field.split_once(':')?;
+        Some((lo.trim().parse().ok()?, hi.trim().parse().ok()?))
+    };
+    let (proto, proto_mask) = fields[4].split_once('/')?;
+    let proto = u8::from_str_radix(proto.trim_start_matches("0x"), 16).ok()?;
+    let proto_mask = u8::from_str_radix(proto_mask.trim_start_matches("0x"), 16).ok()?;
+
+    Some(Rule{
+        priority,
+        src: parse_prefix(fields[0])?,
+        dst: parse_prefix(fields[1])?,
+        proto: if proto_mask == 0 { None } else { Some(proto) },
+        sport: port_range(fields[2])?,
+        dport: port_range(fields[3])?,
+        action: fields.get(5).filter(|a| !a.starts_with("0x")).map_or("match".to_string(), |a| a.to_string()),
+    })
+}
+
+pub fn format_classbench(rules: &[Rule]) -> String{
+    let mut out = String::new();
+    let mut sorted: Vec<&Rule> = rules.iter().collect();
+    sorted.sort_by_key(|r| r.priority);
+
+    for rule in sorted{
+        let (proto, mask) = rule.proto.map_or((0, 0), |p| (p, 0xFF));
+        out.push_str(&format!(
+            "@{}/{}\t{}/{}\t{} : {}\t{} : {}\t0x{:02x}/0x{:02x}\t{}\n",
+            u32_to_ip(rule.src.0),
+            rule.src.1,
+            u32_to_ip(rule.dst.0),
+            rule.dst.1,
+            rule.sport.0,
+            rule.sport.1,
+            rule.dport.0,
+            rule.dport.1,
+            proto,
+            mask,
+            rule.action
+        ));
+    }
+    out
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn hier_trie_matches_linear_search(){
+        let rules = generate_rules(500, 31);
+        let trie = HierTrie::build(&rules);
+        for pkt in generate_packets(&rules, 20_000, 31){
+            assert_eq!(trie.classify(&pkt), linear_classify(&rules, &pkt), "{}", pkt);
+        }
+    }
+
+    #[test]
+    fn classbench_text_round_trips(){
+        let rules = generate_rules(200, 32);
+        assert_eq!(parse_classbench(&format_classbench(&rules)), Ok(rules));
+    }
+}
@@ -0,0 +1,266 @@
+use std::mem::size_of;
+
+use crate::classifier::{PacketHeader, Rule};
+
+//src, dst, sport, dport, proto
+const DIMS: usize = 5;
+const MAX_DEPTH: usize = 32;
+
+type Range = (u64, u64);
+
+fn rule_ranges(rule: &Rule) -> [Range; DIMS]{
+    let prefix = |(value, len): (u32, u8)| -> Range{
+        let span = if len == 0 { u32::MAX as u64 } else { (1u64 << (32 - len)) - 1 };
+        (value as u64, value as u64 + span)
+    };
+    [
+        prefix(rule.src),
+        prefix(rule.dst),
+        (rule.sport.0 as u64, rule.sport.1 as u64),
+        (rule.dport.0 as u64, rule.dport.1 as u64),
+        rule.proto.map_or((0, 255), |p| (p as u64, p as u64)),
+    ]
+}
+
+fn packet_point(pkt: &PacketHeader) -> [u64; DIMS]{
+    [pkt.src as u64, pkt.dst as u64, pkt.sport as u64, pkt.dport as u64, pkt.proto as u64]
+}
+
+fn overlaps(a: Range, b: Range) -> bool{
+    a.0 <= b.1 && b.0 <= a.1
+}
+
+enum Node{
+    //rule indexes in priority order
+    Leaf(Vec<usize>),
+    Cut{
+        dim: usize,
+        lo: u64,
+        width: u64,
+        //arena indexes, adjacent children with the same rules share one node
+        children: Vec<usize>,
+    },
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct HiCutsConfig{
+    //a node with at most this many rules becomes a leaf
+    pub binth: usize,
+    //space factor, bounds sum(child rules) + cuts by spfac * rules at the node
+    pub spfac: f64,
+}
+
+impl HiCutsConfig{
+    pub fn new(binth: usize, spfac: f64) -> Self{
+        HiCutsConfig{ binth, spfac }
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct HiCutsStats{
+    pub nodes: usize,
+    pub leaves: usize,
+    pub max_depth: usize,
+    //average leaf depth weighted by leaf count
+    pub avg_depth: f64,
+    pub rule_refs: usize,
+    pub bytes: usize,
+}
+
+//hicuts decision tree (gupta and mckeown), equal-sized cuts on one dimension per node
+pub struct HiCuts{
+    nodes: Vec<Node>,
+    rules: Vec<Rule>,
+    ranges: Vec<[Range; DIMS]>,
+    config: HiCutsConfig,
+    depth_sum: usize,
+    max_depth: usize,
+}
+
+impl HiCuts{
+    pub fn build(rules: &[Rule], config: HiCutsConfig) -> Self{
+        let mut rules = rules.to_vec();
+        rules.sort_by_key(|r| r.priority);
+        let ranges = rules.iter().map(rule_ranges).collect();
+
+        let mut tree = HiCuts{
+            nodes: Vec::new(),
+            rules,
+            ranges,
+            config,
+            depth_sum: 0,
+            max_depth: 0,
+        };
+
+        let full = [
+            (0, u32::MAX as u64),
+            (0, u32::MAX as u64),
+            (0, u16::MAX as u64),
+            (0, u16::MAX as u64),
+            (0, 255),
+        ];
+        let all: Vec<usize> = (0..tree.rules.len()).collect();
+        tree.build_node(all, full, 0);
+        tree
+    }
+
+    //returns the arena index of the new node, the root ends up last
+    fn build_node(&mut self, rules: Vec<usize>, region: [Range; DIMS], depth: usize) -> usize{
+        let rules = self.prune(rules, &region);
+
+        if rules.len() <= self.config.binth || depth >= MAX_DEPTH{
+            return self.leaf(rules, depth);
+        }
+        let Some((dim, cuts)) = self.choose_cut(&rules, &region) else{
+            return self.leaf(rules, depth);
+        };
+
+        let (lo, hi) = region[dim];
+        let width = (hi - lo + 1).div_ceil(cuts as u64);
+        let child_count = (hi - lo) / width + 1;
+
+        //runs of neighbouring children with the same rules share one node built over their merged region
+        let mut runs: Vec<(u64, u64, Vec<usize>)> = Vec::new();
+        for i in 0..child_count{
+            let child = (lo + i * width, (lo + (i + 1) * width - 1).min(hi));
+            let child_rules: Vec<usize> =
+                rules.iter().copied().filter(|&r| overlaps(self.ranges[r][dim], child)).collect();
+            match runs.last_mut(){
+                Some(run) if run.2 == child_rules => run.1 = i,
+                _ => runs.push((i, i, child_rules)),
+            }
+        }
+
+        //the cut separated nothing, recursing would rebuild this same node
+        if runs.len() == 1{
+            return self.leaf(rules, depth);
+        }
+
+        let mut children = Vec::with_capacity(child_count as usize);
+        for (first, last, child_rules) in runs{
+            let mut child_region = region;
+            child_region[dim] = (lo + first * width, (lo + (last + 1) * width - 1).min(hi));
+            let child = self.build_node(child_rules, child_region, depth + 1);
+            children.extend((first..=last).map(|_| child));
+        }
+
+        self.nodes.push(Node::Cut{ dim, lo, width, children });
+        self.nodes.len() - 1
+    }
+
+    fn leaf(&mut self, rules: Vec<usize>, depth: usize) -> usize{
+        self.depth_sum += depth;
+        self.max_depth = self.max_depth.max(depth);
+        self.nodes.push(Node::Leaf(rules));
+        self.nodes.len() - 1
+    }
+
+    //rules after one that covers the whole region can never win inside it
+    fn prune(&self, rules: Vec<usize>, region: &[Range; DIMS]) -> Vec<usize>{
+        let mut kept = Vec::with_capacity(rules.len());
+        for r in rules{
+            kept.push(r);
+            let covers = (0..DIMS).all(|d| self.ranges[r][d].0 <= region[d].0 && self.ranges[r][d].1 >= region[d].1);
+            if covers{
+                break;
+            }
+        }
+        kept
+    }
+
+    //dimension with the most distinct rule projections, then as many cuts as spfac allows
+    fn choose_cut(&self, rules: &[usize], region: &[Range; DIMS]) -> Option<(usize, usize)>{
+        let mut best: Option<(usize, usize)> = None;
+        for (d, &(lo, hi)) in region.iter().enumerate(){
+            if lo == hi{
+                continue;
+            }
+            let mut projections: Vec<Range> = rules
+                .iter()
+                .map(|&r| (self.ranges[r][d].0.max(lo), self.ranges[r][d].1.min(hi)))
+                .collect();
+            projections.sort_unstable();
+            projections.dedup();
+            if projections.len() > 1 && best.is_none_or(|(_, count)| projections.len() > count){
+                best = Some((d, projections.len()));
+            }
+        }
+        let (dim, _) = best?;
+
+        let (lo, hi) = region[dim];
+        let span = hi - lo + 1;
+        let budget = self.config.spfac * rules.len() as f64;
+        let mut cuts = 2usize;
+        while (cuts * 2) as u64 <= span{
+            let next = cuts * 2;
+            let width = span.div_ceil(next as u64);
+            let mut cost = next;
+            for i in 0..next as u64{
+                let child = (lo + i * width, (lo + (i + 1) * width - 1).min(hi));
+                cost += rules.iter().filter(|&&r| overlaps(self.ranges[r][dim], child)).count();
+            }
+            if cost as f64 > budget{
+                break;
+            }
+            cuts = next;
+        }
+        Some((dim, cuts))
+    }
+
+    pub fn classify(&self, pkt: &PacketHeader) -> Option<&Rule>{
+        let point = packet_point(pkt);
+        let mut node = self.nodes.len().checked_sub(1)?;
+
+        loop{
+            match &self.nodes[node]{
+                Node::Leaf(rules) => {
+                    return rules.iter().map(|&r| &self.rules[r]).find(|rule| rule.matches(pkt));
+                }
+                Node::Cut{ dim, lo, width, children } => {
+                    let idx = ((point[*dim] - lo) / width) as usize;
+                    node = children[idx.min(children.len() - 1)];
+                }
+            }
+        }
+    }
+
+    pub fn stats(&self) -> HiCutsStats{
+        let mut stats = HiCutsStats{
+            nodes: self.nodes.len(),
+            max_depth: self.max_depth,
+            ..HiCutsStats::default()
+        };
+        for node in &self.nodes{
+            //node header plus 4-byte child pointers / rule references, as a hardware layout would use
+            stats.bytes += 8;
+            match node{
+                Node::Leaf(rules) => {
+                    stats.leaves += 1;
+                    stats.rule_refs += rules.len();
+                    stats.bytes += rules.len() * size_of::<u32>();
+                }
+                Node::Cut{ children, .. } => stats.bytes += children.len() * size_of::<u32>(),
+            }
+        }
+        stats.avg_depth = if stats.leaves == 0 { 0.0 } else { self.depth_sum as f64 / stats.leaves as f64 };
+        stats
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::classifier::{generate_packets, generate_rules, linear_classify};
+
+    #[test]
+    fn decision_tree_matches_linear_search(){
+        let rules = generate_rules(1_000, 32);
+        let packets = generate_packets(&rules, 10_000, 32);
+        for (binth, spfac) in [(8, 2.0), (16, 4.0), (32, 8.0)]{
+            let tree = HiCuts::build(&rules, HiCutsConfig::new(binth, spfac));
+            for pkt in &packets{
+                assert_eq!(tree.classify(pkt), linear_classify(&rules, pkt), "{}", pkt);
+            }
+        }
+    }
+}
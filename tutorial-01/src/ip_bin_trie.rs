@@ -1,20 +1,28 @@
+use crate::route_counters::PrefixCounters;
+use crate::route_table::Route;
+use crate::utils::prefix_mask;
+
+//values default to next hop names, other per-prefix data (roas, metadata) reuses the same trie
 #[derive(Debug)]
-pub struct TrieNode{
-    left: Option<Box<TrieNode>>,
-    right: Option<Box<TrieNode>>,
-    next_hop: Option<String>
+pub struct TrieNode<V = String>{
+    left: Option<Box<TrieNode<V>>>,
+    right: Option<Box<TrieNode<V>>>,
+    next_hop: Option<V>,
+    //allocated on the first counted hit, so tables that never count pay one pointer per node
+    counters: Option<Box<PrefixCounters>>,
 }
 
-impl TrieNode{
+impl<V> TrieNode<V>{
     pub fn new() -> Self {
         TrieNode {
             left: None,
             right: None,
             next_hop: None,
+            counters: None,
         }
     }
 
-    pub fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String){
+    pub fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: V){
         let mut curr = self;
 
         for i in (32 - prefix_len..32).rev(){
@@ -30,7 +38,10 @@ impl TrieNode{
         curr.next_hop = Some(next_hop);
     }
 
-    pub fn lookup(&self, ip: u32) -> Option<String>{
+    pub fn lookup(&self, ip: u32) -> Option<V>
+    where
+        V: Clone,
+    {
         let mut curr = self;
         let mut result = None;
 
@@ -59,6 +70,195 @@ impl TrieNode{
             result = Some(hop.clone());
         }
 
-        return result;
+        result
+    }
+
+    //longest match together with the matched prefix
+    pub fn lookup_entry(&self, ip: u32) -> Option<(u32, u8, &V)>{
+        let mut curr = self;
+        let mut result = None;
+        let mut depth = 0u8;
+
+        loop{
+            if let Some(ref hop) = curr.next_hop{
+                result = Some((depth, hop));
+            }
+            if depth == 32{
+                break;
+            }
+
+            let bit = (ip >> (31 - depth)) & 1;
+            let next = if bit == 0 { &curr.left } else { &curr.right };
+            match next{
+                Some(node) => curr = node.as_ref(),
+                None => break,
+            }
+            depth += 1;
+        }
+
+        result.map(|(len, hop)| (ip & prefix_mask(len), len, hop))
+    }
+
+    //lookup_entry that also charges the packet to the matched prefix
+    pub fn lookup_counted(&mut self, ip: u32, packet_len: u32) -> Option<(u32, u8, &V)>{
+        let (_, len, _) = self.lookup_entry(ip)?;
+
+        let mut curr = self;
+        for depth in 0..len{
+            let bit = (ip >> (31 - depth)) & 1;
+            let next = if bit == 0 { &mut curr.left } else { &mut curr.right };
+            curr = next.as_mut()?;
+        }
+        curr.counters.get_or_insert_with(Box::default).hit(packet_len);
+        curr.next_hop.as_ref().map(|value| (ip & prefix_mask(len), len, value))
+    }
+
+    //every prefix that has been hit since the last reset, with its value
+    pub fn counters(&self) -> Vec<(u32, u8, &V, PrefixCounters)>{
+        let mut out = Vec::new();
+        self.collect_counters(0, 0, &mut out);
+        out
+    }
+
+    fn collect_counters<'a>(&'a self, prefix: u32, depth: u8, out: &mut Vec<(u32, u8, &'a V, PrefixCounters)>){
+        if let (Some(value), Some(counters)) = (&self.next_hop, &self.counters){
+            out.push((prefix, depth, value, **counters));
+        }
+        if let Some(ref left) = self.left{
+            left.collect_counters(prefix, depth + 1, out);
+        }
+        if let Some(ref right) = self.right{
+            right.collect_counters(prefix | (1 << (31 - depth)), depth + 1, out);
+        }
+    }
+
+    pub fn reset_counters(&mut self){
+        self.counters = None;
+        if let Some(ref mut left) = self.left{
+            left.reset_counters();
+        }
+        if let Some(ref mut right) = self.right{
+            right.reset_counters();
+        }
+    }
+
+    //every stored prefix that covers prefix/prefix_len (itself included), shortest first
+    pub fn covering(&self, prefix: u32, prefix_len: u8) -> Vec<(u32, u8, &V)>{
+        let mut out = Vec::new();
+        let mut curr = self;
+
+        for depth in 0..=prefix_len{
+            if let Some(ref value) = curr.next_hop{
+                out.push((prefix & prefix_mask(depth), depth, value));
+            }
+            if depth == prefix_len{
+                break;
+            }
+
+            let bit = (prefix >> (31 - depth)) & 1;
+            match curr.child(bit){
+                Some(node) => curr = node,
+                None => break,
+            }
+        }
+        out
+    }
+
+    pub fn get_mut(&mut self, prefix: u32, prefix_len: u8) -> Option<&mut V>{
+        let mut curr = self;
+        for depth in 0..prefix_len{
+            let bit = (prefix >> (31 - depth)) & 1;
+            let next = if bit == 0 { &mut curr.left } else { &mut curr.right };
+            curr = next.as_mut()?;
+        }
+        curr.next_hop.as_mut()
+    }
+
+    //nodes visited by lookup, the root included
+    pub fn lookup_depth(&self, ip: u32) -> usize{
+        let mut curr = self;
+        let mut depth = 1;
+
+        for i in (0..32).rev(){
+            let next = if (ip >> i) & 1 == 0 { &curr.left } else { &curr.right };
+            match next{
+                Some(node) => curr = node.as_ref(),
+                None => break,
+            }
+            depth += 1;
+        }
+        depth
+    }
+
+    pub fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<V>{
+        self.remove_at(prefix, prefix_len, 0)
+    }
+
+    fn remove_at(&mut self, prefix: u32, prefix_len: u8, depth: u8) -> Option<V>{
+        if depth == prefix_len{
+            self.counters = None;
+            return self.next_hop.take();
+        }
+
+        let bit = (prefix >> (31 - depth)) & 1;
+        let child = if bit == 0 { &mut self.left } else { &mut self.right };
+        let node = child.as_mut()?;
+        let removed = node.remove_at(prefix, prefix_len, depth + 1);
+
+        //prune branches that no longer lead to any route
+        if node.is_empty(){
+            *child = None;
+        }
+        removed
+    }
+
+    pub fn child(&self, bit: u32) -> Option<&TrieNode<V>>{
+        if bit == 0 { self.left.as_deref() } else { self.right.as_deref() }
+    }
+
+    pub fn value(&self) -> Option<&V>{
+        self.next_hop.as_ref()
+    }
+
+    pub fn has_children(&self) -> bool{
+        self.left.is_some() || self.right.is_some()
+    }
+
+    fn is_empty(&self) -> bool{
+        self.next_hop.is_none() && self.left.is_none() && self.right.is_none()
+    }
+
+    //all stored prefixes in address order
+    pub fn entries(&self) -> Vec<(u32, u8, &V)>{
+        let mut out = Vec::new();
+        self.collect(0, 0, &mut out);
+        out
+    }
+
+    fn collect<'a>(&'a self, prefix: u32, depth: u8, out: &mut Vec<(u32, u8, &'a V)>){
+        if let Some(ref value) = self.next_hop{
+            out.push((prefix, depth, value));
+        }
+        if let Some(ref left) = self.left{
+            left.collect(prefix, depth + 1, out);
+        }
+        if let Some(ref right) = self.right{
+            right.collect(prefix | (1 << (31 - depth)), depth + 1, out);
+        }
+    }
+
+    pub fn node_count(&self) -> usize{
+        1 + self.left.as_ref().map_or(0, |n| n.node_count())
+            + self.right.as_ref().map_or(0, |n| n.node_count())
+    }
+}
+
+impl TrieNode{
+    pub fn lookup_route(&self, ip: u32) -> Option<Route>{
+        self.lookup_entry(ip).map(|(prefix, len, hop)| Route::new(prefix, len, hop.clone()))
+    }
+
+    pub fn routes(&self) -> Vec<Route>{
+        self.entries().into_iter().map(|(prefix, len, hop)| Route::new(prefix, len, hop.clone())).collect()
     }
 }
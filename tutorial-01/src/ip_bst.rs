@@ -1,3 +1,6 @@
+use crate::route_table::Route;
+use crate::utils::prefix_mask;
+
 #[derive(Debug)]
 pub struct BSTNode{
     prefix: u32,
@@ -33,12 +36,7 @@ impl BSTNode{
     }
 
     pub fn matches(&self, ip: u32) -> bool{
-        let mask = if self.prefix_len == 0{
-            0
-        }else{
-            !0u32 << (32 - self.prefix_len)
-        };
-
+        let mask = prefix_mask(self.prefix_len);
         (ip & mask) == (self.prefix & mask)
     }
 
@@ -57,4 +55,134 @@ impl BSTNode{
             right.lookup(ip, best, best_len);
         }
     }
-}
\ No newline at end of file
+
+    pub fn lookup_route(&self, ip: u32) -> Option<Route>{
+        let mut best: Option<&BSTNode> = None;
+        self.best_match(ip, &mut best);
+        best.map(|node| Route::new(node.prefix, node.prefix_len, node.next_hop.clone()))
+    }
+
+    fn best_match<'a>(&'a self, ip: u32, best: &mut Option<&'a BSTNode>){
+        if self.matches(ip) && best.is_none_or(|b| self.prefix_len > b.prefix_len){
+            *best = Some(self);
+        }
+
+        if let Some(ref left) = self.left{
+            left.best_match(ip, best);
+        }
+        if let Some(ref right) = self.right{
+            right.best_match(ip, best);
+        }
+    }
+
+    pub fn collect(&self, out: &mut Vec<Route>){
+        if let Some(ref left) = self.left{
+            left.collect(out);
+        }
+        out.push(Route::new(self.prefix, self.prefix_len, self.next_hop.clone()));
+        if let Some(ref right) = self.right{
+            right.collect(out);
+        }
+    }
+
+    //takes the subtree by value since the root itself may be the node to delete
+    pub fn remove(node: Option<Box<BSTNode>>, prefix: u32, prefix_len: u8) -> (Option<Box<BSTNode>>, Option<String>){
+        let mut node = match node{
+            Some(node) => node,
+            None => return (None, None),
+        };
+
+        if prefix < node.prefix{
+            let (left, removed) = BSTNode::remove(node.left.take(), prefix, prefix_len);
+            node.left = left;
+            return (Some(node), removed);
+        }
+
+        if prefix != node.prefix || prefix_len != node.prefix_len{
+            //equal prefixes with another length were inserted to the right
+            let (right, removed) = BSTNode::remove(node.right.take(), prefix, prefix_len);
+            node.right = right;
+            return (Some(node), removed);
+        }
+
+        let removed = Some(node.next_hop.clone());
+        match (node.left.take(), node.right.take()){
+            (None, right) => (right, removed),
+            (left, None) => (left, removed),
+            (left, Some(right)) => {
+                //smallest node of the right subtree takes this node's place
+                let (mut min, rest) = BSTNode::take_min(right);
+                min.left = left;
+                min.right = rest;
+                (Some(min), removed)
+            }
+        }
+    }
+
+    fn take_min(mut node: Box<BSTNode>) -> (Box<BSTNode>, Option<Box<BSTNode>>){
+        match node.left.take(){
+            None => {
+                let rest = node.right.take();
+                (node, rest)
+            }
+            Some(left) => {
+                let (min, rest) = BSTNode::take_min(left);
+                node.left = rest;
+                (min, Some(node))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //a /8 per first octet keeps the keys readable
+    fn octets(root: &Option<Box<BSTNode>>) -> Vec<(u32, u8)>{
+        let mut routes = Vec::new();
+        if let Some(root) = root{
+            root.collect(&mut routes);
+        }
+        routes.iter().map(|r| (r.prefix >> 24, r.prefix_len)).collect()
+    }
+
+    #[test]
+    fn remove_a_node_with_two_children(){
+        let mut root = Some(Box::new(BSTNode::new(50 << 24, 8, "50".to_string())));
+        for octet in [30, 70, 20, 40, 60, 80, 65]{
+            root.as_mut().unwrap().insert(octet << 24, 8, octet.to_string());
+        }
+
+        //the root has two children: 60, the smallest on the right, takes its place
+        //and its own right child 65 moves up under 70
+        let (root, removed) = BSTNode::remove(root, 50 << 24, 8);
+        assert_eq!(removed.as_deref(), Some("50"));
+        let new_root = root.as_ref().unwrap();
+        assert_eq!(new_root.prefix, 60 << 24);
+        assert_eq!(new_root.right.as_ref().unwrap().left.as_ref().unwrap().prefix, 65 << 24);
+        assert_eq!(octets(&root), [(20, 8), (30, 8), (40, 8), (60, 8), (65, 8), (70, 8), (80, 8)]);
+
+        let (root, removed) = BSTNode::remove(root, 70 << 24, 8);
+        assert_eq!(removed.as_deref(), Some("70"));
+        assert_eq!(octets(&root), [(20, 8), (30, 8), (40, 8), (60, 8), (65, 8), (80, 8)]);
+
+        let (root, removed) = BSTNode::remove(root, 70 << 24, 8);
+        assert_eq!(removed, None);
+        for octet in [20, 30, 40, 60, 65, 80]{
+            let hop = root.as_ref().unwrap().lookup_route((octet << 24) | 1).map(|r| r.next_hop);
+            assert_eq!(hop, Some(octet.to_string()));
+        }
+    }
+
+    #[test]
+    fn remove_only_the_matching_length(){
+        let mut root = Some(Box::new(BSTNode::new(20 << 24, 8, "short".to_string())));
+        root.as_mut().unwrap().insert(20 << 24, 16, "long".to_string());
+
+        let (root, removed) = BSTNode::remove(root, 20 << 24, 16);
+        assert_eq!(removed.as_deref(), Some("long"));
+        assert_eq!(octets(&root), [(20, 8)]);
+        assert_eq!(root.unwrap().lookup_route(20 << 24).unwrap().next_hop, "short");
+    }
+}
@@ -0,0 +1,312 @@
+use std::cell::{Ref, RefCell};
+use std::collections::BTreeMap;
+use std::mem::size_of;
+
+use crate::route_table::{Route, RouteTable};
+use crate::utils::prefix_mask;
+
+//widest branch any node takes, 2^24 children is already a 16M entry node array
+const MAX_BRANCH: u8 = 24;
+
+#[derive(Debug, Clone, Copy)]
+pub struct LcTrieConfig{
+    //a node branches on k bits if at least fill_factor * 2^k of its children are non-empty
+    pub fill_factor: f64,
+    //the root is forced to at least this many bits, like the 16-bit root in the paper
+    pub root_branch: u8,
+}
+
+impl LcTrieConfig{
+    pub fn new(fill_factor: f64, root_branch: u8) -> Self{
+        assert!(fill_factor > 0.0 && fill_factor <= 1.0, "fill factor must be in (0, 1], got {}", fill_factor);
+        LcTrieConfig{ fill_factor, root_branch: root_branch.clamp(1, MAX_BRANCH) }
+    }
+}
+
+//branch == 0 marks a leaf and adr points into leaves, otherwise adr is the first of 2^branch children
+#[derive(Debug, Clone, Copy)]
+struct LcNode{
+    branch: u8,
+    skip: u8,
+    adr: u32,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct LcLeaf{
+    //base vector entry stored at this leaf, None for an empty child
+    route: Option<u32>,
+    //longest route that is a proper prefix of this leaf's position
+    pre: Option<u32>,
+}
+
+//the built arrays, thrown away and rebuilt after updates
+struct LcCore{
+    nodes: Vec<LcNode>,
+    leaves: Vec<LcLeaf>,
+    //all routes sorted by (prefix, len) with pre[i] = longest proper prefix of routes[i]
+    routes: Vec<Route>,
+    pre: Vec<Option<u32>>,
+}
+
+//nilsson and karlsson's level- and path-compressed trie
+pub struct LcTrie{
+    config: LcTrieConfig,
+    table: BTreeMap<(u32, u8), String>,
+    core: RefCell<Option<LcCore>>,
+}
+
+impl LcTrie{
+    pub fn new(config: LcTrieConfig) -> Self{
+        LcTrie{
+            config,
+            table: BTreeMap::new(),
+            core: RefCell::new(None),
+        }
+    }
+
+    fn core(&self) -> Ref<'_, LcCore>{
+        if self.core.borrow().is_none(){
+            *self.core.borrow_mut() = Some(LcCore::build(&self.table, self.config));
+        }
+        Ref::map(self.core.borrow(), |c| c.as_ref().unwrap())
+    }
+
+    //nodes visited by a lookup, leaf included
+    pub fn lookup_depth(&self, ip: u32) -> usize{
+        self.core().walk(ip).1
+    }
+
+    pub fn max_depth(&self) -> usize{
+        let core = self.core();
+        if core.nodes.is_empty() { 0 } else { core.depth(0) }
+    }
+
+    pub fn size_bytes(&self) -> usize{
+        let core = self.core();
+        core.nodes.len() * size_of::<LcNode>()
+            + core.leaves.len() * size_of::<LcLeaf>()
+            + core.routes.iter().map(|r| size_of::<Route>() + r.next_hop.len()).sum::<usize>()
+    }
+}
+
+impl LcCore{
+    fn build(table: &BTreeMap<(u32, u8), String>, config: LcTrieConfig) -> Self{
+        let routes: Vec<Route> = table.iter().map(|(&(p, l), hop)| Route::new(p, l, hop.clone())).collect();
+
+        //btree order puts every prefix right before the routes it covers, so a stack finds pre
+        let mut pre = vec![None; routes.len()];
+        let mut stack: Vec<usize> = Vec::new();
+        let mut is_prefix = vec![false; routes.len()];
+        for (i, route) in routes.iter().enumerate(){
+            while let Some(&top) = stack.last(){
+                if routes[top].prefix_len < route.prefix_len && routes[top].covers(route.prefix){
+                    break;
+                }
+                stack.pop();
+            }
+            if let Some(&top) = stack.last(){
+                pre[i] = Some(top as u32);
+                is_prefix[top] = true;
+            }
+            stack.push(i);
+        }
+
+        let mut core = LcCore{
+            nodes: Vec::new(),
+            leaves: Vec::new(),
+            routes,
+            pre,
+        };
+
+        //the base vector holds the routes that are not a prefix of another one, it is prefix-free
+        let base: Vec<u32> = (0..core.routes.len()).filter(|&i| !is_prefix[i]).map(|i| i as u32).collect();
+        if base.is_empty(){
+            return core;
+        }
+        core.nodes.push(LcNode{ branch: 0, skip: 0, adr: 0 });
+        core.build_node(0, &base, 0, 0, config, true);
+        core
+    }
+
+    //fills nodes[slot] for base entries that all share the first `pos` bits of `path`
+    fn build_node(&mut self, slot: usize, base: &[u32], pos: u8, path: u32, config: LcTrieConfig, root: bool){
+        if base.len() == 1{
+            let pre = self.pre[base[0] as usize];
+            self.nodes[slot] = self.leaf(Some(base[0]), pre);
+            return;
+        }
+
+        //path compression: skip the bits every entry agrees on
+        let first = self.routes[base[0] as usize].prefix;
+        let last = self.routes[base[base.len() - 1] as usize].prefix;
+        let common = ((first ^ last) << pos).leading_zeros().min(32 - pos as u32) as u8;
+        let pos_after_skip = pos + common;
+        let path = path | (first & prefix_mask(pos_after_skip) & !prefix_mask(pos));
+
+        //level compression: grow the branch while enough children stay non-empty
+        let mut branch = 1u8;
+        if root{
+            branch = config.root_branch.clamp(1, MAX_BRANCH).min(32 - pos_after_skip);
+        }
+        while pos_after_skip + branch < 32
+            && branch < MAX_BRANCH
+            && self.filled(base, pos_after_skip, branch + 1) as f64 >= config.fill_factor * (1u64 << (branch + 1)) as f64
+        {
+            branch += 1;
+        }
+
+        let children = 1usize << branch;
+        let adr = self.nodes.len();
+        self.nodes.resize(adr + children, LcNode{ branch: 0, skip: 0, adr: 0 });
+        self.nodes[slot] = LcNode{ branch, skip: common, adr: adr as u32 };
+
+        let end = pos_after_skip + branch;
+        //children each entry lands in, entries shorter than the branch are replicated over a range
+        let spans: Vec<(usize, usize)> = base
+            .iter()
+            .map(|&i| {
+                let r = &self.routes[i as usize];
+                let bits = ((r.prefix << pos_after_skip) >> (32 - branch as u32)) as usize;
+                let free = end.saturating_sub(r.prefix_len);
+                (bits, bits | ((1usize << free) - 1))
+            })
+            .collect();
+
+        //base is sorted and prefix-free, so the spans are sorted and never overlap
+        let mut first = 0;
+        for child in 0..children{
+            while first < base.len() && spans[first].1 < child{
+                first += 1;
+            }
+            let mut last = first;
+            while last < base.len() && spans[last].0 <= child{
+                last += 1;
+            }
+
+            let child_path = path | ((child as u32) << (32 - end));
+            if first == last{
+                let pre = self.longest_covering(child_path, end);
+                self.nodes[adr + child] = self.leaf(None, pre);
+            }else{
+                let members = base[first..last].to_vec();
+                self.build_node(adr + child, &members, end, child_path, config, false);
+            }
+        }
+    }
+
+    //number of distinct child patterns a branch of `branch` bits at `pos` would see,
+    //a short entry counts once with its zero-padded bits as in the paper
+    fn filled(&self, base: &[u32], pos: u8, branch: u8) -> usize{
+        let mut covered = vec![false; 1 << branch];
+        for &i in base{
+            let bits = (self.routes[i as usize].prefix << pos) >> (32 - branch as u32);
+            covered[bits as usize] = true;
+        }
+        covered.iter().filter(|&&c| c).count()
+    }
+
+    fn longest_covering(&self, path: u32, len: u8) -> Option<u32>{
+        (0..=len)
+            .rev()
+            .find_map(|l| self.routes.binary_search_by_key(&(path & prefix_mask(l), l), |r| (r.prefix, r.prefix_len)).ok())
+            .map(|i| i as u32)
+    }
+
+    fn leaf(&mut self, route: Option<u32>, pre: Option<u32>) -> LcNode{
+        self.leaves.push(LcLeaf{ route, pre });
+        LcNode{ branch: 0, skip: 0, adr: (self.leaves.len() - 1) as u32 }
+    }
+
+    //returns the matching route index and the number of nodes visited
+    fn walk(&self, ip: u32) -> (Option<u32>, usize){
+        if self.nodes.is_empty(){
+            return (None, 0);
+        }
+
+        let mut node = self.nodes[0];
+        let mut pos = node.skip as u32;
+        let mut depth = 1;
+        while node.branch != 0{
+            let child = (ip << pos) >> (32 - node.branch as u32);
+            pos += node.branch as u32;
+            node = self.nodes[node.adr as usize + child as usize];
+            pos += node.skip as u32;
+            depth += 1;
+        }
+
+        //skipped bits were never compared, so confirm the leaf and fall back along the prefix chain
+        let leaf = self.leaves[node.adr as usize];
+        let mut candidate = leaf.route.or(leaf.pre);
+        while let Some(i) = candidate{
+            if self.routes[i as usize].covers(ip){
+                return (Some(i), depth);
+            }
+            candidate = self.pre[i as usize];
+        }
+        (None, depth)
+    }
+
+    fn depth(&self, idx: usize) -> usize{
+        let node = self.nodes[idx];
+        if node.branch == 0{
+            return 1;
+        }
+        1 + (0..1usize << node.branch).map(|c| self.depth(node.adr as usize + c)).max().unwrap_or(0)
+    }
+}
+
+impl RouteTable for LcTrie{
+    fn name(&self) -> &'static str{
+        "lc-trie"
+    }
+
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        self.table.insert((prefix & prefix_mask(prefix_len), prefix_len), next_hop);
+        self.core.replace(None);
+        Ok(())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        let removed = self.table.remove(&(prefix & prefix_mask(prefix_len), prefix_len));
+        self.core.replace(None);
+        removed
+    }
+
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        let core = self.core();
+        core.walk(ip).0.map(|i| core.routes[i as usize].clone())
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        self.table.iter().map(|(&(p, l), hop)| Route::new(p, l, hop.clone())).collect()
+    }
+
+    fn node_count(&self) -> usize{
+        self.core().nodes.len()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::route_table::reference::check_against_trie;
+
+    #[test]
+    fn matches_trie_for_each_fill_and_root(){
+        for (fill, root) in [(1.0, 1), (0.5, 1), (0.5, 16), (0.25, 16)]{
+            check_against_trie(&mut LcTrie::new(LcTrieConfig::new(fill, root)), 3_000, 34);
+        }
+    }
+
+    #[test]
+    fn root_branch_is_capped(){
+        assert_eq!(LcTrieConfig::new(0.5, 32).root_branch, MAX_BRANCH);
+        assert_eq!(LcTrieConfig::new(0.5, 0).root_branch, 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "fill factor")]
+    fn zero_fill_factor_is_rejected(){
+        LcTrieConfig::new(0.0, 16);
+    }
+}
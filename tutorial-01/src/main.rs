@@ -1,17 +1,70 @@
 mod utils;
 mod ip_bst;
 mod ip_bin_trie;
+mod route_table;
+mod route_gen;
+mod route_cache;
+mod bloom_lpm;
+mod tcam;
+mod classifier;
+mod hicuts;
+mod range_expand;
+mod lc_trie;
+mod poptrie;
+mod rpki;
+mod route_diff;
+mod route_counters;
+mod prefix_map;
+mod next_hop;
+mod shell;
 
+use std::env;
+use std::fs;
+use std::mem::size_of;
 use std::time::Instant;
-use utils::{ip_to_u32, u32_to_ip};
+use utils::{ip_to_u32, prefix_mask, u32_to_ip, Rng};
 use ip_bst::BSTNode;
 use ip_bin_trie::TrieNode;
+use route_gen::{generate_routes, generate_trace, nested_count, prefix_len_histogram, RouteGenConfig};
+use route_cache::{CachePolicy, RouteCache};
+use bloom_lpm::BloomLpm;
+use tcam::{Tcam, TcamLayout};
+use classifier::{
+    format_classbench, generate_packets, generate_rules, linear_classify, parse_classbench, HierTrie, PacketHeader, Rule,
+};
+use hicuts::{HiCuts, HiCutsConfig};
+use lc_trie::{LcTrie, LcTrieConfig};
+use range_expand::{classify_ternary, encode_range, expand_rules, RangeEncoding};
+use route_table::{backend_by_name, Route, RouteTable, BACKEND_NAMES};
+use poptrie::Poptrie;
+use next_hop::{NextHop, Rib};
+use prefix_map::{AsnInfo, PrefixDb, PrefixMap};
+use route_counters::{top_n, CounterOrder};
+use route_diff::{changed_addresses, diff_routes, forwarding_changes};
+use rpki::{install_routes, BgpRoute, Validity, Vrp, VrpTable};
 
 fn main() {
+    //`cargo run -- shell [backend]` opens the interactive route table instead
+    let args: Vec<String> = env::args().collect();
+    if args.get(1).map(String::as_str) == Some("shell") {
+        shell::run(args.get(2).map_or("trie", String::as_str));
+        return;
+    }
+    //`cargo run --release -- hicuts <classbench file> [binth] [spfac]`
+    if args.get(1).map(String::as_str) == Some("hicuts") {
+        hicuts_file(&args[2..]);
+        return;
+    }
+    //`cargo run -- asn <file.csv|file.pmdb> <ip>...`
+    if args.get(1).map(String::as_str) == Some("asn") {
+        asn_file(&args[2..]);
+        return;
+    }
+
     println!("ip lookup\n");
 
     //base table
-    let mut routes = vec![
+    let routes = [
         ("192.168.0.0", 16, "Router_A"),
         ("192.168.1.0", 24, "Router_B"),
         ("192.168.1.128", 25, "Router_C"),
@@ -19,24 +72,8 @@ fn main() {
         ("172.16.0.0", 12, "Router_E"),
     ];
 
-    let mut generated_routes: Vec<(String, u8, String)> = vec![];
-    for i in 0..100 {
-        generated_routes.push((
-            format!("10.{}.0.0", i),
-            16,
-            format!("Router_{}", i + 100),
-        ));
-        generated_routes.push((
-            format!("172.{}.0.0", (i % 240) + 16),
-            20,
-            format!("Router_{}", i + 200),
-        ));
-        generated_routes.push((
-            format!("192.168.{}.0", i % 256),
-            24,
-            format!("Router_{}", i + 300),
-        ));
-    }
+    //seeded so every run builds the same table
+    let generated_routes = generate_routes(&RouteGenConfig::new(300, 42));
     
     println!("Total routes: {} (base) + {} (generated) = {}", routes.len(), generated_routes.len(), routes.len() + generated_routes.len());
 
@@ -62,17 +99,23 @@ fn main() {
         trie_root.insert(prefix_ip, *len, hop.to_string());
     }
 
-    for (prefix, len, hop) in &generated_routes {
-        let prefix_ip = ip_to_u32(prefix);
-        bst_root.insert(prefix_ip, *len, hop.to_string());
-        trie_root.insert(prefix_ip, *len, hop.to_string());
+    for route in &generated_routes {
+        bst_root.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        trie_root.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+
+    println!("\nGenerated prefix lengths ({} nested):", nested_count(&generated_routes));
+    for (len, count) in prefix_len_histogram(&generated_routes).iter().enumerate() {
+        if *count > 0 {
+            println!("  /{:<2} {:>4} {}", len, count, "#".repeat(count.div_ceil(4)));
+        }
     }
     
 
     //test lookups
     println!("\nLookup Tests:");
 
-    let test_ips = vec![
+    let test_ips = [
         "192.168.1.5",
         "192.168.1.200",
         "10.5.10.1",
@@ -130,4 +173,865 @@ fn main() {
             "(BST is faster)"
         }
     );
-}
\ No newline at end of file
+
+    route_cache_demo();
+    bloom_lpm_demo();
+    tcam_demo();
+    classifier_demo();
+    hicuts_demo();
+    range_expansion_demo();
+    lc_trie_demo();
+    poptrie_demo();
+    rpki_demo();
+    route_diff_demo();
+    route_counters_demo();
+    prefix_map_demo();
+    next_hop_demo();
+}
+
+fn route_cache_demo() {
+    println!("\nRoute cache - hit rate on a zipf trace");
+
+    let routes = generate_routes(&RouteGenConfig::new(10_000, 7));
+    let trace = generate_trace(&routes, 200_000, 1.0, 7);
+    println!("{} routes, {} lookups\n", routes.len(), trace.len());
+
+    println!("{:>6} {:>10} {:>10}", "size", "lru", "clock");
+    for size in [64, 256, 1024, 4096] {
+        let mut rates = vec![];
+        for policy in [CachePolicy::Lru, CachePolicy::Clock] {
+            let mut table = TrieNode::new();
+            for route in &routes {
+                table.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+            }
+            let mut cache = RouteCache::new(table, size, policy);
+            rates.push(cache.run_trace(&trace).hit_rate() * 100.0);
+        }
+        println!("{:>6} {:>9.2}% {:>9.2}%", size, rates[0], rates[1]);
+    }
+
+    //churn the table under a warm cache, see how much of it survives
+    let mut table = TrieNode::new();
+    for route in &routes {
+        table.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+    let mut cache = RouteCache::new(table, 1024, CachePolicy::Lru);
+    cache.run_trace(&trace[..50_000]);
+
+    for route in generate_routes(&RouteGenConfig::new(1_000, 8)) {
+        cache.insert(route.prefix, route.prefix_len, route.next_hop).expect("trie insert");
+    }
+    for route in routes.iter().step_by(20) {
+        cache.remove(route.prefix, route.prefix_len);
+    }
+    let cached = cache.len();
+    let after = cache.run_trace(&trace[50_000..100_000]);
+    println!(
+        "\nAfter churn: {} routes, {} entries cached, {} invalidated, {:.2}% hit rate on the rest of the trace",
+        cache.table().routes().len(),
+        cached,
+        cache.stats.invalidations,
+        after.hit_rate() * 100.0
+    );
+}
+
+fn bloom_lpm_demo() {
+    println!("\nBloom filter assisted LPM vs binary trie");
+
+    let routes = generate_routes(&RouteGenConfig::new(10_000, 7));
+    //half the trace hits the table, the other half is mostly misses
+    let mut rng = Rng::new(11);
+    let mut trace = generate_trace(&routes, 100_000, 0.0, 11);
+    trace.extend((0..100_000).map(|_| rng.next_u32()));
+
+    let mut trie = TrieNode::new();
+    for route in &routes {
+        trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+    let trie_bytes = trie.node_count() * size_of::<TrieNode>()
+        + routes.iter().map(|r| r.next_hop.len()).sum::<usize>();
+
+    let start = Instant::now();
+    for &ip in &trace {
+        let _ = trie.lookup_route(ip);
+    }
+    let trie_time = start.elapsed();
+
+    println!("{} routes, {} lookups", routes.len(), trace.len());
+    println!("trie: {} nodes, {} KiB, {:.3} ms\n", trie.node_count(), trie_bytes / 1024, trie_time.as_secs_f64() * 1000.0);
+
+    println!(
+        "{:>9} {:>3} {:>14} {:>14} {:>13} {:>10} {:>10} {:>10}",
+        "bits/pfx", "k", "maybe/lookup", "probes/lookup", "extra/lookup", "filt KiB", "tbl KiB", "time ms"
+    );
+    for (bits, hashes) in [(4, 3), (8, 6), (16, 11)] {
+        let mut bloom = BloomLpm::new(bits, hashes);
+        for route in &routes {
+            bloom.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("bloom insert");
+        }
+
+        let start = Instant::now();
+        for &ip in &trace {
+            let _ = bloom.lookup_route(ip);
+        }
+        let bloom_time = start.elapsed();
+
+        let stats = bloom.stats();
+        println!(
+            "{:>9} {:>3} {:>14.3} {:>14.3} {:>13.4} {:>10} {:>10} {:>10.3}",
+            bits,
+            hashes,
+            stats.filter_matches as f64 / stats.lookups as f64,
+            stats.table_probes as f64 / stats.lookups as f64,
+            stats.false_probes as f64 / stats.lookups as f64,
+            bloom.filter_bytes() / 1024,
+            bloom.table_bytes() / 1024,
+            bloom_time.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+fn tcam_demo() {
+    println!("\nTCAM update cost - naive vs prefix-length ordered (Shah-Gupta)");
+
+    let routes = generate_routes(&RouteGenConfig::new(10_000, 7));
+    let updates = generate_routes(&RouteGenConfig::new(2_000, 9));
+
+    println!(
+        "{:>10} {:>10} {:>14} {:>15} {:>10}",
+        "layout", "entries", "load moves", "update moves", "max"
+    );
+    for layout in [TcamLayout::Naive, TcamLayout::PrefixLengthOrdered] {
+        let mut tcam = Tcam::new(16_384, layout);
+        for route in &routes {
+            tcam.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("tcam sized for the table");
+        }
+        let load = tcam.stats;
+
+        //announce then withdraw a second batch on the loaded table
+        for route in &updates {
+            tcam.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("tcam sized for the table");
+        }
+        for route in &updates {
+            tcam.remove(route.prefix, route.prefix_len);
+        }
+        let churn_ops = tcam.stats.inserts + tcam.stats.deletes - load.inserts - load.deletes;
+        let churn_moves = tcam.stats.moves - load.moves;
+        println!(
+            "{:>10} {:>4}/{:<5} {:>14.1} {:>15.1} {:>10}",
+            tcam.name(),
+            tcam.len(),
+            tcam.capacity(),
+            load.moves as f64 / load.inserts as f64,
+            churn_moves as f64 / churn_ops as f64,
+            tcam.stats.max_moves
+        );
+    }
+    println!("(moves are averaged per insert while loading, and per insert/delete during churn)");
+}
+
+fn classifier_demo() {
+    println!("\nFive-tuple classification - hierarchical trie vs linear search");
+
+    let rules = generate_rules(1_000, 3);
+    let packets = generate_packets(&rules, 100_000, 3);
+    let trie = HierTrie::build(&rules);
+    let (src_nodes, dst_nodes) = trie.node_count();
+    println!("{} rules, {} src trie nodes, {} dst trie nodes", trie.rule_count(), src_nodes, dst_nodes);
+
+    let start = Instant::now();
+    for pkt in &packets {
+        let _ = linear_classify(&rules, pkt);
+    }
+    let linear_time = start.elapsed();
+
+    let start = Instant::now();
+    let actual: Vec<_> = packets.iter().map(|p| trie.classify(p)).collect();
+    let trie_time = start.elapsed();
+
+    let matched = actual.iter().filter(|r| r.is_some()).count();
+    println!("{} packets, {} matched a rule", packets.len(), matched);
+    println!("Linear Time: {:.3} ms", linear_time.as_secs_f64() * 1000.0);
+    println!("Trie   Time: {:.3} ms", trie_time.as_secs_f64() * 1000.0);
+    if let Some(rule) = actual[0] {
+        println!("e.g. {}\n  -> {}", packets[0], rule);
+    }
+}
+
+fn hicuts_file(args: &[String]) {
+    let Some(path) = args.first() else {
+        println!("usage: hicuts <classbench file> [binth] [spfac]");
+        return;
+    };
+    let binth = args.get(1).and_then(|b| b.parse().ok()).unwrap_or(16);
+    let spfac = args.get(2).and_then(|s| s.parse().ok()).unwrap_or(4.0);
+
+    let rules = match fs::read_to_string(path).map_err(|e| e.to_string()).and_then(|text| parse_classbench(&text)) {
+        Ok(rules) => rules,
+        Err(e) => {
+            println!("cannot load {}: {}", path, e);
+            return;
+        }
+    };
+    if rules.is_empty() {
+        println!("no rules in {}, nothing to classify", path);
+        return;
+    }
+    println!("{} rules from {}", rules.len(), path);
+
+    let packets = generate_packets(&rules, 100_000, 5);
+    print_hicuts_header();
+    print_hicuts_row(&rules, &packets, HiCutsConfig::new(binth, spfac));
+}
+
+fn hicuts_demo() {
+    println!("\nHiCuts decision tree");
+
+    //round trip through the classbench text format, the same path a rule file takes
+    let path = env::temp_dir().join("nsd_hicuts_rules.txt");
+    fs::write(&path, format_classbench(&generate_rules(2_000, 4))).expect("write rule file");
+    let rules = parse_classbench(&fs::read_to_string(&path).expect("read rule file")).expect("parse rule file");
+    let packets = generate_packets(&rules, 100_000, 4);
+    println!("{} rules parsed from {}, {} packets", rules.len(), path.display(), packets.len());
+
+    print_hicuts_header();
+    for (binth, spfac) in [(8, 2.0), (16, 2.0), (16, 4.0), (32, 8.0)] {
+        print_hicuts_row(&rules, &packets, HiCutsConfig::new(binth, spfac));
+    }
+}
+
+fn print_hicuts_header() {
+    println!(
+        "{:>6} {:>6} {:>8} {:>10} {:>10} {:>10} {:>12}",
+        "binth", "spfac", "nodes", "max depth", "avg depth", "KiB", "Mlookups/s"
+    );
+}
+
+fn print_hicuts_row(rules: &[Rule], packets: &[PacketHeader], config: HiCutsConfig) {
+    let tree = HiCuts::build(rules, config);
+    let stats = tree.stats();
+
+    let start = Instant::now();
+    for pkt in packets {
+        let _ = tree.classify(pkt);
+    }
+    let elapsed = start.elapsed();
+    println!(
+        "{:>6} {:>6.1} {:>8} {:>10} {:>10.2} {:>10} {:>12.2}",
+        config.binth,
+        config.spfac,
+        stats.nodes,
+        stats.max_depth,
+        stats.avg_depth,
+        stats.bytes / 1024,
+        packets.len() as f64 / elapsed.as_secs_f64() / 1e6
+    );
+}
+
+fn range_expansion_demo() {
+    println!("\nPort range to prefix expansion");
+
+    let encodings = [RangeEncoding::Prefix, RangeEncoding::Dirpe];
+    println!("{:>14} {:>8} {:>6}", "range", "prefix", "dirpe");
+    for (lo, hi) in [(80, 80), (0, 1023), (1024, 65535), (6000, 6063), (1000, 2000), (1, 65534)] {
+        let counts: Vec<_> = encodings.iter().map(|&e| encode_range(lo, hi, 16, e).len()).collect();
+        println!("{:>14} {:>8} {:>6}", format!("{}-{}", lo, hi), counts[0], counts[1]);
+    }
+    //three fence bits per 2-bit chunk, the low chunk on the right
+    println!("dirpe entries for 1-14 on 4 bits:");
+    for entry in encode_range(1, 14, 4, RangeEncoding::Dirpe) {
+        println!("  {}", entry.fmt_bits(RangeEncoding::Dirpe.key_bits(4) as u8));
+    }
+
+    let mut rule_sets = vec![("generated", generate_rules(1_000, 6))];
+    if let Ok(text) = fs::read_to_string("rules/acl_sample.rules") {
+        rule_sets.push(("acl_sample", parse_classbench(&text).expect("parse sample rules")));
+    }
+
+    println!(
+        "\n{:>12} {:>8} {:>10} {:>8} {:>9} {:>9} {:>10} {:>9}",
+        "rule set", "rules", "encoding", "entries", "factor", "key bits", "tcam kbit", "time ms"
+    );
+    for (name, rules) in &rule_sets {
+        let packets = generate_packets(rules, 20_000, 6);
+        for encoding in encodings {
+            let entries = expand_rules(rules, encoding);
+            //a linear scan stands in for the parallel tcam search
+            let start = Instant::now();
+            for pkt in &packets {
+                let _ = classify_ternary(&entries, pkt, encoding);
+            }
+            let time = start.elapsed();
+            //addresses and protocol go in as they are, only the ports get re-encoded
+            let key_bits = 32 + 32 + 8 + 2 * encoding.key_bits(16);
+            println!(
+                "{:>12} {:>8} {:>10} {:>8} {:>9.2} {:>9} {:>10.1} {:>9.3}",
+                name,
+                rules.len(),
+                encoding,
+                entries.len(),
+                entries.len() as f64 / rules.len() as f64,
+                key_bits,
+                (entries.len() * key_bits as usize) as f64 / 1000.0,
+                time.as_secs_f64() * 1000.0
+            );
+        }
+    }
+}
+
+fn lc_trie_demo() {
+    println!("\nLC-trie vs binary trie");
+
+    let routes = generate_routes(&RouteGenConfig::new(50_000, 7));
+    let mut rng = Rng::new(13);
+    let mut trace = generate_trace(&routes, 200_000, 0.0, 13);
+    trace.extend((0..100_000).map(|_| rng.next_u32()));
+
+    let mut trie = TrieNode::new();
+    for route in &routes {
+        trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+    let trie_depths: Vec<usize> = trace.iter().map(|&ip| trie.lookup_depth(ip)).collect();
+    let trie_depth = trie_depths.iter().sum::<usize>() as f64 / trace.len() as f64;
+
+    let start = Instant::now();
+    for &ip in &trace {
+        let _ = trie.lookup_route(ip);
+    }
+    let trie_time = start.elapsed();
+
+    println!("{} routes, {} lookups", routes.len(), trace.len());
+    println!(
+        "{:>12} {:>6} {:>9} {:>10} {:>10} {:>9} {:>9}",
+        "structure", "fill", "nodes", "avg depth", "max depth", "KiB", "time ms"
+    );
+    println!(
+        "{:>12} {:>6} {:>9} {:>10.2} {:>10} {:>9} {:>9.3}",
+        "trie",
+        "-",
+        trie.node_count(),
+        trie_depth,
+        trie_depths.iter().max().unwrap_or(&0),
+        trie.node_count() * size_of::<TrieNode>() / 1024,
+        trie_time.as_secs_f64() * 1000.0
+    );
+
+    for (fill, root) in [(1.0, 1), (0.5, 1), (0.5, 16), (0.25, 16)] {
+        let mut lc = LcTrie::new(LcTrieConfig::new(fill, root));
+        for route in &routes {
+            lc.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("lc-trie insert");
+        }
+        let depth = trace.iter().map(|&ip| lc.lookup_depth(ip)).sum::<usize>() as f64 / trace.len() as f64;
+
+        let start = Instant::now();
+        for &ip in &trace {
+            let _ = lc.lookup_route(ip);
+        }
+        let lc_time = start.elapsed();
+
+        println!(
+            "{:>12} {:>6.2} {:>9} {:>10.2} {:>10} {:>9} {:>9.3}",
+            format!("lc-trie/{}", root),
+            fill,
+            lc.node_count(),
+            depth,
+            lc.max_depth(),
+            lc.size_bytes() / 1024,
+            lc_time.as_secs_f64() * 1000.0
+        );
+    }
+}
+
+fn poptrie_demo() {
+    println!("\nPoptrie vs the other backends");
+
+    let routes = generate_routes(&RouteGenConfig::new(50_000, 7));
+    let mut rng = Rng::new(17);
+    let mut trace = generate_trace(&routes, 500_000, 0.0, 17);
+    trace.extend((0..500_000).map(|_| rng.next_u32()));
+
+    println!("{} routes, {} lookups", routes.len(), trace.len());
+
+    println!("{:>10} {:>10} {:>12}", "backend", "build ms", "Mlookups/s");
+    //the naive tcam shifts half the table on every insert, its update cost is covered above
+    for name in BACKEND_NAMES.iter().filter(|&&n| n != "tcam") {
+        let mut table = backend_by_name(name).expect("known backend");
+        //linear structures only get a slice of the trace
+        let sample = match *name {
+            "bst" | "tcam-plo" => &trace[..2_000],
+            _ => &trace[..],
+        };
+
+        let start = Instant::now();
+        for route in &routes {
+            table.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("backend insert");
+        }
+        let _ = table.lookup_route(0);
+        let build = start.elapsed();
+
+        let start = Instant::now();
+        for &ip in sample {
+            let _ = table.lookup_route(ip);
+        }
+        let rate = sample.len() as f64 / start.elapsed().as_secs_f64() / 1e6;
+        println!("{:>10} {:>10.1} {:>12.3}", name, build.as_secs_f64() * 1000.0, rate);
+    }
+
+    //route ids only, the hot path without cloning the matched route
+    let mut poptrie = Poptrie::new(18);
+    for route in &routes {
+        poptrie.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("poptrie insert");
+    }
+    let start = Instant::now();
+    let mut found = 0u64;
+    for &ip in &trace {
+        found += poptrie.lookup_id(ip) as u64;
+    }
+    let rate = trace.len() as f64 / start.elapsed().as_secs_f64() / 1e6;
+    println!("poptrie lookup_id: {:.2} Mlookups/s (checksum {})", rate, found);
+
+    //incremental updates: announce and withdraw a second batch
+    let updates = generate_routes(&RouteGenConfig::new(5_000, 18));
+    let start = Instant::now();
+    for route in &updates {
+        poptrie.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("poptrie insert");
+    }
+    for route in updates.iter().step_by(2) {
+        poptrie.remove(route.prefix, route.prefix_len);
+    }
+    let per_update = start.elapsed().as_secs_f64() * 1e6 / (updates.len() + updates.len().div_ceil(2)) as f64;
+    println!(
+        "after {} updates: {:.1} us/update, {} live nodes, {} KiB",
+        updates.len() + updates.len().div_ceil(2),
+        per_update,
+        poptrie.node_count(),
+        poptrie.size_bytes() / 1024
+    );
+}
+
+fn rpki_demo() {
+    println!("\nRPKI route origin validation");
+
+    let routes = generate_routes(&RouteGenConfig::new(20_000, 7));
+    let mut rng = Rng::new(21);
+    let origins: Vec<u32> = routes.iter().map(|_| 64_512 + rng.below(1_000) as u32).collect();
+
+    //roas for ~60% of the routes, some with room for more-specifics
+    let mut vrps = vec![];
+    for (route, &asn) in routes.iter().zip(&origins) {
+        if rng.below(10) < 6 {
+            let max_len = if rng.below(4) == 0 { (route.prefix_len + 2).min(24) } else { route.prefix_len };
+            vrps.push(Vrp { prefix: route.prefix, prefix_len: route.prefix_len, max_len, asn });
+        }
+    }
+
+    //split the export over the two formats a validator can produce
+    let (csv_part, json_part) = vrps.split_at(vrps.len() / 2);
+    //real exports mix in ipv6 vrps, which the ipv4 table skips
+    let csv: String = ["ASN,IP Prefix,Max Length,Trust Anchor".to_string(), "AS13335,2606:4700::/32,48,demo".to_string()]
+        .into_iter()
+        .chain(csv_part.iter().map(|v| format!("AS{},{}/{},{},demo", v.asn, u32_to_ip(v.prefix), v.prefix_len, v.max_len)))
+        .collect::<Vec<_>>()
+        .join("\n");
+    let json = format!(
+        "{{\"roas\": [\n{}\n]}}",
+        json_part
+            .iter()
+            .map(|v| format!(
+                "  {{\"asn\": \"AS{}\", \"prefix\": \"{}/{}\", \"maxLength\": {}, \"ta\": \"demo\"}}",
+                v.asn,
+                u32_to_ip(v.prefix),
+                v.prefix_len,
+                v.max_len
+            ))
+            .collect::<Vec<_>>()
+            .join(",\n")
+    );
+    let csv_path = env::temp_dir().join("nsd_vrps.csv");
+    let json_path = env::temp_dir().join("nsd_vrps.json");
+    fs::write(&csv_path, csv).expect("write vrp csv");
+    fs::write(&json_path, json).expect("write vrp json");
+
+    let mut table = VrpTable::new();
+    let from_csv = table.load_csv(&fs::read_to_string(&csv_path).expect("read vrp csv")).expect("parse vrp csv");
+    let from_json = table.load_json(&fs::read_to_string(&json_path).expect("read vrp json")).expect("parse vrp json");
+    println!(
+        "{} vrps ({} from csv, {} from json, {} duplicates, {} ipv6 skipped)",
+        table.len(),
+        from_csv.added,
+        from_json.added,
+        from_csv.duplicates + from_json.duplicates,
+        from_csv.skipped + from_json.skipped
+    );
+
+    //announcements: 3% come from the wrong origin
+    let announced: Vec<BgpRoute> = routes
+        .iter()
+        .zip(&origins)
+        .map(|(route, &asn)| {
+            let origin = if rng.below(100) < 3 { 64_512 + rng.below(1_000) as u32 } else { asn };
+            table.annotate(route.clone(), origin)
+        })
+        .collect();
+
+    for drop_invalid in [false, true] {
+        let mut fib = TrieNode::new();
+        let summary = install_routes(&mut fib, &announced, drop_invalid);
+        println!(
+            "drop invalid {:<5}: {} valid, {} invalid, {} not-found, {} installed, {} rejected",
+            drop_invalid, summary.valid, summary.invalid, summary.not_found, summary.installed, summary.rejected
+        );
+    }
+
+    if let Some(bad) = announced.iter().find(|r| r.validity == Validity::Invalid) {
+        println!("e.g. {}", bad);
+        for vrp in table.covering(bad.route.prefix, bad.route.prefix_len) {
+            println!("  covered by {}", vrp);
+        }
+    }
+}
+
+fn route_diff_demo() {
+    println!("\nRouting table diff between two snapshots");
+
+    let before = generate_routes(&RouteGenConfig::new(20_000, 7));
+    //second snapshot: drop some routes, move some next hops, add a fresh batch
+    let mut rng = Rng::new(23);
+    let mut after: Vec<Route> = vec![];
+    for r in &before {
+        if rng.below(100) < 2 {
+            continue;
+        }
+        let hop = if rng.below(100) < 3 { format!("Router_{}", rng.below(16)) } else { r.next_hop.clone() };
+        after.push(Route::new(r.prefix, r.prefix_len, hop));
+    }
+    after.extend(generate_routes(&RouteGenConfig::new(500, 24)));
+
+    let diff = diff_routes(&before, &after);
+    println!(
+        "{} -> {} routes: {} added, {} removed, {} next hop changed",
+        before.len(),
+        after.len(),
+        diff.added.len(),
+        diff.removed.len(),
+        diff.changed.len()
+    );
+
+    let (mut old_trie, mut new_trie) = (TrieNode::new(), TrieNode::new());
+    for route in &before {
+        old_trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+    for route in &after {
+        new_trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+    }
+    let blocks = forwarding_changes(&old_trie, &new_trie);
+    let changed = changed_addresses(&blocks);
+    println!(
+        "forwarding changed in {} blocks, {} addresses ({:.4}% of the ipv4 space)",
+        blocks.len(),
+        changed,
+        changed as f64 / (1u64 << 32) as f64 * 100.0
+    );
+    for block in blocks.iter().take(5) {
+        println!("  {}", block);
+    }
+}
+
+fn route_counters_demo() {
+    println!("\nPer-prefix packet and byte counters");
+
+    let routes = generate_routes(&RouteGenConfig::new(10_000, 7));
+    let trace = generate_trace(&routes, 200_000, 1.1, 29);
+    //imix-like packet sizes
+    let mut rng = Rng::new(29);
+    let sizes: Vec<u32> = trace.iter().map(|_| [64, 64, 64, 64, 64, 64, 64, 576, 576, 576, 1500][rng.below(11) as usize]).collect();
+
+    let mut tables: Vec<Box<dyn RouteTable>> =
+        vec![backend_by_name("trie").expect("trie"), backend_by_name("tcam-plo").expect("tcam-plo")];
+    for table in tables.iter_mut() {
+        for route in &routes {
+            table.insert(route.prefix, route.prefix_len, route.next_hop.clone()).expect("backend insert");
+        }
+        for (&ip, &len) in trace.iter().zip(&sizes) {
+            table.lookup_counted(ip, len);
+        }
+    }
+
+    let mut trie_counts = tables[0].counters();
+    let mut tcam_counts = tables[1].counters();
+    trie_counts.sort_by_key(|(r, _)| (r.prefix, r.prefix_len));
+    tcam_counts.sort_by_key(|(r, _)| (r.prefix, r.prefix_len));
+    let total: u64 = trie_counts.iter().map(|(_, c)| c.packets).sum();
+    println!(
+        "{} packets hit {} prefixes, trie and tcam counters agree: {}",
+        total,
+        trie_counts.len(),
+        trie_counts == tcam_counts
+    );
+
+    println!("top 5 prefixes by bytes:");
+    for (route, counters) in top_n(trie_counts, 5, CounterOrder::Bytes) {
+        println!("  {:<32} {:>8} pkts {:>10} bytes", route.to_string(), counters.packets, counters.bytes);
+    }
+
+    tables[0].reset_counters();
+    println!("after reset: {} prefixes with counters", tables[0].counters().len());
+}
+
+fn prefix_map_demo() {
+    println!("\nPrefix metadata database (ip to asn/org/country)");
+
+    //a few hundred organisations announcing 20k prefixes, some names need quoting
+    let routes = generate_routes(&RouteGenConfig::new(20_000, 7));
+    let countries = ["US", "DE", "IN", "BR", "JP", "GB", "NL", "SG", "ZA", "AU"];
+    let mut rng = Rng::new(39);
+    let mut csv = String::from("network,asn,organization,country\n");
+    for route in &routes {
+        let asn = 64_512 + rng.below(300) as u32;
+        let org = if asn.is_multiple_of(7) { format!("\"Transit {}, Inc.\"", asn) } else { format!("Net{} Ltd", asn) };
+        let country = countries[asn as usize % countries.len()];
+        csv.push_str(&format!("{}/{},AS{},{},{}\n", u32_to_ip(route.prefix), route.prefix_len, asn, org, country));
+    }
+    let csv_path = env::temp_dir().join("nsd_asn.csv");
+    let db_path = env::temp_dir().join("nsd_asn.pmdb");
+    fs::write(&csv_path, &csv).expect("write asn csv");
+
+    let mut map = PrefixMap::new();
+    let loaded = match fs::read_to_string(&csv_path).map_err(|e| e.to_string()).and_then(|text| map.load_csv(&text)) {
+        Ok(rows) => rows,
+        Err(e) => {
+            println!("cannot load {}: {}", csv_path.display(), e);
+            return;
+        }
+    };
+    println!("loaded {} rows into {} prefixes ({} trie nodes)", loaded, map.len(), map.node_count());
+
+    //a transfer and a withdrawal before compiling the file
+    let (prefix, len, _) = map.entries()[0];
+    let old = map.insert(prefix, len, AsnInfo { asn: 64_999, org: "Acquirer Corp".to_string(), country: "FR".to_string() });
+    println!(
+        "  {}/{} moved from {} to {}",
+        u32_to_ip(prefix),
+        len,
+        old.expect("existing prefix"),
+        map.get(prefix, len).expect("just inserted")
+    );
+    let (prefix, len, _) = map.entries()[1];
+    println!("  {}/{} withdrawn from {}", u32_to_ip(prefix), len, map.remove(prefix, len).expect("existing prefix"));
+
+    map.write_file(&db_path).expect("write prefix db");
+    let db: PrefixDb<AsnInfo> = PrefixDb::open(&db_path).expect("open prefix db");
+    println!(
+        "csv {} bytes -> db {} bytes ({:.1} bytes/prefix, {} tree nodes)",
+        csv.len(),
+        db.size_bytes(),
+        db.size_bytes() as f64 / db.len() as f64,
+        db.node_count()
+    );
+    //prefixes entirely covered by more specifics answer nothing and are not in the file
+    let listed = db.entries();
+    println!(
+        "{} of {} prefixes kept in the file, {} listed back, all as in the map: {}",
+        db.len(),
+        map.len(),
+        listed.len(),
+        listed.iter().all(|(p, l, v)| map.get(*p, *l) == Some(v))
+    );
+
+    for &(prefix, len, _) in map.entries().iter().step_by(5_000) {
+        let ip = prefix | (!prefix_mask(len) & 0x0000_0123);
+        match db.lookup(ip) {
+            Some((p, l, info)) => println!("  {:<16} -> {:<18} {}", u32_to_ip(ip), format!("{}/{}", u32_to_ip(p), l), info),
+            None => println!("  {:<16} -> no match", u32_to_ip(ip)),
+        }
+    }
+
+    //the file must answer exactly like the in-memory trie, prefix included
+    let mut addrs = generate_trace(&routes, 100_000, 1.0, 41);
+    addrs.extend((0..100_000).map(|_| rng.next_u32()));
+    let mismatches = addrs
+        .iter()
+        .filter(|&&ip| {
+            let expected = map.lookup(ip).map(|(p, l, v)| (p, l, v.clone()));
+            db.lookup(ip) != expected
+        })
+        .count();
+
+    let start = Instant::now();
+    let map_hits = addrs.iter().filter(|&&ip| map.lookup(ip).is_some()).count();
+    let map_time = start.elapsed();
+    let start = Instant::now();
+    let db_hits = addrs.iter().filter(|&&ip| db.lookup(ip).is_some()).count();
+    let db_time = start.elapsed();
+    println!(
+        "{} lookups, {} mismatches, trie {} hits in {:.3} ms, db {} hits in {:.3} ms",
+        addrs.len(),
+        mismatches,
+        map_hits,
+        map_time.as_secs_f64() * 1000.0,
+        db_hits,
+        db_time.as_secs_f64() * 1000.0
+    );
+}
+
+fn asn_file(args: &[String]) {
+    let Some(path) = args.first() else {
+        println!("usage: asn <file.csv|file.pmdb> <ip>...");
+        return;
+    };
+
+    //csv is loaded into a trie and compiled, a .pmdb file is read as is
+    let db = if path.ends_with(".csv") {
+        let mut map = PrefixMap::new();
+        fs::read_to_string(path)
+            .map_err(|e| e.to_string())
+            .and_then(|text| map.load_csv(&text))
+            .and_then(|_| PrefixDb::from_bytes(map.to_bytes()))
+    } else {
+        PrefixDb::open(path)
+    };
+    let db: PrefixDb<AsnInfo> = match db {
+        Ok(db) => db,
+        Err(e) => {
+            println!("{}: {}", path, e);
+            return;
+        }
+    };
+
+    for arg in &args[1..] {
+        match utils::parse_ip(arg).map(|ip| db.lookup(ip)) {
+            Some(Some((prefix, len, info))) => println!("{} -> {}/{} {}", arg, u32_to_ip(prefix), len, info),
+            Some(None) => println!("{} -> no match", arg),
+            None => println!("{}: bad address", arg),
+        }
+    }
+}
+
+fn next_hop_demo() {
+    println!("\nRecursive next-hop resolution");
+
+    let p = |s: &str| utils::parse_prefix(s).expect("demo prefix");
+    let show = |rib: &Rib, changed: &[(u32, u8)]| {
+        for &(prefix, len) in changed {
+            let hop = rib.next_hop(prefix, len).map_or("withdrawn".to_string(), |h| h.to_string());
+            match rib.resolution(prefix, len) {
+                Some(Ok(res)) => println!("  {:<18} {:<22} => {}", format!("{}/{}", u32_to_ip(prefix), len), hop, res),
+                Some(Err(e)) => println!("  {:<18} {:<22} => {}", format!("{}/{}", u32_to_ip(prefix), len), hop, e),
+                None => println!("  {:<18} {}", format!("{}/{}", u32_to_ip(prefix), len), hop),
+            }
+        }
+    };
+
+    let mut rib = Rib::new();
+    rib.add_interface("eth0", ip_to_u32("10.0.0.1"), 24);
+    rib.add_interface("eth1", ip_to_u32("192.168.1.1"), 30);
+    let config = [
+        ("0.0.0.0/0", "192.168.1.2"),
+        ("172.16.0.0/16", "10.0.0.2"),
+        ("203.0.113.0/24", "172.16.5.5"),
+        ("198.51.100.0/24", "203.0.113.9"),
+        ("100.64.0.0/10", "dev eth1"),
+        ("192.0.2.0/25", "192.0.2.200"),
+        ("192.0.2.128/25", "192.0.2.10"),
+    ];
+    for (prefix, hop) in config {
+        let (prefix, len) = p(prefix);
+        rib.insert(prefix, len, NextHop::parse(hop));
+    }
+    let all: Vec<(u32, u8)> = rib.entries().iter().map(|&(p, l, _, _)| (p, l)).collect();
+    show(&rib, &all);
+
+    let (more_specific, len) = p("172.16.5.0/24");
+    println!("add 172.16.5.0/24 via 192.168.1.2:");
+    let changed = rib.insert(more_specific, len, NextHop::parse("192.168.1.2"));
+    show(&rib, &changed);
+    println!("eth1 down:");
+    let changed = rib.set_interface("eth1", false);
+    show(&rib, &changed);
+    println!("eth1 up, 172.16.5.0/24 withdrawn:");
+    let mut changed = rib.set_interface("eth1", true);
+    changed.extend(rib.remove(more_specific, len).unwrap_or_default());
+    changed.sort_unstable();
+    changed.dedup();
+    show(&rib, &changed);
+
+    //a full table resolved over a couple of hundred igp loopbacks
+    let routes = generate_routes(&RouteGenConfig::new(20_000, 7));
+    let loopback = |i: u32| ip_to_u32("172.31.0.0") | i;
+    let neighbours = [NextHop::parse("10.0.0.2"), NextHop::parse("192.168.1.2")];
+    let mut rng = Rng::new(40);
+    for i in 1..=200 {
+        rib.insert(loopback(i), 32, neighbours[rng.below(2) as usize].clone());
+    }
+    let start = Instant::now();
+    for route in &routes {
+        rib.insert(route.prefix, route.prefix_len, NextHop::Via(loopback(1 + rng.below(200) as u32)));
+    }
+    let resolved = rib.entries().iter().filter(|(_, _, _, res)| res.is_ok()).count();
+    println!("{} routes, {} resolved, built in {:.1} ms", rib.len(), resolved, start.elapsed().as_secs_f64() * 1000.0);
+
+    let before = rib.stats().resolutions;
+    let changed = rib.insert(loopback(7), 32, NextHop::parse("192.168.1.2"));
+    println!(
+        "igp change for {}: {} routes re-resolved, {} changed",
+        u32_to_ip(loopback(7)),
+        rib.stats().resolutions - before,
+        changed.len()
+    );
+
+    //churn, then check the incremental fib against one resolved from scratch
+    let before = rib.stats();
+    let start = Instant::now();
+    for _ in 0..200 {
+        match rng.below(4) {
+            0 => {
+                rib.remove(loopback(1 + rng.below(200) as u32), 32);
+            }
+            1 => {
+                rib.insert(loopback(1 + rng.below(200) as u32), 32, neighbours[rng.below(2) as usize].clone());
+            }
+            2 => {
+                let route = &routes[rng.below(routes.len() as u64) as usize];
+                rib.remove(route.prefix, route.prefix_len);
+            }
+            _ => {
+                rib.set_interface(["eth0", "eth1"][rng.below(2) as usize], rng.below(2) == 0);
+            }
+        }
+    }
+    let churn_time = start.elapsed();
+    let after = rib.stats();
+    rib.set_interface("eth0", true);
+    rib.set_interface("eth1", true);
+
+    let mut fresh = Rib::new();
+    fresh.add_interface("eth0", ip_to_u32("10.0.0.1"), 24);
+    fresh.add_interface("eth1", ip_to_u32("192.168.1.1"), 30);
+    let mut config: Vec<(u32, u8, NextHop)> = rib.entries().into_iter().map(|(p, l, hop, _)| (p, l, hop.clone())).collect();
+    rng.shuffle(&mut config);
+    for (prefix, len, hop) in config {
+        fresh.insert(prefix, len, hop);
+    }
+    let mismatches = rib.entries().iter().zip(fresh.entries()).filter(|(a, b)| **a != *b).count();
+    println!(
+        "200 changes in {:.1} ms, {} routes re-resolved, {} of them changed, {} mismatches against a fresh rib",
+        churn_time.as_secs_f64() * 1000.0,
+        after.resolutions - before.resolutions,
+        after.changed - before.changed,
+        mismatches
+    );
+
+    //the resolved fib drops into any of the lpm backends
+    let mut fib = backend_by_name("poptrie").expect("poptrie");
+    for route in rib.fib_routes() {
+        fib.insert(route.prefix, route.prefix_len, route.next_hop).expect("poptrie insert");
+    }
+    for ip in ["203.0.113.77", "100.64.1.1", "192.0.2.5"] {
+        let installed = fib.lookup_route(ip_to_u32(ip)).map_or("no route".to_string(), |r| r.to_string());
+        //unresolvable routes stay out of the fib, so traffic falls through to a shorter match
+        match rib.lookup(ip_to_u32(ip)) {
+            Some((prefix, len, Err(e))) => println!("  {} -> {} ({}/{} skipped: {})", ip, installed, u32_to_ip(prefix), len, e),
+            _ => println!("  {} -> {}", ip, installed),
+        }
+    }
+}
@@ -0,0 +1,429 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+
+use crate::prefix_map::PrefixMap;
+use crate::route_table::Route;
+use crate::utils::{parse_ip, prefix_mask, u32_to_ip};
+
+//what a route is configured with: a gateway address that has to be resolved
+//through another route, or an egress interface (connected and static dev routes).
+//plain router names from the older tables are treated as interfaces
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum NextHop{
+    Via(u32),
+    Connected(String),
+}
+
+impl NextHop{
+    pub fn parse(s: &str) -> NextHop{
+        let s = s.trim();
+        match parse_ip(s){
+            Some(ip) => NextHop::Via(ip),
+            None => NextHop::Connected(s.strip_prefix("dev ").unwrap_or(s).trim().to_string()),
+        }
+    }
+}
+
+impl fmt::Display for NextHop{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            NextHop::Via(ip) => write!(f, "via {}", u32_to_ip(*ip)),
+            NextHop::Connected(interface) => write!(f, "dev {}", interface),
+        }
+    }
+}
+
+//where a packet actually leaves: the interface and the neighbour to send it to
+//(none means the destination itself is on the link). `via` lists the routes
+//the gateway was resolved through
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Resolved{
+    pub interface: String,
+    pub gateway: Option<u32>,
+    pub via: Vec<(u32, u8)>,
+}
+
+impl fmt::Display for Resolved{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self.gateway{
+            Some(gw) => write!(f, "{} dev {}", u32_to_ip(gw), self.interface)?,
+            None => write!(f, "direct dev {}", self.interface)?,
+        }
+        if !self.via.is_empty(){
+            let via: Vec<String> = self.via.iter().map(|&(p, l)| format!("{}/{}", u32_to_ip(p), l)).collect();
+            write!(f, " (via {})", via.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ResolveError{
+    Unreachable(u32),
+    InterfaceDown(String),
+    //the routes visited, the first one repeats at the end
+    Loop(Vec<(u32, u8)>),
+}
+
+impl fmt::Display for ResolveError{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            ResolveError::Unreachable(gw) => write!(f, "gateway {} unreachable", u32_to_ip(*gw)),
+            ResolveError::InterfaceDown(interface) => write!(f, "interface {} down", interface),
+            ResolveError::Loop(chain) => {
+                let chain: Vec<String> = chain.iter().map(|&(p, l)| format!("{}/{}", u32_to_ip(p), l)).collect();
+                write!(f, "resolution loop {}", chain.join(" -> "))
+            }
+        }
+    }
+}
+
+pub type Resolution = Result<Resolved, ResolveError>;
+
+#[derive(Debug, Clone)]
+pub struct Interface{
+    pub address: u32,
+    pub prefix_len: u8,
+    pub up: bool,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RibStats{
+    pub resolutions: u64,
+    pub changed: u64,
+}
+
+//configured routes plus the resolved fib derived from them. every gateway
+//lookup a resolution made is remembered with the length of the route that
+//answered it, so when a route appears, changes or goes away only the routes
+//whose answer it can change are resolved again
+pub struct Rib{
+    routes: PrefixMap<NextHop>,
+    fib: PrefixMap<Resolution>,
+    interfaces: BTreeMap<String, Interface>,
+    //gateway address -> routes whose resolution looked it up, with the length
+    //of the route that matched (none if nothing did)
+    watchers: BTreeMap<u32, BTreeMap<(u32, u8), Option<u8>>>,
+    watching: BTreeMap<(u32, u8), Vec<u32>>,
+    stats: RibStats,
+}
+
+impl Rib{
+    pub fn new() -> Self{
+        Rib{
+            routes: PrefixMap::new(),
+            fib: PrefixMap::new(),
+            interfaces: BTreeMap::new(),
+            watchers: BTreeMap::new(),
+            watching: BTreeMap::new(),
+            stats: RibStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize{
+        self.routes.len()
+    }
+
+    pub fn stats(&self) -> RibStats{
+        self.stats
+    }
+
+    //an interface address brings its subnet in as a connected route
+    pub fn add_interface(&mut self, name: &str, address: u32, prefix_len: u8) -> Vec<(u32, u8)>{
+        self.interfaces.insert(name.to_string(), Interface{ address, prefix_len, up: true });
+        self.insert(address & prefix_mask(prefix_len), prefix_len, NextHop::Connected(name.to_string()))
+    }
+
+    //link down withdraws the connected route, link up brings it back;
+    //either way everything resolved over the interface follows
+    pub fn set_interface(&mut self, name: &str, up: bool) -> Vec<(u32, u8)>{
+        let Some(interface) = self.interfaces.get_mut(name) else{
+            return vec![];
+        };
+        if interface.up == up{
+            return vec![];
+        }
+        interface.up = up;
+        let (prefix, len) = (interface.address & prefix_mask(interface.prefix_len), interface.prefix_len);
+
+        let mut changed = if up{
+            self.insert(prefix, len, NextHop::Connected(name.to_string()))
+        }else{
+            self.remove(prefix, len).unwrap_or_default()
+        };
+        //static dev routes on the interface are kept but stop (or start) resolving,
+        //and so does whatever resolved through them. the connected route itself
+        //was just handled by insert or remove
+        let pinned: Vec<(u32, u8)> = self
+            .routes
+            .entries()
+            .into_iter()
+            .filter(|&(p, l, ref hop)| (p, l) != (prefix, len) && matches!(hop, NextHop::Connected(i) if i == name))
+            .map(|(p, l, _)| (p, l))
+            .collect();
+        let mut dirty: BTreeSet<(u32, u8)> = pinned.iter().copied().collect();
+        for &(p, l) in &pinned{
+            dirty.extend(self.affected_by(p, l, false));
+        }
+        changed.extend(self.reresolve(dirty));
+        changed.sort_unstable();
+        changed.dedup();
+        changed
+    }
+
+    //returns the routes whose resolution changed, including this one
+    pub fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: NextHop) -> Vec<(u32, u8)>{
+        let prefix = prefix & prefix_mask(prefix_len);
+        self.routes.insert(prefix, prefix_len, next_hop);
+
+        let mut dirty = self.affected_by(prefix, prefix_len, true);
+        dirty.insert((prefix, prefix_len));
+        self.reresolve(dirty)
+    }
+
+    pub fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<Vec<(u32, u8)>>{
+        let prefix = prefix & prefix_mask(prefix_len);
+        self.routes.remove(prefix, prefix_len)?;
+        self.fib.remove(prefix, prefix_len);
+        self.unwatch(prefix, prefix_len);
+
+        //withdrawn route still counts as changed for the caller
+        let mut changed = vec![(prefix, prefix_len)];
+        changed.extend(self.reresolve(self.affected_by(prefix, prefix_len, false)));
+        Some(changed)
+    }
+
+    pub fn next_hop(&self, prefix: u32, prefix_len: u8) -> Option<&NextHop>{
+        self.routes.get(prefix, prefix_len)
+    }
+
+    pub fn resolution(&self, prefix: u32, prefix_len: u8) -> Option<&Resolution>{
+        self.fib.get(prefix, prefix_len)
+    }
+
+    //forwarding lookup: longest match among all routes, resolved or not
+    pub fn lookup(&self, ip: u32) -> Option<(u32, u8, &Resolution)>{
+        self.fib.lookup(ip)
+    }
+
+    pub fn entries(&self) -> Vec<(u32, u8, &NextHop, &Resolution)>{
+        self.fib
+            .entries()
+            .into_iter()
+            .filter_map(|(p, l, res)| Some((p, l, self.routes.get(p, l)?, res)))
+            .collect()
+    }
+
+    //resolved routes in the plain route model, ready for any RouteTable backend
+    pub fn fib_routes(&self) -> Vec<Route>{
+        self.fib
+            .entries()
+            .into_iter()
+            .filter_map(|(p, l, res)| res.as_ref().ok().map(|r| Route::new(p, l, r.to_string())))
+            .collect()
+    }
+
+    //routes that looked up a gateway inside the prefix and got an answer it can
+    //change: a shorter match (or none) when it is added, the prefix itself when it
+    //is replaced, goes away or stops resolving
+    fn affected_by(&self, prefix: u32, prefix_len: u8, added: bool) -> BTreeSet<(u32, u8)>{
+        let last = prefix | !prefix_mask(prefix_len);
+        self.watchers
+            .range(prefix..=last)
+            .flat_map(|(_, routes)| routes.iter())
+            .filter(|&(_, &matched)| match matched{
+                Some(len) => len == prefix_len || (added && len < prefix_len),
+                None => added,
+            })
+            .map(|(&route, _)| route)
+            .collect()
+    }
+
+    fn unwatch(&mut self, prefix: u32, prefix_len: u8){
+        for gw in self.watching.remove(&(prefix, prefix_len)).unwrap_or_default(){
+            if let Some(routes) = self.watchers.get_mut(&gw){
+                routes.remove(&(prefix, prefix_len));
+                if routes.is_empty(){
+                    self.watchers.remove(&gw);
+                }
+            }
+        }
+    }
+
+    //each dirty route is resolved from scratch; a route's watch list covers every
+    //gateway on its chain, so routes stacked on a changed route are already dirty
+    fn reresolve(&mut self, dirty: BTreeSet<(u32, u8)>) -> Vec<(u32, u8)>{
+        let mut changed = vec![];
+        for (prefix, len) in dirty{
+            if self.routes.get(prefix, len).is_none(){
+                continue;
+            }
+            let (resolution, watched) = self.resolve(prefix, len);
+            self.stats.resolutions += 1;
+
+            self.unwatch(prefix, len);
+            for &(gw, matched) in &watched{
+                self.watchers.entry(gw).or_default().insert((prefix, len), matched);
+            }
+            self.watching.insert((prefix, len), watched.into_iter().map(|(gw, _)| gw).collect());
+
+            if self.fib.get(prefix, len) != Some(&resolution){
+                self.fib.insert(prefix, len, resolution);
+                self.stats.changed += 1;
+                changed.push((prefix, len));
+            }
+        }
+        changed
+    }
+
+    fn resolve(&self, prefix: u32, prefix_len: u8) -> (Resolution, Vec<(u32, Option<u8>)>){
+        let mut chain = vec![(prefix, prefix_len)];
+        let mut watched = vec![];
+        let mut gateway = None;
+        let mut hop = self.routes.get(prefix, prefix_len).expect("resolving a configured route");
+
+        loop{
+            match hop{
+                NextHop::Connected(name) => {
+                    let result = match self.interfaces.get(name){
+                        Some(interface) if !interface.up => Err(ResolveError::InterfaceDown(name.clone())),
+                        _ => Ok(Resolved{ interface: name.clone(), gateway, via: chain[1..].to_vec() }),
+                    };
+                    return (result, watched);
+                }
+                NextHop::Via(gw) => {
+                    gateway = Some(*gw);
+                    //a route never resolves through itself, a gateway only it covers is unreachable
+                    let current = chain[chain.len() - 1];
+                    let matched = self.routes.covering(*gw).into_iter().rev().find(|&(p, l, _)| (p, l) != current);
+                    watched.push((*gw, matched.map(|(_, l, _)| l)));
+                    let Some((p, l, next)) = matched else{
+                        return (Err(ResolveError::Unreachable(*gw)), watched);
+                    };
+                    let repeat = chain.contains(&(p, l));
+                    chain.push((p, l));
+                    if repeat{
+                        return (Err(ResolveError::Loop(chain)), watched);
+                    }
+                    hop = next;
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::utils::Rng;
+
+    fn p(s: &str) -> (u32, u8){
+        crate::utils::parse_prefix(s).unwrap()
+    }
+
+    fn ip(s: &str) -> u32{
+        parse_ip(s).unwrap()
+    }
+
+    fn lab() -> Rib{
+        let mut rib = Rib::new();
+        rib.add_interface("eth0", ip("10.0.0.1"), 24);
+        rib.add_interface("eth1", ip("192.168.1.1"), 30);
+        for (prefix, hop) in [("0.0.0.0/0", "192.168.1.2"), ("172.16.0.0/16", "10.0.0.2"), ("203.0.113.0/24", "172.16.5.5")]{
+            let (prefix, len) = p(prefix);
+            rib.insert(prefix, len, NextHop::parse(hop));
+        }
+        rib
+    }
+
+    #[test]
+    fn a_route_never_resolves_through_itself(){
+        let mut rib = lab();
+        let (default, len) = p("0.0.0.0/0");
+        assert!(rib.resolution(default, len).unwrap().is_ok());
+
+        rib.set_interface("eth1", false);
+        assert_eq!(rib.resolution(default, len), Some(&Err(ResolveError::Unreachable(ip("192.168.1.2")))));
+
+        //a gateway inside its own route falls back to a shorter one
+        rib.set_interface("eth1", true);
+        let (prefix, len) = p("10.1.0.0/16");
+        rib.insert(prefix, len, NextHop::parse("10.1.0.1"));
+        let resolved = rib.resolution(prefix, len).unwrap().as_ref().unwrap();
+        assert_eq!(resolved.via, vec![(default, 0), p("192.168.1.0/30")]);
+        let (wide, wide_len) = p("10.0.0.0/8");
+        rib.insert(wide, wide_len, NextHop::parse("10.0.0.2"));
+        let resolved = rib.resolution(prefix, len).unwrap().as_ref().unwrap();
+        assert_eq!(resolved.via, vec![(wide, wide_len), p("10.0.0.0/24")]);
+
+        //two routes pointing at each other are still a loop
+        let (a, a_len) = p("198.51.100.0/25");
+        let (b, b_len) = p("198.51.100.128/25");
+        rib.insert(a, a_len, NextHop::parse("198.51.100.200"));
+        rib.insert(b, b_len, NextHop::parse("198.51.100.10"));
+        assert!(matches!(rib.resolution(a, a_len), Some(Err(ResolveError::Loop(_)))));
+    }
+
+    #[test]
+    fn only_affected_routes_are_resolved_again(){
+        let mut rib = lab();
+        let before = rib.stats().resolutions;
+        //10.0.0.2 is answered by the connected /24, a covering /8 changes nothing for it
+        let (prefix, len) = p("10.0.0.0/8");
+        rib.insert(prefix, len, NextHop::parse("192.168.1.2"));
+        assert_eq!(rib.stats().resolutions - before, 1);
+
+        //a more specific route for the gateway takes over the routes behind it
+        let before = rib.stats().resolutions;
+        let (host, host_len) = p("172.16.5.0/24");
+        let changed = rib.insert(host, host_len, NextHop::parse("192.168.1.2"));
+        assert_eq!(changed, vec![(host, host_len), p("203.0.113.0/24")]);
+        assert_eq!(rib.stats().resolutions - before, 2);
+    }
+
+    //incremental updates must leave the same fib as resolving everything once
+    #[test]
+    fn churn_matches_a_fresh_rib(){
+        let mut rng = Rng::new(40);
+        let mut rib = lab();
+        let loopback = |i: u32| ip("172.31.0.0") | i;
+        let neighbours = [NextHop::parse("10.0.0.2"), NextHop::parse("192.168.1.2")];
+        for i in 1..=20{
+            rib.insert(loopback(i), 32, neighbours[rng.below(2) as usize].clone());
+        }
+        let routes = crate::route_gen::generate_routes(&crate::route_gen::RouteGenConfig::new(500, 40));
+        for route in &routes{
+            rib.insert(route.prefix, route.prefix_len, NextHop::Via(loopback(1 + rng.below(20) as u32)));
+        }
+        for _ in 0..300{
+            match rng.below(4){
+                0 => {
+                    rib.remove(loopback(1 + rng.below(20) as u32), 32);
+                }
+                1 => {
+                    rib.insert(loopback(1 + rng.below(20) as u32), 32, neighbours[rng.below(2) as usize].clone());
+                }
+                2 => {
+                    let route = &routes[rng.below(routes.len() as u64) as usize];
+                    rib.remove(route.prefix, route.prefix_len);
+                }
+                _ => {
+                    rib.set_interface(["eth0", "eth1"][rng.below(2) as usize], rng.below(2) == 0);
+                }
+            }
+        }
+
+        let mut fresh = Rib::new();
+        for name in ["eth0", "eth1"]{
+            let interface = &rib.interfaces[name];
+            fresh.add_interface(name, interface.address, interface.prefix_len);
+            fresh.set_interface(name, interface.up);
+        }
+        let mut config: Vec<(u32, u8, NextHop)> = rib.entries().into_iter().map(|(p, l, hop, _)| (p, l, hop.clone())).collect();
+        rng.shuffle(&mut config);
+        for (prefix, len, hop) in config{
+            fresh.insert(prefix, len, hop);
+        }
+        assert_eq!(rib.entries(), fresh.entries());
+    }
+}
@@ -0,0 +1,305 @@
+use std::collections::HashMap;
+use std::mem::size_of;
+
+use crate::ip_bin_trie::TrieNode;
+use crate::route_table::{Route, RouteTable};
+use crate::utils::prefix_mask;
+
+const STRIDE: u8 = 6;
+//direct pointing entries with this bit set are leaves holding a route id
+const LEAF_FLAG: u32 = 1 << 31;
+//route id 0 means no route
+const NO_ROUTE: u32 = 0;
+
+#[derive(Debug, Clone, Copy, Default)]
+struct PopNode{
+    //bit v set when child v is an internal node
+    vector: u64,
+    //bit v set where a run of identical leaves starts
+    leafvec: u64,
+    base0: u32,
+    base1: u32,
+}
+
+//asai and ohara's poptrie: 6-bit strides, children and leaves found with popcount
+//leaves hold route ids rather than bare next hops so lookups can report the matched prefix
+pub struct Poptrie{
+    //binary trie kept as the rib, every update rebuilds the affected subtrees from it
+    rib: TrieNode,
+    direct_bits: u8,
+    direct: Vec<u32>,
+    nodes: Vec<PopNode>,
+    leaves: Vec<u32>,
+    routes: Vec<Option<Route>>,
+    route_ids: HashMap<(u32, u8), u32>,
+    free_ids: Vec<u32>,
+    garbage_nodes: usize,
+    garbage_leaves: usize,
+}
+
+impl Poptrie{
+    pub fn new(direct_bits: u8) -> Self{
+        let direct_bits = direct_bits.clamp(STRIDE, 24);
+        Poptrie{
+            rib: TrieNode::new(),
+            direct_bits,
+            direct: vec![LEAF_FLAG | NO_ROUTE; 1 << direct_bits],
+            nodes: Vec::new(),
+            leaves: Vec::new(),
+            routes: vec![None],
+            route_ids: HashMap::new(),
+            free_ids: Vec::new(),
+            garbage_nodes: 0,
+            garbage_leaves: 0,
+        }
+    }
+
+    pub fn lookup_id(&self, ip: u32) -> u32{
+        let entry = self.direct[(ip >> (32 - self.direct_bits)) as usize];
+        if entry & LEAF_FLAG != 0{
+            return entry & !LEAF_FLAG;
+        }
+
+        //64-bit key so the last stride can read zero padding past bit 32
+        let key = (ip as u64) << 32;
+        let mut node = self.nodes[entry as usize];
+        let mut offset = self.direct_bits as u32;
+        loop{
+            let v = (key << offset) >> (64 - STRIDE as u32);
+            let upto = (2u64 << v).wrapping_sub(1);
+            if node.vector & (1 << v) != 0{
+                let bc = (node.vector & upto).count_ones();
+                node = self.nodes[(node.base1 + bc - 1) as usize];
+                offset += STRIDE as u32;
+            }else{
+                let bc = (node.leafvec & upto).count_ones();
+                return self.leaves[(node.base0 + bc - 1) as usize];
+            }
+        }
+    }
+
+    pub fn size_bytes(&self) -> usize{
+        self.direct.len() * size_of::<u32>()
+            + self.live_nodes() * size_of::<PopNode>()
+            + (self.leaves.len() - self.garbage_leaves) * size_of::<u32>()
+    }
+
+    pub fn live_nodes(&self) -> usize{
+        self.nodes.len() - self.garbage_nodes
+    }
+
+    fn intern(&mut self, prefix: u32, prefix_len: u8, next_hop: &str) -> u32{
+        let route = Route::new(prefix, prefix_len, next_hop.to_string());
+        if let Some(&id) = self.route_ids.get(&(prefix, prefix_len)){
+            self.routes[id as usize] = Some(route);
+            return id;
+        }
+
+        let id = match self.free_ids.pop(){
+            Some(id) => {
+                self.routes[id as usize] = Some(route);
+                id
+            }
+            None => {
+                self.routes.push(Some(route));
+                (self.routes.len() - 1) as u32
+            }
+        };
+        self.route_ids.insert((prefix, prefix_len), id);
+        id
+    }
+
+    //rebuilds every direct pointing entry the prefix overlaps
+    fn update(&mut self, prefix: u32, prefix_len: u8){
+        let shift = 32 - self.direct_bits as u32;
+        let first = prefix >> shift;
+        let count = 1u32 << self.direct_bits.saturating_sub(prefix_len);
+        for entry in first..first + count{
+            self.rebuild_direct(entry);
+        }
+
+        //old subtrees are left behind as garbage, compact once they dominate
+        if self.garbage_nodes > 1024 && self.garbage_nodes * 2 > self.nodes.len(){
+            self.rebuild_all();
+        }
+    }
+
+    fn rebuild_all(&mut self){
+        self.nodes.clear();
+        self.leaves.clear();
+        self.garbage_nodes = 0;
+        self.garbage_leaves = 0;
+        for entry in 0..self.direct.len() as u32{
+            self.direct[entry as usize] = LEAF_FLAG | NO_ROUTE;
+            self.rebuild_direct(entry);
+        }
+    }
+
+    fn rebuild_direct(&mut self, entry: u32){
+        let old = self.direct[entry as usize];
+        if old & LEAF_FLAG == 0{
+            self.collect_garbage(old);
+        }
+
+        let mut builder = Builder{
+            route_ids: &self.route_ids,
+            nodes: &mut self.nodes,
+            leaves: &mut self.leaves,
+        };
+
+        //walk the rib down to the direct pointing depth, remembering the best route on the way
+        let path = entry << (32 - self.direct_bits as u32);
+        let mut curr = Some(&self.rib);
+        let mut best = builder.route_at(&self.rib, 0, 0).unwrap_or(NO_ROUTE);
+        for depth in 0..self.direct_bits{
+            let bit = (entry >> (self.direct_bits - 1 - depth)) & 1;
+            curr = curr.and_then(|node| node.child(bit));
+            match curr{
+                Some(node) => best = builder.route_at(node, path, depth + 1).unwrap_or(best),
+                None => break,
+            }
+        }
+
+        self.direct[entry as usize] = match curr{
+            Some(node) if node.has_children() => {
+                let idx = builder.nodes.len();
+                builder.nodes.push(PopNode::default());
+                builder.nodes[idx] = builder.build_node(node, self.direct_bits, path, best);
+                idx as u32
+            }
+            _ => LEAF_FLAG | best,
+        };
+    }
+
+    fn collect_garbage(&mut self, idx: u32){
+        let node = self.nodes[idx as usize];
+        self.garbage_nodes += 1;
+        self.garbage_leaves += node.leafvec.count_ones() as usize;
+        for i in 0..node.vector.count_ones(){
+            self.collect_garbage(node.base1 + i);
+        }
+    }
+}
+
+//borrows the arrays separately from the rib so subtrees can be built straight from it
+struct Builder<'a>{
+    route_ids: &'a HashMap<(u32, u8), u32>,
+    nodes: &'a mut Vec<PopNode>,
+    leaves: &'a mut Vec<u32>,
+}
+
+impl Builder<'_>{
+    fn route_at(&self, node: &TrieNode, path: u32, depth: u8) -> Option<u32>{
+        node.value()?;
+        self.route_ids.get(&(path & prefix_mask(depth), depth)).copied()
+    }
+
+    //one poptrie node for the rib subtree at `depth`, children are laid out contiguously
+    fn build_node(&mut self, rib: &TrieNode, depth: u8, path: u32, inherited: u32) -> PopNode{
+        let mut node = PopNode::default();
+        let mut children: Vec<(&TrieNode, u32, u32)> = Vec::new();
+        let mut leaves: Vec<u32> = Vec::new();
+
+        for v in 0..64u32{
+            let (reached, best, child_path) = self.descend(rib, depth, path, v, inherited);
+            match reached{
+                Some(child) if child.has_children() => {
+                    node.vector |= 1 << v;
+                    children.push((child, best, child_path));
+                }
+                _ => {
+                    //leaf compression, a run of equal leaves is stored once
+                    if leaves.last() != Some(&best){
+                        node.leafvec |= 1 << v;
+                        leaves.push(best);
+                    }
+                }
+            }
+        }
+
+        node.base0 = self.leaves.len() as u32;
+        self.leaves.extend(leaves);
+        node.base1 = self.nodes.len() as u32;
+        self.nodes.resize(self.nodes.len() + children.len(), PopNode::default());
+
+        for (i, (child, best, child_path)) in children.into_iter().enumerate(){
+            let built = self.build_node(child, depth + STRIDE, child_path, best);
+            self.nodes[node.base1 as usize + i] = built;
+        }
+        node
+    }
+
+    //follows the bits of v below depth and returns the node reached if the whole stride exists,
+    //the best route seen on the way and the reached node's path
+    fn descend<'t>(&self, rib: &'t TrieNode, depth: u8, path: u32, v: u32, inherited: u32) -> (Option<&'t TrieNode>, u32, u32){
+        let mut curr = rib;
+        let mut best = inherited;
+        let mut child_path = path;
+
+        for i in 0..STRIDE{
+            let bit_pos = depth + i;
+            //bits past 32 are zero padding in the last stride
+            if bit_pos >= 32{
+                return (None, best, child_path);
+            }
+            let bit = (v >> (STRIDE - 1 - i)) & 1;
+            child_path |= bit << (31 - bit_pos as u32);
+            match curr.child(bit){
+                Some(child) => curr = child,
+                None => return (None, best, child_path),
+            }
+            best = self.route_at(curr, child_path, bit_pos + 1).unwrap_or(best);
+        }
+        (Some(curr), best, child_path)
+    }
+}
+
+impl RouteTable for Poptrie{
+    fn name(&self) -> &'static str{
+        "poptrie"
+    }
+
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        let prefix = prefix & prefix_mask(prefix_len);
+        self.intern(prefix, prefix_len, &next_hop);
+        self.rib.insert(prefix, prefix_len, next_hop);
+        self.update(prefix, prefix_len);
+        Ok(())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        let prefix = prefix & prefix_mask(prefix_len);
+        let removed = self.rib.remove(prefix, prefix_len)?;
+        if let Some(id) = self.route_ids.remove(&(prefix, prefix_len)){
+            self.routes[id as usize] = None;
+            self.update(prefix, prefix_len);
+            self.free_ids.push(id);
+        }
+        Some(removed)
+    }
+
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        self.routes[self.lookup_id(ip) as usize].clone()
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        self.rib.routes()
+    }
+
+    fn node_count(&self) -> usize{
+        self.live_nodes()
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::route_table::reference::check_against_trie;
+
+    #[test]
+    fn matches_trie_after_incremental_updates(){
+        for direct_bits in [6, 16, 18]{
+            check_against_trie(&mut Poptrie::new(direct_bits), 2_000, 35);
+        }
+    }
+}
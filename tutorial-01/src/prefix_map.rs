@@ -0,0 +1,718 @@
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fmt;
+use std::fs;
+use std::marker::PhantomData;
+use std::path::Path;
+
+use crate::ip_bin_trie::TrieNode;
+use crate::utils::{parse_prefix, prefix_mask};
+
+//any per-prefix metadata keyed by the same binary trie the route tables use
+pub struct PrefixMap<V>{
+    trie: TrieNode<V>,
+    count: usize,
+}
+
+impl<V> PrefixMap<V>{
+    pub fn new() -> Self{
+        PrefixMap{
+            trie: TrieNode::new(),
+            count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize{
+        self.count
+    }
+
+    pub fn node_count(&self) -> usize{
+        self.trie.node_count()
+    }
+
+    //returns the value it replaced, if the prefix was already present
+    pub fn insert(&mut self, prefix: u32, prefix_len: u8, value: V) -> Option<V>{
+        let prefix = prefix & prefix_mask(prefix_len);
+        match self.trie.get_mut(prefix, prefix_len){
+            Some(old) => Some(std::mem::replace(old, value)),
+            None => {
+                self.trie.insert(prefix, prefix_len, value);
+                self.count += 1;
+                None
+            }
+        }
+    }
+
+    pub fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<V>{
+        let removed = self.trie.remove(prefix & prefix_mask(prefix_len), prefix_len);
+        if removed.is_some(){
+            self.count -= 1;
+        }
+        removed
+    }
+
+    pub fn get(&self, prefix: u32, prefix_len: u8) -> Option<&V>{
+        self.trie
+            .covering(prefix & prefix_mask(prefix_len), prefix_len)
+            .into_iter()
+            .find(|&(_, len, _)| len == prefix_len)
+            .map(|(_, _, value)| value)
+    }
+
+    //most specific prefix holding the address, with its value
+    pub fn lookup(&self, ip: u32) -> Option<(u32, u8, &V)>{
+        self.trie.lookup_entry(ip)
+    }
+
+    //every prefix holding the address, shortest first
+    pub fn covering(&self, ip: u32) -> Vec<(u32, u8, &V)>{
+        self.trie.covering(ip, 32)
+    }
+
+    pub fn entries(&self) -> Vec<(u32, u8, &V)>{
+        self.trie.entries()
+    }
+}
+
+impl<V: MapValue> PrefixMap<V>{
+    pub fn to_bytes(&self) -> Vec<u8>{
+        DbWriter::new().write(&self.trie)
+    }
+
+    pub fn write_file(&self, path: impl AsRef<Path>) -> Result<usize, String>{
+        let bytes = self.to_bytes();
+        fs::write(path, &bytes).map_err(|e| e.to_string())?;
+        Ok(bytes.len())
+    }
+}
+
+//values that can live in the data section of the on-disk format
+pub trait MapValue: Sized{
+    fn encode(&self, out: &mut Vec<u8>);
+    //the value and how many bytes it took
+    fn decode(bytes: &[u8]) -> Option<(Self, usize)>;
+}
+
+impl MapValue for String{
+    fn encode(&self, out: &mut Vec<u8>){
+        let len = self.len().min(u16::MAX as usize);
+        out.extend_from_slice(&(len as u16).to_be_bytes());
+        out.extend_from_slice(&self.as_bytes()[..len]);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<(Self, usize)>{
+        let len = u16::from_be_bytes(bytes.get(..2)?.try_into().ok()?) as usize;
+        let text = std::str::from_utf8(bytes.get(2..2 + len)?).ok()?;
+        Some((text.to_string(), 2 + len))
+    }
+}
+
+//what an ip-to-asn/geo database answers for a prefix
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub struct AsnInfo{
+    pub asn: u32,
+    pub org: String,
+    pub country: String,
+}
+
+impl fmt::Display for AsnInfo{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "AS{} {} ({})", self.asn, self.org, self.country)
+    }
+}
+
+impl MapValue for AsnInfo{
+    fn encode(&self, out: &mut Vec<u8>){
+        out.extend_from_slice(&self.asn.to_be_bytes());
+        self.org.encode(out);
+        self.country.encode(out);
+    }
+
+    fn decode(bytes: &[u8]) -> Option<(Self, usize)>{
+        let asn = u32::from_be_bytes(bytes.get(..4)?.try_into().ok()?);
+        let (org, org_len) = String::decode(&bytes[4..])?;
+        let (country, country_len) = String::decode(&bytes[4 + org_len..])?;
+        Some((AsnInfo{ asn, org, country }, 4 + org_len + country_len))
+    }
+}
+
+impl PrefixMap<AsnInfo>{
+    //network,asn,organization,country with an optional header line, org may be quoted
+    pub fn load_csv(&mut self, text: &str) -> Result<usize, String>{
+        let mut loaded = 0;
+        for (n, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.to_ascii_lowercase().starts_with("network"){
+                continue;
+            }
+
+            let fields = split_csv(line);
+            let entry = match fields.as_slice(){
+                [network, asn, org, country, ..] => parse_prefix(network).zip(parse_asn(asn)).map(|((prefix, len), asn)| {
+                    (prefix, len, AsnInfo{ asn, org: org.clone(), country: country.to_ascii_uppercase() })
+                }),
+                _ => None,
+            };
+            let (prefix, len, info) = entry.ok_or_else(|| format!("line {}: cannot parse '{}'", n + 1, line))?;
+            self.insert(prefix, len, info);
+            loaded += 1;
+        }
+        Ok(loaded)
+    }
+}
+
+fn parse_asn(text: &str) -> Option<u32>{
+    let text = text.trim();
+    text.strip_prefix("AS").unwrap_or(text).parse().ok()
+}
+
+//comma separated fields, double quotes protect commas and "" is a literal quote
+fn split_csv(line: &str) -> Vec<String>{
+    let mut fields = vec![];
+    let mut field = String::new();
+    let mut quoted = false;
+    let mut chars = line.chars().peekable();
+
+    while let Some(c) = chars.next(){
+        match c{
+            '"' if quoted && chars.peek() == Some(&'"') => {
+                field.push('"');
+                chars.next();
+            }
+            '"' => quoted = !quoted,
+            ',' if !quoted => fields.push(std::mem::take(&mut field).trim().to_string()),
+            _ => field.push(c),
+        }
+    }
+    fields.push(field.trim().to_string());
+    fields
+}
+
+//on-disk layout, loosely after maxmind's mmdb, all integers big-endian:
+//  header   "PMDB", version, record bits (24 or 32), node count, tree bytes, prefix count, data bytes
+//  tree     nodes addressed by byte offset, the root at 0
+//  data     encoded values, each distinct value stored once
+//a node is a skip count, then if it is non-zero the skipped bits (u32) and a miss
+//record, then the left and right records. a lookup first compares the skipped
+//bits and takes the miss record if they differ, so a single-child chain costs
+//one node. a record below the tree size is a node offset, equal to it means no
+//match, above it holds (data offset << 6 | prefix length) + tree size + 1.
+//values are pushed down to the records, so a lookup never backtracks; the price
+//is that a prefix fully covered by more specifics is not in the file at all, and
+//the prefix count in the header only counts the prefixes that made it in
+const MAGIC: &[u8; 4] = b"PMDB";
+const VERSION: u8 = 3;
+const HEADER_BYTES: usize = 22;
+const LEN_BITS: u32 = 6;
+
+#[derive(Clone, Copy)]
+enum Record{
+    Node(u32),
+    Empty,
+    //id tells apart prefixes that share a value, it is not written out
+    Value{ offset: u32, len: u8, id: u32 },
+}
+
+struct TreeNode{
+    skip: u8,
+    path: u32,
+    miss: Record,
+    children: [Record; 2],
+}
+
+impl TreeNode{
+    fn bytes(&self, record_bytes: usize) -> usize{
+        let skip = if self.skip > 0 { 4 + record_bytes } else { 0 };
+        1 + skip + 2 * record_bytes
+    }
+}
+
+struct DbWriter{
+    nodes: Vec<TreeNode>,
+    data: Vec<u8>,
+    offsets: HashMap<Vec<u8>, u32>,
+    values: u32,
+}
+
+impl DbWriter{
+    fn new() -> Self{
+        DbWriter{
+            nodes: vec![],
+            data: vec![],
+            offsets: HashMap::new(),
+            values: 0,
+        }
+    }
+
+    fn write<V: MapValue>(mut self, trie: &TrieNode<V>) -> Vec<u8>{
+        self.emit(trie, 0, Record::Empty, true);
+        let prefix_count = self.kept();
+
+        //node offsets depend on the record size and the largest record on the
+        //offsets, so lay the tree out with 24-bit records first
+        let layout = |record_bytes: usize| {
+            let mut at = 0;
+            let offsets: Vec<u32> = self
+                .nodes
+                .iter()
+                .map(|node| {
+                    let offset = at as u32;
+                    at += node.bytes(record_bytes);
+                    offset
+                })
+                .collect();
+            (offsets, at as u64)
+        };
+        let largest = |tree_bytes: u64| tree_bytes + 1 + ((self.data.len() as u64) << LEN_BITS | 32);
+        let (mut offsets, mut tree_bytes) = layout(3);
+        let mut record_bits: u8 = 24;
+        if largest(tree_bytes) >= 1 << 24{
+            (offsets, tree_bytes) = layout(4);
+            record_bits = 32;
+            assert!(largest(tree_bytes) <= u32::MAX as u64, "prefix database too large for 32-bit records");
+        }
+        let record_bytes = record_bits as usize / 8;
+        let tree_bytes = tree_bytes as u32;
+        let encode = |record: Record| match record{
+            Record::Node(i) => offsets[i as usize],
+            Record::Empty => tree_bytes,
+            Record::Value{ offset, len, .. } => tree_bytes + 1 + (offset << LEN_BITS | len as u32),
+        };
+
+        let mut out = Vec::with_capacity(HEADER_BYTES + tree_bytes as usize + self.data.len());
+        out.extend_from_slice(MAGIC);
+        out.push(VERSION);
+        out.push(record_bits);
+        out.extend_from_slice(&(self.nodes.len() as u32).to_be_bytes());
+        out.extend_from_slice(&tree_bytes.to_be_bytes());
+        out.extend_from_slice(&(prefix_count as u32).to_be_bytes());
+        out.extend_from_slice(&(self.data.len() as u32).to_be_bytes());
+
+        let push = |out: &mut Vec<u8>, record: Record| {
+            out.extend_from_slice(&encode(record).to_be_bytes()[4 - record_bytes..]);
+        };
+        for node in &self.nodes{
+            out.push(node.skip);
+            if node.skip > 0{
+                out.extend_from_slice(&node.path.to_be_bytes());
+                push(&mut out, node.miss);
+            }
+            push(&mut out, node.children[0]);
+            push(&mut out, node.children[1]);
+        }
+        out.extend_from_slice(&self.data);
+        out
+    }
+
+    //prefixes some record still answers with, the rest are shadowed by more specifics
+    fn kept(&self) -> usize{
+        let mut ids = HashSet::new();
+        for node in &self.nodes{
+            let miss = (node.skip > 0).then_some(&node.miss);
+            for record in node.children.iter().chain(miss){
+                if let Record::Value{ id, .. } = record{
+                    ids.insert(*id);
+                }
+            }
+        }
+        ids.len()
+    }
+
+    //preorder walk; `best` is the record of the deepest value seen on the way
+    //down, which is what every record without a more specific subtree resolves to
+    fn emit<V: MapValue>(&mut self, node: &TrieNode<V>, depth: u8, best: Record, root: bool) -> Record{
+        let best = match node.value(){
+            Some(value) => self.add_value(depth, value),
+            None => best,
+        };
+        if !node.has_children() && !root{
+            return best;
+        }
+
+        //follow the chain while it has one way to go and nothing to answer on the way
+        let (mut node, mut depth) = (node, depth);
+        let (mut skip, mut path) = (0u8, 0u32);
+        while let (Some(child), None) | (None, Some(child)) = (node.child(0), node.child(1)){
+            if child.value().is_some() || !child.has_children(){
+                break;
+            }
+            let bit = node.child(1).is_some() as u32;
+            path = path << 1 | bit;
+            skip += 1;
+            depth += 1;
+            node = child;
+        }
+
+        let index = self.nodes.len();
+        self.nodes.push(TreeNode{ skip, path, miss: best, children: [best, best] });
+        for bit in 0..2{
+            if let Some(child) = node.child(bit){
+                let record = self.emit(child, depth + 1, best, false);
+                self.nodes[index].children[bit as usize] = record;
+            }
+        }
+        Record::Node(index as u32)
+    }
+
+    fn add_value<V: MapValue>(&mut self, len: u8, value: &V) -> Record{
+        self.values += 1;
+        let mut encoded = vec![];
+        value.encode(&mut encoded);
+        let offset = match self.offsets.get(&encoded){
+            Some(&offset) => offset,
+            None => {
+                let offset = self.data.len() as u32;
+                self.data.extend_from_slice(&encoded);
+                self.offsets.insert(encoded, offset);
+                offset
+            }
+        };
+        Record::Value{ offset, len, id: self.values }
+    }
+}
+
+//read side of the format: lookups run straight off the byte buffer, only the
+//matched value is decoded
+pub struct PrefixDb<V>{
+    bytes: Vec<u8>,
+    record_bytes: usize,
+    node_count: u32,
+    tree_bytes: u32,
+    prefix_count: u32,
+    data_at: usize,
+    _value: PhantomData<V>,
+}
+
+impl<V: MapValue> PrefixDb<V>{
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, String>{
+        Self::from_bytes(fs::read(path).map_err(|e| e.to_string())?)
+    }
+
+    pub fn from_bytes(bytes: Vec<u8>) -> Result<Self, String>{
+        if bytes.len() < HEADER_BYTES || &bytes[..4] != MAGIC{
+            return Err("not a prefix database".to_string());
+        }
+        if bytes[4] != VERSION{
+            return Err(format!("unsupported version {}", bytes[4]));
+        }
+        let record_bytes = match bytes[5]{
+            24 => 3,
+            32 => 4,
+            bits => return Err(format!("unsupported record size {}", bits)),
+        };
+        let word = |at: usize| u32::from_be_bytes(bytes[at..at + 4].try_into().unwrap());
+        let (node_count, tree_bytes, prefix_count, data_len) = (word(6), word(10), word(14), word(18) as usize);
+
+        let data_at = HEADER_BYTES + tree_bytes as usize;
+        if node_count == 0 || bytes.len() != data_at + data_len{
+            return Err(format!("truncated database: {} bytes, header says {}", bytes.len(), data_at + data_len));
+        }
+
+        let db = PrefixDb{
+            bytes,
+            record_bytes,
+            node_count,
+            tree_bytes,
+            prefix_count,
+            data_at,
+            _value: PhantomData,
+        };
+        let kept = db.check_tree()?;
+        if kept != prefix_count as usize{
+            return Err(format!("header says {} prefixes, the tree holds {}", prefix_count, kept));
+        }
+        Ok(db)
+    }
+
+    //lookups index the buffer without bounds checks of their own, so the tree is
+    //walked once here: every node lies inside the tree section, links only point
+    //forward to the start of a node that nothing else links to (so no walk can
+    //loop), paths stay within 32 bits and every value record decodes.
+    //returns how many distinct prefixes the records answer with
+    fn check_tree(&self) -> Result<usize, String>{
+        let rb = self.record_bytes as u32;
+        let data_len = self.bytes.len() - self.data_at;
+        //depth and path each linked node is reached with, by offset
+        let mut linked: HashMap<u32, (u32, u32)> = HashMap::from([(0, (0, 0))]);
+        let mut prefixes = HashSet::new();
+        let mut decoded = HashSet::new();
+        let (mut at, mut nodes) = (0u32, 0u32);
+
+        while at < self.tree_bytes{
+            let skip = self.bytes[HEADER_BYTES + at as usize] as u32;
+            let size = 1 + if skip > 0 { 4 + rb } else { 0 } + 2 * rb;
+            if at + size > self.tree_bytes{
+                return Err(format!("node at {} runs past the tree", at));
+            }
+            let (depth, path) = linked.remove(&at).ok_or_else(|| format!("node at {} is not linked from the tree", at))?;
+            let node = self.node(at);
+            if depth + skip > 31 || (skip > 0 && node.path >> skip != 0){
+                return Err(format!("node at {} skips past 32 bits", at));
+            }
+
+            let mut leaves = vec![];
+            if skip > 0{
+                leaves.push((path, node.miss));
+            }
+            let depth = depth + skip;
+            let path = if skip > 0 { path | node.path << (32 - depth) } else { path };
+            for (bit, &record) in node.children.iter().enumerate(){
+                let path = path | (bit as u32) << (31 - depth);
+                if record < self.tree_bytes{
+                    if record <= at || linked.insert(record, (depth + 1, path)).is_some(){
+                        return Err(format!("node at {} links back or to a shared node", at));
+                    }
+                }else{
+                    leaves.push((path, record));
+                }
+            }
+            for (path, record) in leaves{
+                let Some((offset, len)) = self.value_ref(record) else { continue };
+                if len > 32 || offset >= data_len{
+                    return Err(format!("bad value record {} in node at {}", record, at));
+                }
+                if decoded.insert(offset) && self.value(offset).is_none(){
+                    return Err(format!("value at {} does not decode", offset));
+                }
+                prefixes.insert((path & prefix_mask(len), len));
+            }
+            at += size;
+            nodes += 1;
+        }
+
+        if let Some(at) = linked.keys().min(){
+            return Err(format!("link to {}, which is not the start of a node", at));
+        }
+        if nodes != self.node_count{
+            return Err(format!("header says {} nodes, the tree holds {}", self.node_count, nodes));
+        }
+        Ok(prefixes.len())
+    }
+
+    //prefixes the file answers with, which leaves out those of the source map
+    //that more specifics cover entirely; the same set entries() lists
+    pub fn len(&self) -> usize{
+        self.prefix_count as usize
+    }
+
+    pub fn node_count(&self) -> usize{
+        self.node_count as usize
+    }
+
+    pub fn size_bytes(&self) -> usize{
+        self.bytes.len()
+    }
+
+    pub fn lookup(&self, ip: u32) -> Option<(u32, u8, V)>{
+        let mut at = 0;
+        let mut depth = 0;
+        loop{
+            let node = self.node(at);
+            if node.skip > 0{
+                if (ip << depth) >> (32 - node.skip as u32) != node.path{
+                    return self.resolve(ip, node.miss);
+                }
+                depth += node.skip as u32;
+            }
+            let record = node.children[((ip >> (31 - depth)) & 1) as usize];
+            depth += 1;
+            if record < self.tree_bytes{
+                at = record;
+            }else{
+                return self.resolve(ip, record);
+            }
+        }
+    }
+
+    //every prefix that still answers some address, in address order
+    pub fn entries(&self) -> Vec<(u32, u8, V)>{
+        let mut found = BTreeMap::new();
+        let mut stack = vec![(0u32, 0u32, 0u32)];
+        while let Some((at, path, depth)) = stack.pop(){
+            let node = self.node(at);
+            //a miss answers with a value from above the chain, the path so far covers it
+            let mut leaves = vec![];
+            if node.skip > 0{
+                leaves.push((path, node.miss));
+            }
+            let depth = depth + node.skip as u32;
+            let path = if node.skip > 0 { path | node.path << (32 - depth) } else { path };
+            for (bit, &record) in node.children.iter().enumerate(){
+                let path = path | (bit as u32) << (31 - depth);
+                if record < self.tree_bytes{
+                    stack.push((record, path, depth + 1));
+                }else{
+                    leaves.push((path, record));
+                }
+            }
+            for (path, record) in leaves{
+                if let Some((offset, len)) = self.value_ref(record){
+                    found.insert((path & prefix_mask(len), len), offset);
+                }
+            }
+        }
+        found
+            .into_iter()
+            .filter_map(|((prefix, len), offset)| Some((prefix, len, self.value(offset)?)))
+            .collect()
+    }
+
+    fn node(&self, at: u32) -> DbNode{
+        let rb = self.record_bytes;
+        let record = |at: usize| self.bytes[at..at + rb].iter().fold(0, |acc, &b| acc << 8 | b as u32);
+        let at = HEADER_BYTES + at as usize;
+        let skip = self.bytes[at];
+        if skip == 0{
+            return DbNode{ skip, path: 0, miss: self.tree_bytes, children: [record(at + 1), record(at + 1 + rb)] };
+        }
+        let path = u32::from_be_bytes(self.bytes[at + 1..at + 5].try_into().unwrap());
+        let records = at + 5 + rb;
+        DbNode{ skip, path, miss: record(at + 5), children: [record(records), record(records + rb)] }
+    }
+
+    fn value_ref(&self, record: u32) -> Option<(usize, u8)>{
+        let packed = record.checked_sub(self.tree_bytes + 1)?;
+        Some(((packed >> LEN_BITS) as usize, (packed & ((1 << LEN_BITS) - 1)) as u8))
+    }
+
+    fn value(&self, offset: usize) -> Option<V>{
+        V::decode(self.bytes.get(self.data_at + offset..)?).map(|(value, _)| value)
+    }
+
+    fn resolve(&self, ip: u32, record: u32) -> Option<(u32, u8, V)>{
+        let (offset, len) = self.value_ref(record)?;
+        Some((ip & prefix_mask(len), len, self.value(offset)?))
+    }
+}
+
+struct DbNode{
+    skip: u8,
+    path: u32,
+    miss: u32,
+    children: [u32; 2],
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::route_gen::{generate_routes, generate_trace, RouteGenConfig};
+    use crate::utils::Rng;
+
+    fn compile(map: &PrefixMap<String>) -> PrefixDb<String>{
+        PrefixDb::from_bytes(map.to_bytes()).expect("compiled db opens")
+    }
+
+    #[test]
+    fn db_answers_like_the_map(){
+        let routes = generate_routes(&RouteGenConfig::new(5_000, 39));
+        let mut map = PrefixMap::new();
+        for route in &routes{
+            map.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        }
+        map.insert(0, 0, "default".to_string());
+        for route in routes.iter().step_by(7){
+            map.remove(route.prefix, route.prefix_len);
+        }
+        let db = compile(&map);
+        assert!(db.len() <= map.len());
+
+        let listed: Vec<(u32, u8)> = db.entries().into_iter().map(|(p, l, _)| (p, l)).collect();
+        let mut rng = Rng::new(39);
+        let mut addrs = generate_trace(&routes, 20_000, 1.0, 39);
+        addrs.extend((0..20_000).map(|_| rng.next_u32()));
+        for ip in addrs{
+            let expected = map.lookup(ip).map(|(p, l, v)| (p, l, v.clone()));
+            let found = db.lookup(ip);
+            assert_eq!(found, expected, "{:08x}", ip);
+            let (prefix, len, _) = found.unwrap();
+            assert!(listed.binary_search(&(prefix, len)).is_ok());
+        }
+        assert_eq!(db.entries().len(), db.len());
+        for (prefix, len, value) in db.entries(){
+            assert_eq!(map.get(prefix, len), Some(&value));
+        }
+    }
+
+    #[test]
+    fn covered_prefixes_are_not_counted(){
+        let mut map = PrefixMap::new();
+        map.insert(0x0A00_0000, 8, "net".to_string());
+        map.insert(0x0A00_0000, 9, "low".to_string());
+        map.insert(0x0A80_0000, 9, "high".to_string());
+        map.insert(0x0B00_0000, 8, "net".to_string());
+        let db = compile(&map);
+        assert_eq!((map.len(), db.len()), (4, 3));
+        assert_eq!(db.entries().len(), 3);
+    }
+
+    #[test]
+    fn single_child_chains_take_one_node(){
+        let mut map = PrefixMap::new();
+        assert_eq!(compile(&map).lookup(0x0A01_0203), None);
+
+        map.insert(0x0A01_0203, 32, "host".to_string());
+        let db = compile(&map);
+        assert_eq!(db.node_count(), 1);
+        assert_eq!(db.lookup(0x0A01_0203), Some((0x0A01_0203, 32, "host".to_string())));
+        assert_eq!(db.lookup(0x0A01_0202), None);
+        assert_eq!(db.lookup(0x8A01_0203), None);
+
+        map.insert(0x0A00_0000, 8, "net".to_string());
+        let db = compile(&map);
+        assert_eq!(db.node_count(), 2);
+        assert_eq!(db.lookup(0x0A01_0202), Some((0x0A00_0000, 8, "net".to_string())));
+        assert_eq!(db.entries().len(), 2);
+    }
+
+    #[test]
+    fn rejects_damaged_files(){
+        let mut map = PrefixMap::new();
+        map.insert(0x0A00_0000, 8, "net".to_string());
+        let bytes = map.to_bytes();
+        assert!(PrefixDb::<String>::from_bytes(bytes[..bytes.len() - 1].to_vec()).is_err());
+        let mut wrong_version = bytes.clone();
+        wrong_version[4] = 1;
+        assert!(PrefixDb::<String>::from_bytes(wrong_version).is_err());
+        assert!(PrefixDb::<String>::from_bytes(b"MMDB".to_vec()).is_err());
+    }
+
+    #[test]
+    fn rejects_crafted_trees(){
+        let mut map = PrefixMap::new();
+        map.insert(0x0A00_0000, 8, "net".to_string());
+        map.insert(0x0A01_0000, 16, "sub".to_string());
+        map.insert(0xC000_0200, 24, "doc".to_string());
+        let bytes = map.to_bytes();
+        assert_eq!(bytes[5], 24);
+        let root_right = HEADER_BYTES + 4;
+
+        //a link back to the root and two into the middle of a node
+        for record in [0u32, 1, 2]{
+            let mut crafted = bytes.clone();
+            crafted[root_right..root_right + 3].copy_from_slice(&record.to_be_bytes()[1..]);
+            assert!(PrefixDb::<String>::from_bytes(crafted).is_err(), "record {}", record);
+        }
+        //a skip that runs past 32 bits
+        let mut crafted = bytes.clone();
+        crafted[HEADER_BYTES] = 40;
+        assert!(PrefixDb::<String>::from_bytes(crafted).is_err());
+        //a wrong prefix count
+        let mut crafted = bytes.clone();
+        crafted[17] += 1;
+        assert!(PrefixDb::<String>::from_bytes(crafted).is_err());
+
+        //whatever single byte gets damaged, the file is refused or answers without panicking
+        let mut rng = Rng::new(39);
+        for at in HEADER_BYTES..bytes.len(){
+            for flip in [0x01, 0x80, 0xFF]{
+                let mut crafted = bytes.clone();
+                crafted[at] ^= flip;
+                if let Ok(db) = PrefixDb::<String>::from_bytes(crafted){
+                    for _ in 0..100{
+                        db.lookup(rng.next_u32());
+                    }
+                    assert_eq!(db.entries().len(), db.len());
+                }
+            }
+        }
+    }
+}
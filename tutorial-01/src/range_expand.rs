@@ -0,0 +1,270 @@
+use std::fmt;
+
+use crate::classifier::{PacketHeader, Rule};
+use crate::utils::{prefix_mask, prefix_mask_bits};
+
+const PORT_BITS: u8 = 16;
+
+//value/mask pair as stored in a tcam, bits outside the mask are don't-care
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Ternary{
+    pub value: u32,
+    pub mask: u32,
+}
+
+impl Ternary{
+    pub fn from_prefix(prefix: u32, prefix_len: u8, width: u8) -> Self{
+        let mask = prefix_mask_bits(prefix_len, width);
+        Ternary{ value: prefix & mask, mask }
+    }
+
+    pub fn matches(&self, key: u32) -> bool{
+        key & self.mask == self.value
+    }
+
+    pub fn fmt_bits(&self, width: u8) -> String{
+        (0..width)
+            .rev()
+            .map(|i| match (self.mask >> i & 1, self.value >> i & 1){
+                (0, _) => '*',
+                (_, 0) => '0',
+                _ => '1',
+            })
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RangeEncoding{
+    //classic split into maximal aligned blocks, at most 2w - 2 entries
+    Prefix,
+    //dirpe (lakshminarayanan, rangarajan, venkatachary): keys are cut into chunks and
+    //each chunk is fence coded, so any run of chunk values is a single ternary pattern.
+    //with 2-bit chunks a port takes 24 tcam bits instead of 16, at most w - 1 entries
+    Dirpe,
+}
+
+const CHUNK_BITS: u8 = 2;
+//fence (thermometer) code of one chunk: value v sets the low v bits
+const FENCE_BITS: u8 = (1 << CHUNK_BITS) - 1;
+
+impl RangeEncoding{
+    pub fn encode_key(&self, key: u32) -> u32{
+        match self{
+            RangeEncoding::Prefix => key,
+            RangeEncoding::Dirpe => fence_key(key, PORT_BITS),
+        }
+    }
+
+    //tcam bits an encoded field of this width occupies
+    pub fn key_bits(&self, width: u8) -> u32{
+        match self{
+            RangeEncoding::Prefix => width as u32,
+            RangeEncoding::Dirpe => (width / CHUNK_BITS * FENCE_BITS) as u32,
+        }
+    }
+}
+
+impl fmt::Display for RangeEncoding{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            RangeEncoding::Prefix => f.pad("prefix"),
+            RangeEncoding::Dirpe => f.pad("dirpe"),
+        }
+    }
+}
+
+//greedy: take the largest aligned block starting at lo that still fits
+pub fn range_to_prefixes(lo: u32, hi: u32, width: u8) -> Vec<Ternary>{
+    let mut out = Vec::new();
+    let mut lo = lo as u64;
+    let hi = hi as u64;
+
+    while lo <= hi{
+        let mut size_bits = 0u8;
+        while size_bits < width && lo.is_multiple_of(1u64 << (size_bits + 1)) && lo + (1u64 << (size_bits + 1)) - 1 <= hi{
+            size_bits += 1;
+        }
+        out.push(Ternary::from_prefix(lo as u32, width - size_bits, width));
+        lo += 1u64 << size_bits;
+    }
+    out
+}
+
+fn fence_key(key: u32, width: u8) -> u32{
+    (0..width / CHUNK_BITS).fold(0, |acc, i| {
+        let chunk = key >> (i * CHUNK_BITS) & ((1 << CHUNK_BITS) - 1);
+        acc | ((1 << chunk) - 1) << (i * FENCE_BITS)
+    })
+}
+
+//chunk values a..=b: the low a fence bits must be set, bit b and above must be clear
+fn fence_range(a: u32, b: u32) -> Ternary{
+    let ones = (1u32 << a) - 1;
+    let above = ((1u32 << FENCE_BITS) - 1) & !((1u32 << b) - 1);
+    Ternary{ value: ones, mask: ones | above }
+}
+
+//like prefix expansion, but a block may span several values of its top chunk:
+//each piece fixes the leading chunks and takes a run of values in the next one
+pub fn range_to_fences(lo: u32, hi: u32, width: u8) -> Vec<Ternary>{
+    assert!(width.is_multiple_of(CHUNK_BITS), "dirpe needs a whole number of {}-bit chunks", CHUNK_BITS);
+    let mut out = Vec::new();
+    split_fences(lo, hi, width / CHUNK_BITS, Ternary{ value: 0, mask: 0 }, &mut out);
+    out
+}
+
+fn split_fences(lo: u32, hi: u32, chunks: u8, fixed: Ternary, out: &mut Vec<Ternary>){
+    let shift = (chunks - 1) * CHUNK_BITS;
+    let at = (chunks - 1) * FENCE_BITS;
+    let rest_max = (1u32 << shift) - 1;
+    let (top_lo, top_hi) = (lo >> shift, hi >> shift);
+    let (rest_lo, rest_hi) = (lo & rest_max, hi & rest_max);
+    let piece = |a, b| {
+        let t = fence_range(a, b);
+        Ternary{ value: fixed.value | t.value << at, mask: fixed.mask | t.mask << at }
+    };
+
+    if chunks == 1{
+        out.push(piece(top_lo, top_hi));
+        return;
+    }
+    if top_lo == top_hi{
+        split_fences(rest_lo, rest_hi, chunks - 1, piece(top_lo, top_lo), out);
+        return;
+    }
+    //ragged ends recurse, whole top-chunk values in between are one entry
+    let (mut first, mut last) = (top_lo, top_hi);
+    if rest_lo != 0{
+        split_fences(rest_lo, rest_max, chunks - 1, piece(top_lo, top_lo), out);
+        first += 1;
+    }
+    if rest_hi != rest_max{
+        split_fences(0, rest_hi, chunks - 1, piece(top_hi, top_hi), out);
+        last -= 1;
+    }
+    if first <= last{
+        out.push(piece(first, last));
+    }
+}
+
+pub fn encode_range(lo: u32, hi: u32, width: u8, encoding: RangeEncoding) -> Vec<Ternary>{
+    match encoding{
+        RangeEncoding::Prefix => range_to_prefixes(lo, hi, width),
+        RangeEncoding::Dirpe => range_to_fences(lo, hi, width),
+    }
+}
+
+//the action stays with the rule, a hit only needs to name the rule by its priority
+#[derive(Debug, Clone)]
+pub struct TernaryRule{
+    pub priority: u32,
+    pub src: Ternary,
+    pub dst: Ternary,
+    pub proto: Ternary,
+    pub sport: Ternary,
+    pub dport: Ternary,
+}
+
+impl TernaryRule{
+    pub fn matches(&self, pkt: &PacketHeader, encoding: RangeEncoding) -> bool{
+        self.src.matches(pkt.src)
+            && self.dst.matches(pkt.dst)
+            && self.proto.matches(pkt.proto as u32)
+            && self.sport.matches(encoding.encode_key(pkt.sport as u32))
+            && self.dport.matches(encoding.encode_key(pkt.dport as u32))
+    }
+}
+
+//one entry per (sport, dport) pair, prefixes and protocol map straight across
+pub fn expand_rule(rule: &Rule, encoding: RangeEncoding) -> Vec<TernaryRule>{
+    let src = Ternary{ value: rule.src.0, mask: prefix_mask(rule.src.1) };
+    let dst = Ternary{ value: rule.dst.0, mask: prefix_mask(rule.dst.1) };
+    let proto = rule.proto.map_or(Ternary{ value: 0, mask: 0 }, |p| Ternary{ value: p as u32, mask: 0xFF });
+    let sports = encode_range(rule.sport.0 as u32, rule.sport.1 as u32, PORT_BITS, encoding);
+    let dports = encode_range(rule.dport.0 as u32, rule.dport.1 as u32, PORT_BITS, encoding);
+
+    let mut out = Vec::with_capacity(sports.len() * dports.len());
+    for &sport in &sports{
+        for &dport in &dports{
+            out.push(TernaryRule{
+                priority: rule.priority,
+                src,
+                dst,
+                proto,
+                sport,
+                dport,
+            });
+        }
+    }
+    out
+}
+
+//tcam-ready entry list, ordered so the first hit is the highest priority rule
+pub fn expand_rules(rules: &[Rule], encoding: RangeEncoding) -> Vec<TernaryRule>{
+    let mut out: Vec<TernaryRule> = rules.iter().flat_map(|r| expand_rule(r, encoding)).collect();
+    out.sort_by_key(|r| r.priority);
+    out
+}
+
+pub fn classify_ternary<'a>(entries: &'a [TernaryRule], pkt: &PacketHeader, encoding: RangeEncoding) -> Option<&'a TernaryRule>{
+    entries.iter().find(|e| e.matches(pkt, encoding))
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::classifier::{generate_packets, generate_rules, linear_classify};
+    use crate::utils::Rng;
+
+    const ENCODINGS: [RangeEncoding; 2] = [RangeEncoding::Prefix, RangeEncoding::Dirpe];
+
+    //every port must hit an entry exactly when it is inside the range
+    #[test]
+    fn encodings_are_exact(){
+        let mut rng = Rng::new(33);
+        let mut ranges = vec![(0, 65535), (80, 80), (0, 1023), (1024, 65535), (1, 65534), (1000, 2000)];
+        for _ in 0..20{
+            let (a, b) = (rng.below(65536) as u32, rng.below(65536) as u32);
+            ranges.push((a.min(b), a.max(b)));
+        }
+        for (lo, hi) in ranges{
+            for encoding in ENCODINGS{
+                let entries = encode_range(lo, hi, PORT_BITS, encoding);
+                for port in 0..=u16::MAX as u32{
+                    let hit = entries.iter().any(|t| t.matches(encoding.encode_key(port)));
+                    assert_eq!(hit, (lo..=hi).contains(&port), "{} {}-{} port {}", encoding, lo, hi, port);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn dirpe_stays_under_its_bound(){
+        let mut rng = Rng::new(34);
+        for _ in 0..10_000{
+            let (a, b) = (rng.below(65536) as u32, rng.below(65536) as u32);
+            let prefix = encode_range(a.min(b), a.max(b), PORT_BITS, RangeEncoding::Prefix).len();
+            let dirpe = encode_range(a.min(b), a.max(b), PORT_BITS, RangeEncoding::Dirpe).len();
+            assert!(prefix <= 2 * PORT_BITS as usize - 2);
+            assert!(dirpe < PORT_BITS as usize);
+            assert!(dirpe <= prefix);
+        }
+        assert_eq!(encode_range(1024, 65535, PORT_BITS, RangeEncoding::Dirpe).len(), 3);
+    }
+
+    #[test]
+    fn expanded_rules_match_linear_search(){
+        let rules = generate_rules(300, 33);
+        let packets = generate_packets(&rules, 5_000, 33);
+        for encoding in ENCODINGS{
+            let entries = expand_rules(&rules, encoding);
+            for pkt in &packets{
+                assert_eq!(
+                    classify_ternary(&entries, pkt, encoding).map(|e| e.priority),
+                    linear_classify(&rules, pkt).map(|r| r.priority)
+                );
+            }
+        }
+    }
+}
@@ -0,0 +1,249 @@
+use std::collections::HashMap;
+
+use crate::route_table::{Route, RouteTable};
+
+const NIL: usize = usize::MAX;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CachePolicy{
+    Lru,
+    Clock,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct CacheStats{
+    pub hits: usize,
+    pub misses: usize,
+    pub evictions: usize,
+    pub invalidations: usize,
+}
+
+impl CacheStats{
+    pub fn hit_rate(&self) -> f64{
+        let total = self.hits + self.misses;
+        if total == 0{
+            0.0
+        }else{
+            self.hits as f64 / total as f64
+        }
+    }
+}
+
+struct Slot{
+    ip: u32,
+    //negative answers are cached too, a later insert can invalidate them
+    route: Option<Route>,
+    referenced: bool,
+    prev: usize,
+    next: usize,
+}
+
+//exact-match destination cache in front of any lpm table
+pub struct RouteCache<T: RouteTable>{
+    table: T,
+    policy: CachePolicy,
+    capacity: usize,
+    slots: Vec<Slot>,
+    index: HashMap<u32, usize>,
+    free: Vec<usize>,
+    //lru list, head is the most recently used slot
+    head: usize,
+    tail: usize,
+    //clock hand
+    hand: usize,
+    pub stats: CacheStats,
+}
+
+impl<T: RouteTable> RouteCache<T>{
+    pub fn new(table: T, capacity: usize, policy: CachePolicy) -> Self{
+        RouteCache{
+            table,
+            policy,
+            capacity: capacity.max(1),
+            slots: Vec::new(),
+            index: HashMap::new(),
+            free: Vec::new(),
+            head: NIL,
+            tail: NIL,
+            hand: 0,
+            stats: CacheStats::default(),
+        }
+    }
+
+    pub fn table(&self) -> &T{
+        &self.table
+    }
+
+    pub fn len(&self) -> usize{
+        self.index.len()
+    }
+
+    pub fn lookup(&mut self, ip: u32) -> Option<Route>{
+        if let Some(&slot) = self.index.get(&ip){
+            self.stats.hits += 1;
+            match self.policy{
+                CachePolicy::Lru => {
+                    self.unlink(slot);
+                    self.push_front(slot);
+                }
+                CachePolicy::Clock => self.slots[slot].referenced = true,
+            }
+            return self.slots[slot].route.clone();
+        }
+
+        self.stats.misses += 1;
+        let route = self.table.lookup_route(ip);
+        self.fill(ip, route.clone());
+        route
+    }
+
+    pub fn run_trace(&mut self, trace: &[u32]) -> CacheStats{
+        let before = self.stats;
+        for &ip in trace{
+            self.lookup(ip);
+        }
+        CacheStats{
+            hits: self.stats.hits - before.hits,
+            misses: self.stats.misses - before.misses,
+            evictions: self.stats.evictions - before.evictions,
+            invalidations: self.stats.invalidations - before.invalidations,
+        }
+    }
+
+    pub fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        let new_route = Route::new(prefix, prefix_len, next_hop.clone());
+        //a rejected route changes nothing, so the cache stays as it is
+        self.table.insert(prefix, prefix_len, next_hop)?;
+        //a new prefix only changes answers for covered addresses whose current match is shorter
+        self.invalidate(|cached_ip, cached| {
+            new_route.covers(cached_ip) && cached.as_ref().is_none_or(|r| r.prefix_len <= prefix_len)
+        });
+        Ok(())
+    }
+
+    pub fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        //only addresses that were answered by this exact route can change
+        self.invalidate(|_, cached| {
+            cached.as_ref().is_some_and(|r| r.prefix == prefix && r.prefix_len == prefix_len)
+        });
+        self.table.remove(prefix, prefix_len)
+    }
+
+    fn invalidate<F: Fn(u32, &Option<Route>) -> bool>(&mut self, stale: F){
+        let victims: Vec<usize> = self
+            .index
+            .values()
+            .copied()
+            .filter(|&slot| stale(self.slots[slot].ip, &self.slots[slot].route))
+            .collect();
+
+        for slot in victims{
+            self.index.remove(&self.slots[slot].ip);
+            if self.policy == CachePolicy::Lru{
+                self.unlink(slot);
+            }
+            self.slots[slot].route = None;
+            self.free.push(slot);
+            self.stats.invalidations += 1;
+        }
+    }
+
+    fn fill(&mut self, ip: u32, route: Option<Route>){
+        let slot = if let Some(slot) = self.free.pop(){
+            slot
+        }else if self.slots.len() < self.capacity{
+            self.slots.push(Slot{ ip, route: None, referenced: false, prev: NIL, next: NIL });
+            self.slots.len() - 1
+        }else{
+            self.stats.evictions += 1;
+            let victim = self.victim();
+            self.index.remove(&self.slots[victim].ip);
+            victim
+        };
+
+        self.slots[slot].ip = ip;
+        self.slots[slot].route = route;
+        self.slots[slot].referenced = false;
+        self.index.insert(ip, slot);
+        if self.policy == CachePolicy::Lru{
+            self.push_front(slot);
+        }
+    }
+
+    //only called when every slot is in use
+    fn victim(&mut self) -> usize{
+        match self.policy{
+            CachePolicy::Lru => {
+                let slot = self.tail;
+                self.unlink(slot);
+                slot
+            }
+            CachePolicy::Clock => loop{
+                let slot = self.hand;
+                self.hand = (self.hand + 1) % self.slots.len();
+                if self.slots[slot].referenced{
+                    //second chance
+                    self.slots[slot].referenced = false;
+                }else{
+                    return slot;
+                }
+            },
+        }
+    }
+
+    fn unlink(&mut self, slot: usize){
+        let (prev, next) = (self.slots[slot].prev, self.slots[slot].next);
+        if prev == NIL { self.head = next } else { self.slots[prev].next = next }
+        if next == NIL { self.tail = prev } else { self.slots[next].prev = prev }
+        self.slots[slot].prev = NIL;
+        self.slots[slot].next = NIL;
+    }
+
+    fn push_front(&mut self, slot: usize){
+        self.slots[slot].prev = NIL;
+        self.slots[slot].next = self.head;
+        if self.head != NIL{
+            self.slots[self.head].prev = slot;
+        }
+        self.head = slot;
+        if self.tail == NIL{
+            self.tail = slot;
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::ip_bin_trie::TrieNode;
+    use crate::route_gen::{generate_routes, generate_trace, RouteGenConfig};
+
+    //churn the table under a warm cache, every answer must still match the table
+    #[test]
+    fn churn_invalidates_stale_entries(){
+        let routes = generate_routes(&RouteGenConfig::new(3_000, 28));
+        let trace = generate_trace(&routes, 40_000, 1.0, 28);
+        for policy in [CachePolicy::Lru, CachePolicy::Clock]{
+            let mut table = TrieNode::new();
+            for route in &routes{
+                table.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+            }
+            let mut cache = RouteCache::new(table, 512, policy);
+            cache.run_trace(&trace[..20_000]);
+
+            for route in generate_routes(&RouteGenConfig::new(500, 29)){
+                cache.insert(route.prefix, route.prefix_len, route.next_hop).unwrap();
+            }
+            for route in routes.iter().step_by(10){
+                cache.remove(route.prefix, route.prefix_len);
+            }
+            assert!(cache.stats.invalidations > 0);
+            assert!(cache.len() < 512);
+
+            for &ip in &trace[20_000..]{
+                let expected = cache.table().lookup_route(ip);
+                assert_eq!(cache.lookup(ip), expected);
+            }
+        }
+    }
+}
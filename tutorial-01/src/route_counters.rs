@@ -0,0 +1,137 @@
+use crate::route_table::Route;
+
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct PrefixCounters{
+    pub packets: u64,
+    pub bytes: u64,
+}
+
+impl PrefixCounters{
+    pub fn hit(&mut self, packet_len: u32){
+        self.packets += 1;
+        self.bytes += packet_len as u64;
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CounterOrder{
+    Packets,
+    Bytes,
+}
+
+//busiest prefixes first, ties broken by address so the report is stable
+pub fn top_n(mut counters: Vec<(Route, PrefixCounters)>, n: usize, order: CounterOrder) -> Vec<(Route, PrefixCounters)>{
+    counters.sort_by(|(ra, a), (rb, b)| {
+        let (ka, kb) = match order{
+            CounterOrder::Packets => (a.packets, b.packets),
+            CounterOrder::Bytes => (a.bytes, b.bytes),
+        };
+        kb.cmp(&ka).then((ra.prefix, ra.prefix_len).cmp(&(rb.prefix, rb.prefix_len)))
+    });
+    counters.truncate(n);
+    counters
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::ip_bin_trie::TrieNode;
+    use crate::route_gen::{generate_routes, generate_trace, RouteGenConfig};
+    use crate::route_table::RouteTable;
+    use crate::tcam::{Tcam, TcamLayout};
+
+    fn counted(packets: u64, bytes: u64) -> PrefixCounters{
+        PrefixCounters{ packets, bytes }
+    }
+
+    fn nested() -> TrieNode{
+        let mut trie = TrieNode::new();
+        trie.insert(0, 0, "default".to_string());
+        trie.insert(0x0A00_0000, 8, "a".to_string());
+        trie.insert(0x0A01_0000, 16, "b".to_string());
+        trie
+    }
+
+    #[test]
+    fn hits_land_on_the_longest_match_only(){
+        let mut trie = nested();
+        let table: &mut dyn RouteTable = &mut trie;
+        assert_eq!(table.lookup_counted(0x0A01_0203, 100).map(|r| r.prefix_len), Some(16));
+        table.lookup_counted(0x0A01_0204, 50);
+        table.lookup_counted(0x0A02_0000, 1500);
+        table.lookup_counted(0xC000_0201, 64);
+
+        let mut counters = table.counters();
+        counters.sort_by_key(|(r, _)| r.prefix_len);
+        let got: Vec<(u8, PrefixCounters)> = counters.into_iter().map(|(r, c)| (r.prefix_len, c)).collect();
+        assert_eq!(got, vec![(0, counted(1, 64)), (8, counted(1, 1500)), (16, counted(2, 150))]);
+    }
+
+    #[test]
+    fn reset_zeroes_every_counter(){
+        let mut trie = nested();
+        let mut tcam = Tcam::new(16, TcamLayout::PrefixLengthOrdered);
+        for route in RouteTable::routes(&trie){
+            tcam.insert(route.prefix, route.prefix_len, route.next_hop).unwrap();
+        }
+        for table in [&mut trie as &mut dyn RouteTable, &mut tcam]{
+            for ip in [0x0A01_0203, 0x0A02_0000, 0xC000_0201]{
+                table.lookup_counted(ip, 100);
+            }
+            assert_eq!(table.counters().len(), 3);
+            table.reset_counters();
+            assert!(table.counters().is_empty(), "{}", table.name());
+
+            //counting starts again from zero
+            table.lookup_counted(0x0A01_0203, 40);
+            let counters: Vec<PrefixCounters> = table.counters().into_iter().map(|(_, c)| c).collect();
+            assert_eq!(counters, vec![counted(1, 40)], "{}", table.name());
+        }
+    }
+
+    #[test]
+    fn top_n_orders_by_packets_or_bytes(){
+        let route = |prefix: u32, prefix_len: u8| Route::new(prefix, prefix_len, "hop".to_string());
+        let counters = vec![
+            (route(0x0B00_0000, 8), counted(5, 100)),
+            (route(0x0A00_0000, 8), counted(5, 900)),
+            (route(0x0A00_0000, 16), counted(9, 90)),
+            (route(0x0C00_0000, 8), counted(1, 900)),
+        ];
+        let order = |top: Vec<(Route, PrefixCounters)>| top.into_iter().map(|(r, _)| (r.prefix, r.prefix_len)).collect::<Vec<_>>();
+
+        //equal packet counts fall back to address, then length
+        assert_eq!(
+            order(top_n(counters.clone(), 3, CounterOrder::Packets)),
+            vec![(0x0A00_0000, 16), (0x0A00_0000, 8), (0x0B00_0000, 8)]
+        );
+        assert_eq!(
+            order(top_n(counters.clone(), 10, CounterOrder::Bytes)),
+            vec![(0x0A00_0000, 8), (0x0C00_0000, 8), (0x0B00_0000, 8), (0x0A00_0000, 16)]
+        );
+        assert!(top_n(counters, 0, CounterOrder::Bytes).is_empty());
+    }
+
+    #[test]
+    fn trie_and_tcam_counters_agree(){
+        let routes = generate_routes(&RouteGenConfig::new(1_000, 38));
+        let mut trie = TrieNode::new();
+        let mut tcam = Tcam::new(1_024, TcamLayout::PrefixLengthOrdered);
+        for route in &routes{
+            trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+            tcam.insert(route.prefix, route.prefix_len, route.next_hop.clone()).unwrap();
+        }
+        for (i, ip) in generate_trace(&routes, 20_000, 0.5, 38).into_iter().enumerate(){
+            let len = 64 + (i as u32 * 37) % 1_400;
+            assert_eq!(RouteTable::lookup_counted(&mut trie, ip, len), tcam.lookup_counted(ip, len));
+        }
+
+        let mut from_trie = RouteTable::counters(&trie);
+        let mut from_tcam = tcam.counters();
+        from_trie.sort_by_key(|(r, _)| (r.prefix, r.prefix_len));
+        from_tcam.sort_by_key(|(r, _)| (r.prefix, r.prefix_len));
+        assert!(!from_trie.is_empty());
+        assert_eq!(from_trie, from_tcam);
+        assert_eq!(top_n(from_trie, 10, CounterOrder::Bytes), top_n(from_tcam, 10, CounterOrder::Bytes));
+    }
+}
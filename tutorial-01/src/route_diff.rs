@@ -0,0 +1,206 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::ip_bin_trie::TrieNode;
+use crate::route_table::Route;
+use crate::utils::u32_to_ip;
+
+#[derive(Debug, Default)]
+pub struct RouteDiff{
+    pub added: Vec<Route>,
+    pub removed: Vec<Route>,
+    //(old, new) for prefixes whose next hop changed
+    pub changed: Vec<(Route, Route)>,
+}
+
+impl RouteDiff{
+    pub fn is_empty(&self) -> bool{
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+}
+
+//prefix-level diff, results come back in address order
+pub fn diff_routes(old: &[Route], new: &[Route]) -> RouteDiff{
+    let old_map: BTreeMap<(u32, u8), &Route> = old.iter().map(|r| ((r.prefix, r.prefix_len), r)).collect();
+    let new_map: BTreeMap<(u32, u8), &Route> = new.iter().map(|r| ((r.prefix, r.prefix_len), r)).collect();
+    let mut diff = RouteDiff::default();
+
+    for (key, &route) in &new_map{
+        match old_map.get(key){
+            None => diff.added.push(route.clone()),
+            Some(&before) if before.next_hop != route.next_hop => diff.changed.push((before.clone(), route.clone())),
+            Some(_) => {}
+        }
+    }
+    for (key, &route) in &old_map{
+        if !new_map.contains_key(key){
+            diff.removed.push(route.clone());
+        }
+    }
+    diff
+}
+
+//an address block whose forwarding decision differs between the two tables
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChangedBlock{
+    pub prefix: u32,
+    pub prefix_len: u8,
+    pub old_hop: Option<String>,
+    pub new_hop: Option<String>,
+}
+
+impl ChangedBlock{
+    pub fn addresses(&self) -> u64{
+        1u64 << (32 - self.prefix_len)
+    }
+}
+
+impl fmt::Display for ChangedBlock{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(
+            f,
+            "{}/{}: {} -> {}",
+            u32_to_ip(self.prefix),
+            self.prefix_len,
+            self.old_hop.as_deref().unwrap_or("no route"),
+            self.new_hop.as_deref().unwrap_or("no route")
+        )
+    }
+}
+
+//walks both tries in lockstep carrying each side's inherited next hop,
+//so a prefix change that is shadowed by a more-specific route costs nothing
+pub fn forwarding_changes(old: &TrieNode, new: &TrieNode) -> Vec<ChangedBlock>{
+    let mut out = Vec::new();
+    walk(Some(old), Some(new), None, None, 0, 0, &mut out);
+    out
+}
+
+pub fn changed_addresses(blocks: &[ChangedBlock]) -> u64{
+    blocks.iter().map(|b| b.addresses()).sum()
+}
+
+fn walk<'a>(
+    old: Option<&'a TrieNode>,
+    new: Option<&'a TrieNode>,
+    old_hop: Option<&'a String>,
+    new_hop: Option<&'a String>,
+    prefix: u32,
+    depth: u8,
+    out: &mut Vec<ChangedBlock>,
+){
+    let old_hop = old.and_then(|n| n.value()).or(old_hop);
+    let new_hop = new.and_then(|n| n.value()).or(new_hop);
+
+    let branches = old.is_some_and(|n| n.has_children()) || new.is_some_and(|n| n.has_children());
+    if !branches{
+        //the whole block forwards the same way on each side
+        if old_hop != new_hop{
+            out.push(ChangedBlock{
+                prefix,
+                prefix_len: depth,
+                old_hop: old_hop.cloned(),
+                new_hop: new_hop.cloned(),
+            });
+        }
+        return;
+    }
+
+    for bit in 0..2{
+        let child_prefix = prefix | (bit << (31 - depth as u32));
+        walk(
+            old.and_then(|n| n.child(bit)),
+            new.and_then(|n| n.child(bit)),
+            old_hop,
+            new_hop,
+            child_prefix,
+            depth + 1,
+            out,
+        );
+    }
+
+    //two halves that changed the same way are reported as their parent block
+    let n = out.len();
+    if n >= 2{
+        let (a, b) = (&out[n - 2], &out[n - 1]);
+        let halves = a.prefix_len == depth + 1
+            && b.prefix_len == depth + 1
+            && a.prefix == prefix
+            && b.prefix == prefix | (1 << (31 - depth as u32));
+        if halves && a.old_hop == b.old_hop && a.new_hop == b.new_hop{
+            let merged = ChangedBlock{ prefix, prefix_len: depth, old_hop: a.old_hop.clone(), new_hop: a.new_hop.clone() };
+            out.truncate(n - 2);
+            out.push(merged);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn route(prefix: u32, prefix_len: u8, hop: &str) -> Route{
+        Route::new(prefix, prefix_len, hop.to_string())
+    }
+
+    fn trie(routes: &[Route]) -> TrieNode{
+        let mut trie = TrieNode::new();
+        for r in routes{
+            trie.insert(r.prefix, r.prefix_len, r.next_hop.clone());
+        }
+        trie
+    }
+
+    fn block(prefix: u32, prefix_len: u8, old_hop: Option<&str>, new_hop: Option<&str>) -> ChangedBlock{
+        ChangedBlock{ prefix, prefix_len, old_hop: old_hop.map(str::to_string), new_hop: new_hop.map(str::to_string) }
+    }
+
+    #[test]
+    fn diff_finds_added_removed_and_changed(){
+        let old = [route(0x0A00_0000, 8, "A"), route(0x0B00_0000, 8, "B"), route(0x0C00_0000, 8, "C")];
+        let new = [route(0x0C00_0000, 8, "C"), route(0x0A00_0000, 8, "X"), route(0x0D00_0000, 8, "D")];
+        let diff = diff_routes(&old, &new);
+        assert_eq!(diff.added, vec![route(0x0D00_0000, 8, "D")]);
+        assert_eq!(diff.removed, vec![route(0x0B00_0000, 8, "B")]);
+        assert_eq!(diff.changed, vec![(route(0x0A00_0000, 8, "A"), route(0x0A00_0000, 8, "X"))]);
+        assert!(diff_routes(&old, &old).is_empty());
+
+        let blocks = forwarding_changes(&trie(&old), &trie(&new));
+        assert_eq!(
+            blocks,
+            vec![
+                block(0x0A00_0000, 8, Some("A"), Some("X")),
+                block(0x0B00_0000, 8, Some("B"), None),
+                block(0x0D00_0000, 8, None, Some("D")),
+            ]
+        );
+        assert_eq!(changed_addresses(&blocks), 3 << 24);
+    }
+
+    #[test]
+    fn changes_under_a_more_specific_are_hidden(){
+        //the /8 moves, but its lower half is covered by an unchanged /9
+        let old = [route(0x0A00_0000, 8, "A"), route(0x0A00_0000, 9, "B")];
+        let new = [route(0x0A00_0000, 8, "X"), route(0x0A00_0000, 9, "B")];
+        assert_eq!(diff_routes(&old, &new).changed.len(), 1);
+        assert_eq!(forwarding_changes(&trie(&old), &trie(&new)), vec![block(0x0A80_0000, 9, Some("A"), Some("X"))]);
+
+        //both halves covered: nothing forwards differently
+        let old = [route(0x0A00_0000, 8, "A"), route(0x0A00_0000, 9, "B"), route(0x0A80_0000, 9, "C")];
+        let new = [route(0x0A00_0000, 8, "X"), route(0x0A00_0000, 9, "B"), route(0x0A80_0000, 9, "C")];
+        assert!(forwarding_changes(&trie(&old), &trie(&new)).is_empty());
+    }
+
+    #[test]
+    fn halves_replacing_their_parent_change_nothing(){
+        let old = [route(0x0A00_0000, 8, "A")];
+        let new = [route(0x0A00_0000, 9, "A"), route(0x0A80_0000, 9, "A")];
+        let diff = diff_routes(&old, &new);
+        assert_eq!((diff.added.len(), diff.removed.len(), diff.changed.len()), (2, 1, 0));
+        assert!(forwarding_changes(&trie(&old), &trie(&new)).is_empty());
+
+        //halves that both move the same way come back as the parent block
+        let new = [route(0x0A00_0000, 9, "X"), route(0x0A80_0000, 9, "X")];
+        assert_eq!(forwarding_changes(&trie(&old), &trie(&new)), vec![block(0x0A00_0000, 8, Some("A"), Some("X"))]);
+    }
+}
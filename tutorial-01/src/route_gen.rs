@@ -0,0 +1,192 @@
+use std::collections::HashSet;
+
+use crate::route_table::Route;
+use crate::utils::{prefix_mask, Rng};
+
+//rough share of each prefix length in a full ipv4 bgp table (percent), /24 dominates
+pub const PREFIX_LEN_WEIGHTS: [(u8, f64); 17] = [
+    (8, 0.01),
+    (9, 0.01),
+    (10, 0.03),
+    (11, 0.08),
+    (12, 0.25),
+    (13, 0.45),
+    (14, 0.9),
+    (15, 1.1),
+    (16, 1.4),
+    (17, 0.9),
+    (18, 1.5),
+    (19, 3.2),
+    (20, 4.6),
+    (21, 5.3),
+    (22, 10.4),
+    (23, 9.8),
+    (24, 59.6),
+];
+
+pub struct RouteGenConfig{
+    pub count: usize,
+    pub next_hops: usize,
+    //chance that a new route is carved out of an already generated, shorter one
+    pub nested_fraction: f64,
+    pub seed: u64,
+}
+
+impl RouteGenConfig{
+    pub fn new(count: usize, seed: u64) -> Self{
+        RouteGenConfig{
+            count,
+            next_hops: 16,
+            nested_fraction: 0.3,
+            seed,
+        }
+    }
+}
+
+pub fn generate_routes(config: &RouteGenConfig) -> Vec<Route>{
+    let mut rng = Rng::new(config.seed);
+    let mut seen: HashSet<(u32, u8)> = HashSet::new();
+    let mut routes: Vec<Route> = Vec::with_capacity(config.count);
+    let next_hops = config.next_hops.max(1) as u64;
+
+    //the address space only holds so many distinct prefixes of these lengths
+    let mut attempts = 0;
+    while routes.len() < config.count && attempts < config.count * 20{
+        attempts += 1;
+
+        let parent = if !routes.is_empty() && rng.next_f64() < config.nested_fraction{
+            let candidate = &routes[rng.below(routes.len() as u64) as usize];
+            if candidate.prefix_len < 24 { Some((candidate.prefix, candidate.prefix_len)) } else { None }
+        }else{
+            None
+        };
+
+        let (prefix, prefix_len) = match parent{
+            Some((parent_prefix, parent_len)) => {
+                let len = sample_prefix_len(&mut rng, parent_len + 1);
+                let host_bits = rng.next_u32() & !prefix_mask(parent_len);
+                ((parent_prefix | host_bits) & prefix_mask(len), len)
+            }
+            None => {
+                let len = sample_prefix_len(&mut rng, 8);
+                (random_unicast(&mut rng) & prefix_mask(len), len)
+            }
+        };
+
+        if seen.insert((prefix, prefix_len)){
+            let hop = format!("Router_{}", rng.below(next_hops));
+            routes.push(Route::new(prefix, prefix_len, hop));
+        }
+    }
+
+    routes
+}
+
+//draws from PREFIX_LEN_WEIGHTS restricted to lengths >= min_len
+fn sample_prefix_len(rng: &mut Rng, min_len: u8) -> u8{
+    let total: f64 = PREFIX_LEN_WEIGHTS.iter().filter(|(len, _)| *len >= min_len).map(|(_, w)| w).sum();
+    let mut pick = rng.next_f64() * total;
+
+    for &(len, weight) in PREFIX_LEN_WEIGHTS.iter().filter(|(len, _)| *len >= min_len){
+        if pick < weight{
+            return len;
+        }
+        pick -= weight;
+    }
+    24
+}
+
+//first octet in 1..=223, skipping loopback
+fn random_unicast(rng: &mut Rng) -> u32{
+    loop{
+        let ip = rng.next_u32();
+        let first = ip >> 24;
+        if (1..=223).contains(&first) && first != 127{
+            return ip;
+        }
+    }
+}
+
+pub fn prefix_len_histogram(routes: &[Route]) -> [usize; 33]{
+    let mut histogram = [0usize; 33];
+    for route in routes{
+        histogram[route.prefix_len as usize] += 1;
+    }
+    histogram
+}
+
+//how many routes sit inside a shorter route of the same table
+pub fn nested_count(routes: &[Route]) -> usize{
+    let prefixes: HashSet<(u32, u8)> = routes.iter().map(|r| (r.prefix, r.prefix_len)).collect();
+    routes
+        .iter()
+        .filter(|r| (0..r.prefix_len).any(|len| prefixes.contains(&(r.prefix & prefix_mask(len), len))))
+        .count()
+}
+
+//destination trace with locality: a pool of flows inside the table, picked with zipf(skew) popularity
+pub fn generate_trace(routes: &[Route], count: usize, skew: f64, seed: u64) -> Vec<u32>{
+    let mut rng = Rng::new(seed);
+    let flows = (count / 10).max(1);
+
+    let destinations: Vec<u32> = (0..flows)
+        .map(|_| {
+            if routes.is_empty(){
+                return rng.next_u32();
+            }
+            let route = &routes[rng.below(routes.len() as u64) as usize];
+            route.prefix | (rng.next_u32() & !prefix_mask(route.prefix_len))
+        })
+        .collect();
+
+    //cumulative zipf weights, rank 1 is the most popular flow
+    let mut cdf = Vec::with_capacity(flows);
+    let mut total = 0.0;
+    for rank in 1..=flows{
+        total += 1.0 / (rank as f64).powf(skew);
+        cdf.push(total);
+    }
+
+    (0..count)
+        .map(|_| {
+            let pick = rng.next_f64() * total;
+            let idx = cdf.partition_point(|&c| c <= pick).min(flows - 1);
+            destinations[idx]
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    #[test]
+    fn same_seed_same_table(){
+        let a = generate_routes(&RouteGenConfig::new(2_000, 27));
+        assert_eq!(a, generate_routes(&RouteGenConfig::new(2_000, 27)));
+        assert_ne!(a, generate_routes(&RouteGenConfig::new(2_000, 28)));
+        assert_eq!(generate_trace(&a, 1_000, 1.0, 5), generate_trace(&a, 1_000, 1.0, 5));
+    }
+
+    #[test]
+    fn routes_are_distinct_canonical_and_nested(){
+        let routes = generate_routes(&RouteGenConfig::new(5_000, 27));
+        assert_eq!(routes.len(), 5_000);
+        let distinct: HashSet<(u32, u8)> = routes.iter().map(|r| (r.prefix, r.prefix_len)).collect();
+        assert_eq!(distinct.len(), routes.len());
+        assert!(routes.iter().all(|r| (8..=24).contains(&r.prefix_len) && r.prefix & !prefix_mask(r.prefix_len) == 0));
+        //30% of draws try to carve out of an earlier route, but /24 parents cannot be split
+        let nested = nested_count(&routes);
+        assert!(nested > routes.len() / 10, "{} nested", nested);
+        //the histogram is dominated by /24 like a real table
+        assert!(prefix_len_histogram(&routes)[24] > routes.len() / 2);
+    }
+
+    #[test]
+    fn trace_stays_inside_the_table(){
+        let routes = generate_routes(&RouteGenConfig::new(1_000, 27));
+        for ip in generate_trace(&routes, 5_000, 1.0, 3){
+            assert!(routes.iter().any(|r| r.covers(ip)));
+        }
+    }
+}
@@ -0,0 +1,223 @@
+use std::fmt;
+
+use crate::bloom_lpm::BloomLpm;
+use crate::ip_bst::BSTNode;
+use crate::ip_bin_trie::TrieNode;
+use crate::lc_trie::{LcTrie, LcTrieConfig};
+use crate::poptrie::Poptrie;
+use crate::route_counters::PrefixCounters;
+use crate::tcam::{Tcam, TcamLayout};
+use crate::utils::{prefix_mask, u32_to_ip};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Route{
+    pub prefix: u32,
+    pub prefix_len: u8,
+    pub next_hop: String,
+}
+
+impl Route{
+    pub fn new(prefix: u32, prefix_len: u8, next_hop: String) -> Self{
+        Route{
+            prefix,
+            prefix_len,
+            next_hop,
+        }
+    }
+
+    pub fn covers(&self, ip: u32) -> bool{
+        let mask = prefix_mask(self.prefix_len);
+        (ip & mask) == (self.prefix & mask)
+    }
+}
+
+impl fmt::Display for Route{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{}/{} -> {}", u32_to_ip(self.prefix), self.prefix_len, self.next_hop)
+    }
+}
+
+//common interface so the shell (and benchmarks) can swap lpm structures
+pub trait RouteTable{
+    fn name(&self) -> &'static str;
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>;
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>;
+    fn lookup_route(&self, ip: u32) -> Option<Route>;
+    fn routes(&self) -> Vec<Route>;
+    fn node_count(&self) -> usize;
+
+    //backends with per-route counters override these, the rest just look up
+    fn lookup_counted(&mut self, ip: u32, _packet_len: u32) -> Option<Route>{
+        self.lookup_route(ip)
+    }
+
+    fn counters(&self) -> Vec<(Route, PrefixCounters)>{
+        Vec::new()
+    }
+
+    fn reset_counters(&mut self){}
+}
+
+impl RouteTable for TrieNode{
+    fn name(&self) -> &'static str{
+        "trie"
+    }
+
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        TrieNode::insert(self, prefix, prefix_len, next_hop);
+        Ok(())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        TrieNode::remove(self, prefix, prefix_len)
+    }
+
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        TrieNode::lookup_route(self, ip)
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        TrieNode::routes(self)
+    }
+
+    fn node_count(&self) -> usize{
+        TrieNode::node_count(self)
+    }
+
+    fn lookup_counted(&mut self, ip: u32, packet_len: u32) -> Option<Route>{
+        TrieNode::lookup_counted(self, ip, packet_len).map(|(prefix, len, hop)| Route::new(prefix, len, hop.clone()))
+    }
+
+    fn counters(&self) -> Vec<(Route, PrefixCounters)>{
+        TrieNode::counters(self)
+            .into_iter()
+            .map(|(prefix, len, hop, counters)| (Route::new(prefix, len, hop.clone()), counters))
+            .collect()
+    }
+
+    fn reset_counters(&mut self){
+        TrieNode::reset_counters(self);
+    }
+}
+
+//BSTNode has no empty state, so the table owns an optional root
+#[derive(Debug, Default)]
+pub struct BstTable{
+    root: Option<Box<BSTNode>>,
+}
+
+impl BstTable{
+    pub fn new() -> Self{
+        BstTable{ root: None }
+    }
+}
+
+impl RouteTable for BstTable{
+    fn name(&self) -> &'static str{
+        "bst"
+    }
+
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        //replace an existing route instead of keeping a duplicate node
+        self.remove(prefix, prefix_len);
+        match &mut self.root{
+            Some(root) => root.insert(prefix, prefix_len, next_hop),
+            None => self.root = Some(Box::new(BSTNode::new(prefix, prefix_len, next_hop))),
+        }
+        Ok(())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        let (root, removed) = BSTNode::remove(self.root.take(), prefix, prefix_len);
+        self.root = root;
+        removed
+    }
+
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        self.root.as_ref()?.lookup_route(ip)
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        let mut out = Vec::new();
+        if let Some(ref root) = self.root{
+            root.collect(&mut out);
+        }
+        out
+    }
+
+    fn node_count(&self) -> usize{
+        self.routes().len()
+    }
+}
+
+pub fn backend_by_name(name: &str) -> Option<Box<dyn RouteTable>>{
+    match name{
+        "trie" => Some(Box::new(TrieNode::new())),
+        "bst" => Some(Box::new(BstTable::new())),
+        "bloom" => Some(Box::new(BloomLpm::new(10, 7))),
+        "lc-trie" => Some(Box::new(LcTrie::new(LcTrieConfig::new(0.5, 16)))),
+        "poptrie" => Some(Box::new(Poptrie::new(18))),
+        "tcam" => Some(Box::new(Tcam::new(1 << 18, TcamLayout::Naive))),
+        "tcam-plo" => Some(Box::new(Tcam::new(1 << 18, TcamLayout::PrefixLengthOrdered))),
+        _ => None,
+    }
+}
+
+pub const BACKEND_NAMES: &[&str] = &["trie", "bst", "bloom", "lc-trie", "poptrie", "tcam", "tcam-plo"];
+
+//shared reference check for the backend tests: same answers as the binary
+//trie on generated routes, before and after a round of announce/withdraw churn
+#[cfg(test)]
+pub mod reference{
+    use super::RouteTable;
+    use crate::ip_bin_trie::TrieNode;
+    use crate::route_gen::{generate_routes, generate_trace, RouteGenConfig};
+    use crate::utils::Rng;
+
+    pub fn check_against_trie(table: &mut dyn RouteTable, count: usize, seed: u64){
+        let routes = generate_routes(&RouteGenConfig::new(count, seed));
+        let mut trie = TrieNode::new();
+        for route in &routes{
+            table.insert(route.prefix, route.prefix_len, route.next_hop.clone()).unwrap();
+            trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        }
+
+        let mut rng = Rng::new(seed);
+        let mut trace = generate_trace(&routes, 10_000, 0.0, seed);
+        trace.extend((0..10_000).map(|_| rng.next_u32()));
+        assert_same(table, &trie, &trace);
+
+        let updates = generate_routes(&RouteGenConfig::new(count / 5, seed + 1));
+        for route in &updates{
+            table.insert(route.prefix, route.prefix_len, route.next_hop.clone()).unwrap();
+            trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        }
+        for route in updates.iter().step_by(2).chain(routes.iter().step_by(10)){
+            assert_eq!(table.remove(route.prefix, route.prefix_len), trie.remove(route.prefix, route.prefix_len));
+        }
+        assert_same(table, &trie, &trace);
+    }
+
+    fn assert_same(table: &dyn RouteTable, trie: &TrieNode, trace: &[u32]){
+        for &ip in trace{
+            assert_eq!(table.lookup_route(ip), trie.lookup_route(ip), "{} at {:08x}", table.name(), ip);
+        }
+        let mut routes = table.routes();
+        let mut expected = trie.routes();
+        routes.sort_by_key(|r| (r.prefix, r.prefix_len));
+        expected.sort_by_key(|r| (r.prefix, r.prefix_len));
+        assert_eq!(routes, expected);
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use reference::check_against_trie;
+
+    //every lookup walks the whole tree, so a smaller table than the other backends
+    #[test]
+    fn bst_matches_trie(){
+        check_against_trie(&mut BstTable::new(), 1_000, 26);
+    }
+}
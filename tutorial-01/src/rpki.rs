@@ -0,0 +1,359 @@
+use std::fmt;
+
+use crate::ip_bin_trie::TrieNode;
+use crate::route_table::{Route, RouteTable};
+use crate::utils::{parse_prefix, prefix_mask, u32_to_ip};
+
+//validated roa payload: prefix, max length and the origin as allowed to announce it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Vrp{
+    pub prefix: u32,
+    pub prefix_len: u8,
+    pub max_len: u8,
+    pub asn: u32,
+}
+
+impl fmt::Display for Vrp{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "AS{} {}/{}-{}", self.asn, u32_to_ip(self.prefix), self.prefix_len, self.max_len)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Validity{
+    Valid,
+    Invalid,
+    NotFound,
+}
+
+impl fmt::Display for Validity{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        match self{
+            Validity::Valid => f.pad("valid"),
+            Validity::Invalid => f.pad("invalid"),
+            Validity::NotFound => f.pad("not-found"),
+        }
+    }
+}
+
+//a route as learned from bgp, with its origin and the outcome of origin validation
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BgpRoute{
+    pub route: Route,
+    pub origin_as: u32,
+    pub validity: Validity,
+}
+
+impl fmt::Display for BgpRoute{
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result{
+        write!(f, "{} origin AS{} [{}]", self.route, self.origin_as, self.validity)
+    }
+}
+
+//what a load did with its rows: duplicates of vrps already held are not counted as added
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+pub struct LoadSummary{
+    pub added: usize,
+    pub duplicates: usize,
+    //ipv6 vrps, the table only holds ipv4
+    pub skipped: usize,
+}
+
+//vrps keyed by prefix in the binary trie, so the covering set is the path to the route's node
+pub struct VrpTable{
+    trie: TrieNode<Vec<Vrp>>,
+    count: usize,
+}
+
+impl VrpTable{
+    pub fn new() -> Self{
+        VrpTable{
+            trie: TrieNode::new(),
+            count: 0,
+        }
+    }
+
+    pub fn len(&self) -> usize{
+        self.count
+    }
+
+    //false if the vrp was already there
+    pub fn add(&mut self, vrp: Vrp) -> bool{
+        match self.trie.get_mut(vrp.prefix, vrp.prefix_len){
+            Some(vrps) => {
+                if vrps.contains(&vrp){
+                    return false;
+                }
+                vrps.push(vrp);
+            }
+            None => self.trie.insert(vrp.prefix, vrp.prefix_len, vec![vrp]),
+        }
+        self.count += 1;
+        true
+    }
+
+    fn load_row(&mut self, summary: &mut LoadSummary, asn: &str, prefix: &str, max_len: &str) -> Option<()>{
+        if prefix.contains(':'){
+            summary.skipped += 1;
+            return Some(());
+        }
+        if self.add(parse_vrp(asn, prefix, max_len)?){
+            summary.added += 1;
+        }else{
+            summary.duplicates += 1;
+        }
+        Some(())
+    }
+
+    //routinator/rpki-client style csv: ASN,IP Prefix,Max Length[,Trust Anchor]
+    pub fn load_csv(&mut self, text: &str) -> Result<LoadSummary, String>{
+        let mut summary = LoadSummary::default();
+        for (n, line) in text.lines().enumerate(){
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') || line.to_ascii_lowercase().starts_with("asn"){
+                continue;
+            }
+
+            let fields: Vec<&str> = line.split(',').map(str::trim).collect();
+            let loaded = match fields.as_slice(){
+                [asn, prefix, max_len, ..] => self.load_row(&mut summary, asn, prefix, max_len),
+                _ => None,
+            };
+            loaded.ok_or_else(|| format!("line {}: cannot parse '{}'", n + 1, line))?;
+        }
+        Ok(summary)
+    }
+
+    //the usual json export: {"roas": [{"asn": "AS13335", "prefix": "1.1.1.0/24", "maxLength": 24, ...}]}
+    //only these three keys are read, so a small scanner is enough instead of a json crate.
+    //other members may nest (routinator's jsonext has a "source" array per roa)
+    pub fn load_json(&mut self, text: &str) -> Result<LoadSummary, String>{
+        let members = json_members(text)?;
+        let roas = members.iter().find(|(key, _)| *key == "roas").ok_or("no \"roas\" array")?.1;
+        if !roas.starts_with('['){
+            return Err("\"roas\" is not an array".to_string());
+        }
+        let mut summary = LoadSummary::default();
+
+        for object in json_items(roas)?{
+            let members = json_members(object)?;
+            let field = |key: &str| members.iter().find(|(k, _)| *k == key).map(|(_, v)| unquote(v));
+            let loaded = match (field("asn"), field("prefix"), field("maxLength")){
+                (Some(asn), Some(prefix), Some(max_len)) => self.load_row(&mut summary, asn, prefix, max_len),
+                _ => None,
+            };
+            loaded.ok_or_else(|| format!("cannot parse roa {}", object))?;
+        }
+        Ok(summary)
+    }
+
+    pub fn covering(&self, prefix: u32, prefix_len: u8) -> Vec<Vrp>{
+        self.trie
+            .covering(prefix, prefix_len)
+            .into_iter()
+            .flat_map(|(_, _, vrps)| vrps.iter().copied())
+            .collect()
+    }
+
+    //rfc 6811: valid if a covering vrp has this origin and allows this length,
+    //invalid if vrps cover the prefix but none match, not-found if nothing covers it
+    pub fn validate(&self, prefix: u32, prefix_len: u8, origin_as: u32) -> Validity{
+        let covering = self.covering(prefix & prefix_mask(prefix_len), prefix_len);
+        if covering.is_empty(){
+            return Validity::NotFound;
+        }
+
+        //as0 roas never validate anything
+        let matched = covering.iter().any(|v| v.asn != 0 && v.asn == origin_as && prefix_len <= v.max_len);
+        if matched { Validity::Valid } else { Validity::Invalid }
+    }
+
+    pub fn annotate(&self, route: Route, origin_as: u32) -> BgpRoute{
+        let validity = self.validate(route.prefix, route.prefix_len, origin_as);
+        BgpRoute{ route, origin_as, validity }
+    }
+}
+
+fn parse_vrp(asn: &str, prefix: &str, max_len: &str) -> Option<Vrp>{
+    let asn: u32 = asn.trim().trim_start_matches("AS").trim_start_matches("as").parse().ok()?;
+    let (prefix, prefix_len) = parse_prefix(prefix)?;
+    let max_len: u8 = max_len.trim().parse().ok()?;
+    if max_len < prefix_len || max_len > 32{
+        return None;
+    }
+    Some(Vrp{ prefix, prefix_len, max_len, asn })
+}
+
+//end of the json value starting at bytes[start]: the next comma outside any
+//string, object or array, or the end of the input. none if the brackets do not balance
+fn json_value_end(bytes: &[u8], start: usize) -> Option<usize>{
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut i = start;
+    while i < bytes.len(){
+        match (in_string, bytes[i]){
+            (true, b'\\') => i += 1,
+            (true, b'"') => in_string = false,
+            (true, _) => {}
+            (false, b'"') => in_string = true,
+            (false, b'{' | b'[') => depth += 1,
+            (false, b'}' | b']') => depth = depth.checked_sub(1)?,
+            (false, b',') if depth == 0 => return Some(i),
+            _ => {}
+        }
+        i += 1;
+    }
+    (depth == 0 && !in_string).then_some(i)
+}
+
+//top-level values of a json object or array, nested ones come back whole
+fn json_items(text: &str) -> Result<Vec<&str>, String>{
+    let text = text.trim();
+    let inner = match (text.chars().next(), text.chars().last()){
+        (Some('{'), Some('}')) | (Some('['), Some(']')) => &text[1..text.len() - 1],
+        _ => return Err(format!("expected a json object or array at '{}'", text.chars().take(20).collect::<String>())),
+    };
+    let bytes = inner.as_bytes();
+    let mut items = Vec::new();
+    let mut start = 0;
+    while start < bytes.len(){
+        let end = json_value_end(bytes, start).ok_or("unbalanced json")?;
+        let item = inner[start..end].trim();
+        if !item.is_empty(){
+            items.push(item);
+        }
+        start = end + 1;
+    }
+    Ok(items)
+}
+
+//"key": value pairs of one json object, values left raw
+fn json_members(object: &str) -> Result<Vec<(&str, &str)>, String>{
+    json_items(object)?
+        .into_iter()
+        .map(|item| {
+            let (key, rest) = item.strip_prefix('"').and_then(|r| r.split_once('"')).ok_or_else(|| format!("bad json member {}", item))?;
+            let value = rest.trim_start().strip_prefix(':').ok_or_else(|| format!("bad json member {}", item))?;
+            Ok((key, value.trim()))
+        })
+        .collect()
+}
+
+//strings lose their quotes, numbers stay as they are; vrp fields never need unescaping
+fn unquote(value: &str) -> &str{
+    value.strip_prefix('"').and_then(|v| v.strip_suffix('"')).unwrap_or(value)
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct RovSummary{
+    pub valid: usize,
+    pub invalid: usize,
+    pub not_found: usize,
+    pub installed: usize,
+    //refused by the fib, e.g. a full tcam
+    pub rejected: usize,
+}
+
+//installs routes into a fib, optionally dropping rov-invalid ones
+pub fn install_routes(fib: &mut dyn RouteTable, routes: &[BgpRoute], drop_invalid: bool) -> RovSummary{
+    let mut summary = RovSummary::default();
+    for bgp in routes{
+        match bgp.validity{
+            Validity::Valid => summary.valid += 1,
+            Validity::Invalid => summary.invalid += 1,
+            Validity::NotFound => summary.not_found += 1,
+        }
+        if drop_invalid && bgp.validity == Validity::Invalid{
+            continue;
+        }
+        match fib.insert(bgp.route.prefix, bgp.route.prefix_len, bgp.route.next_hop.clone()){
+            Ok(()) => summary.installed += 1,
+            Err(_) => summary.rejected += 1,
+        }
+    }
+    summary
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    //routinator jsonext: every roa carries a nested source array, and uris may hold braces
+    const JSONEXT: &str = r#"{
+  "metadata": {"generated": 1700000000, "generatedTime": "2023-11-14T22:13:20Z"},
+  "roas": [
+    {"asn": "AS13335", "prefix": "1.1.1.0/24", "maxLength": 24,
+     "source": [{"type": "roa", "uri": "rsync://rpki.example/repo/{a}.roa",
+                 "validity": {"notBefore": "2023-01-01T00:00:00Z", "notAfter": "2024-01-01T00:00:00Z"},
+                 "chainValidity": {"notBefore": "2023-01-01T00:00:00Z", "notAfter": "2024-01-01T00:00:00Z"}}]},
+    {"asn": "AS64500", "prefix": "192.0.2.0/24", "maxLength": 25,
+     "source": [{"type": "roa", "uri": "rsync://rpki.example/repo/b.roa", "tags": ["x", "}]"]},
+                {"type": "aspa", "uri": "rsync://rpki.example/repo/c.asa"}]}
+  ]
+}"#;
+
+    #[test]
+    fn load_json_skips_nested_members(){
+        let mut table = VrpTable::new();
+        assert_eq!(table.load_json(JSONEXT).map(|s| s.added), Ok(2));
+        assert_eq!(table.len(), 2);
+        assert_eq!(table.validate(0x0101_0100, 24, 13335), Validity::Valid);
+        assert_eq!(table.validate(0xC000_0280, 25, 64500), Validity::Valid);
+        assert_eq!(table.validate(0xC000_0280, 25, 64501), Validity::Invalid);
+    }
+
+    #[test]
+    fn load_csv_skips_ipv6_and_counts_duplicates(){
+        let mut table = VrpTable::new();
+        let csv = "ASN,IP Prefix,Max Length,Trust Anchor\n\
+                   # comment\n\
+                   AS64500,10.0.0.0/8,16,ta\n\
+                   AS64500,2001:db8::/32,48,ta\n\
+                   AS64501,10.0.0.0/8,8,ta\n\
+                   AS64500,10.0.0.0/8,16,ta\n\
+                   \n\
+                   AS0,192.0.2.0/24,32,ta\n";
+        assert_eq!(table.load_csv(csv), Ok(LoadSummary{ added: 3, duplicates: 1, skipped: 1 }));
+        assert_eq!(table.len(), 3);
+        //loading the same file again adds nothing
+        assert_eq!(table.load_csv(csv), Ok(LoadSummary{ added: 0, duplicates: 4, skipped: 1 }));
+        assert_eq!(table.len(), 3);
+    }
+
+    #[test]
+    fn load_csv_rejects_bad_rows(){
+        let mut table = VrpTable::new();
+        assert!(table.load_csv("AS1,10.0.0.0/8").is_err());
+        assert!(table.load_csv("AS1,10.0.0.0/16,8").is_err());
+        assert!(table.load_csv("AS1,10.0.0.0/8,33").is_err());
+        assert_eq!(table.len(), 0);
+    }
+
+    #[test]
+    fn validate_checks_origin_and_max_length(){
+        let mut table = VrpTable::new();
+        table.add(Vrp{ prefix: 0x0A00_0000, prefix_len: 8, max_len: 16, asn: 64500 });
+        table.add(Vrp{ prefix: 0xC000_0200, prefix_len: 24, max_len: 32, asn: 0 });
+
+        assert_eq!(table.validate(0x0A00_0000, 8, 64500), Validity::Valid);
+        assert_eq!(table.validate(0x0A01_0000, 16, 64500), Validity::Valid);
+        //longer than max_len, or the wrong origin
+        assert_eq!(table.validate(0x0A01_0100, 24, 64500), Validity::Invalid);
+        assert_eq!(table.validate(0x0A01_0000, 16, 64501), Validity::Invalid);
+        //as0 covers the prefix but never validates, not even for origin 0
+        assert_eq!(table.validate(0xC000_0200, 24, 0), Validity::Invalid);
+        assert_eq!(table.validate(0xC000_0280, 25, 64500), Validity::Invalid);
+        //nothing covers it, a less specific of a vrp is not covered either
+        assert_eq!(table.validate(0x0B00_0000, 8, 64500), Validity::NotFound);
+        assert_eq!(table.validate(0x0A00_0000, 7, 64500), Validity::NotFound);
+    }
+
+    #[test]
+    fn load_json_rejects_broken_input(){
+        let mut table = VrpTable::new();
+        assert!(table.load_json(r#"{"roas": [{"asn": "AS1", "prefix": "10.0.0.0/8", "maxLength": 8}"#).is_err());
+        assert!(table.load_json(r#"{"roas": [{"asn": "AS1", "prefix": "10.0.0.0/8"}]}"#).is_err());
+        assert!(table.load_json(r#"{"vrps": []}"#).is_err());
+        assert_eq!(table.load_json(r#"{"roas": []}"#), Ok(LoadSummary::default()));
+    }
+}
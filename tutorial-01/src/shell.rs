@@ -0,0 +1,396 @@
+use std::fs;
+use std::io::{self, BufRead, Write};
+
+use crate::ip_bin_trie::TrieNode;
+use crate::route_counters::{top_n, CounterOrder};
+use crate::route_diff::{changed_addresses, diff_routes, forwarding_changes};
+use crate::route_gen::{generate_routes, RouteGenConfig};
+use crate::route_table::{backend_by_name, Route, RouteTable, BACKEND_NAMES};
+use crate::utils::{parse_ip, parse_prefix, u32_to_ip, Rng};
+
+const HELP: &str = "commands:
+  add <prefix/len> <next_hop>   insert or replace a route
+  del <prefix/len>              remove a route
+  lookup <ip> [bytes]           longest prefix match, counted as a packet of that size
+  show routes                   list every route in the table
+  show stats                    table size and lookup counters
+  show top [n]                  busiest prefixes by packets
+  clear counters                reset the per-prefix counters
+  load <file>                   read \"prefix/len next_hop\" lines from a file
+  reload <file>                 replace the table with a file and show the churn
+  gen <count> [seed]            add a synthetic internet-like table
+  backend <name>                switch lpm structure, keeping the routes
+  help                          this text
+  quit                          leave the shell";
+
+//everything the shell prints goes through its writer, so tests can read it back
+macro_rules! say{
+    ($out:expr, $($arg:tt)*) => {{
+        let _ = writeln!($out, $($arg)*);
+    }};
+}
+
+pub struct Shell<W: Write = io::Stdout>{
+    table: Box<dyn RouteTable>,
+    lookups: usize,
+    hits: usize,
+    out: W,
+}
+
+impl Shell{
+    pub fn new(table: Box<dyn RouteTable>) -> Self{
+        Shell::with_output(table, io::stdout())
+    }
+}
+
+impl<W: Write> Shell<W>{
+    pub fn with_output(table: Box<dyn RouteTable>, out: W) -> Self{
+        Shell{
+            table,
+            lookups: 0,
+            hits: 0,
+            out,
+        }
+    }
+
+    //returns false once the user asks to quit
+    pub fn execute(&mut self, line: &str) -> bool{
+        let words: Vec<&str> = line.split_whitespace().collect();
+
+        match words.as_slice(){
+            [] => {}
+            ["add", prefix, hop] => self.add(prefix, hop),
+            ["del", prefix] => self.del(prefix),
+            ["lookup", ip] => self.lookup(ip, "64"),
+            ["lookup", ip, bytes] => self.lookup(ip, bytes),
+            ["show", "routes"] => self.show_routes(),
+            ["show", "stats"] => self.show_stats(),
+            ["show", "top"] => self.show_top("10"),
+            ["show", "top", n] => self.show_top(n),
+            ["clear", "counters"] => {
+                self.table.reset_counters();
+                say!(self.out, "counters cleared");
+            }
+            ["load", path] => self.load(path),
+            ["reload", path] => self.reload(path),
+            ["gen", count] => self.generate(count, "1"),
+            ["gen", count, seed] => self.generate(count, seed),
+            ["backend", name] => self.switch_backend(name),
+            ["help"] => say!(self.out, "{}", HELP),
+            ["quit"] | ["exit"] => return false,
+            _ => say!(self.out, "unknown command '{}', try 'help'", line.trim()),
+        }
+        true
+    }
+
+    fn add(&mut self, prefix: &str, hop: &str){
+        let Some((prefix, len)) = parse_prefix(prefix) else{
+            say!(self.out, "bad prefix '{}', expected a.b.c.d/len", prefix);
+            return;
+        };
+
+        let old = self.table.remove(prefix, len);
+        if let Err(e) = self.table.insert(prefix, len, hop.to_string()){
+            //the slot the old route freed is still there, so putting it back cannot fail
+            if let Some(old) = old{
+                let _ = self.table.insert(prefix, len, old);
+            }
+            say!(self.out, "cannot add {}/{}: {}", u32_to_ip(prefix), len, e);
+            return;
+        }
+        match old{
+            Some(old) => say!(self.out, "replaced {}/{} ({} -> {})", u32_to_ip(prefix), len, old, hop),
+            None => say!(self.out, "added {}/{} -> {}", u32_to_ip(prefix), len, hop),
+        }
+    }
+
+    fn del(&mut self, prefix: &str){
+        let Some((prefix, len)) = parse_prefix(prefix) else{
+            say!(self.out, "bad prefix '{}', expected a.b.c.d/len", prefix);
+            return;
+        };
+
+        match self.table.remove(prefix, len){
+            Some(hop) => say!(self.out, "removed {}/{} -> {}", u32_to_ip(prefix), len, hop),
+            None => say!(self.out, "no route for {}/{}", u32_to_ip(prefix), len),
+        }
+    }
+
+    fn lookup(&mut self, ip_str: &str, bytes: &str){
+        let Some(ip) = parse_ip(ip_str) else{
+            say!(self.out, "bad address '{}'", ip_str);
+            return;
+        };
+        let Ok(bytes) = bytes.parse() else{
+            say!(self.out, "bad packet size '{}'", bytes);
+            return;
+        };
+        self.lookups += 1;
+
+        //every covering prefix is a candidate, the longest one wins
+        let mut covering: Vec<_> = self.table.routes().into_iter().filter(|r| r.covers(ip)).collect();
+        covering.sort_by_key(|r| r.prefix_len);
+
+        match self.table.lookup_counted(ip, bytes){
+            Some(route) => {
+                self.hits += 1;
+                say!(self.out, "{} matched {} [{}]", u32_to_ip(ip), route, self.table.name());
+                let candidates: Vec<String> = covering
+                    .iter()
+                    .map(|r| format!("{}/{}", u32_to_ip(r.prefix), r.prefix_len))
+                    .collect();
+                say!(self.out, 
+                    "  why: longest of {} covering prefix(es): {}",
+                    covering.len(),
+                    candidates.join(", ")
+                );
+            }
+            None => say!(self.out, "{} has no route: no prefix in the table covers it", u32_to_ip(ip)),
+        }
+    }
+
+    fn show_routes(&mut self){
+        let mut routes = self.table.routes();
+        routes.sort_by_key(|r| (r.prefix, r.prefix_len));
+        for route in &routes{
+            say!(self.out, "  {}", route);
+        }
+        say!(self.out, "{} route(s)", routes.len());
+    }
+
+    fn show_top(&mut self, n: &str){
+        let Ok(n) = n.parse() else{
+            say!(self.out, "usage: show top [n]");
+            return;
+        };
+
+        let counters = self.table.counters();
+        if counters.is_empty(){
+            say!(self.out, "no counters on the {} backend yet", self.table.name());
+            return;
+        }
+        for (route, c) in top_n(counters, n, CounterOrder::Packets){
+            say!(self.out, "  {:<32} {:>8} pkts {:>10} bytes", route.to_string(), c.packets, c.bytes);
+        }
+    }
+
+    fn show_stats(&mut self){
+        say!(self.out, "backend: {}", self.table.name());
+        say!(self.out, "routes: {}", self.table.routes().len());
+        say!(self.out, "nodes: {}", self.table.node_count());
+        say!(self.out, "lookups: {} ({} hit, {} miss)", self.lookups, self.hits, self.lookups - self.hits);
+    }
+
+    //inserts what the backend accepts and reports the rest, returns how many were refused
+    fn insert_all(&mut self, routes: &[Route]) -> usize{
+        let mut rejected = 0;
+        for route in routes{
+            if let Err(e) = self.table.insert(route.prefix, route.prefix_len, route.next_hop.clone()){
+                if rejected == 0{
+                    say!(self.out, "{}: {}", route, e);
+                }
+                rejected += 1;
+            }
+        }
+        if rejected > 0{
+            say!(self.out, "{} route(s) rejected by the {} backend", rejected, self.table.name());
+        }
+        rejected
+    }
+
+    fn load(&mut self, path: &str){
+        let Some(routes) = read_route_file(path, &mut self.out) else{
+            return;
+        };
+        let rejected = self.insert_all(&routes);
+        say!(self.out, "loaded {} route(s) from {}", routes.len() - rejected, path);
+    }
+
+    //replaces the whole table with the file and reports what changed
+    fn reload(&mut self, path: &str){
+        let Some(routes) = read_route_file(path, &mut self.out) else{
+            return;
+        };
+
+        let old_routes = self.table.routes();
+        let diff = diff_routes(&old_routes, &routes);
+        let (mut old_trie, mut new_trie) = (TrieNode::new(), TrieNode::new());
+        for route in &old_routes{
+            old_trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        }
+        for route in &routes{
+            new_trie.insert(route.prefix, route.prefix_len, route.next_hop.clone());
+        }
+        let blocks = forwarding_changes(&old_trie, &new_trie);
+
+        for route in &diff.removed{
+            self.table.remove(route.prefix, route.prefix_len);
+        }
+        let updated: Vec<Route> = diff.added.iter().chain(diff.changed.iter().map(|(_, new)| new)).cloned().collect();
+        self.insert_all(&updated);
+
+        if diff.is_empty(){
+            say!(self.out, "no changes in {}", path);
+            return;
+        }
+
+        for route in &diff.added{
+            say!(self.out, "  + {}", route);
+        }
+        for route in &diff.removed{
+            say!(self.out, "  - {}", route);
+        }
+        for (old, new) in &diff.changed{
+            say!(self.out, "  ~ {} (was {})", new, old.next_hop);
+        }
+        say!(self.out, 
+            "{} added, {} removed, {} next hop changed",
+            diff.added.len(),
+            diff.removed.len(),
+            diff.changed.len()
+        );
+        for block in &blocks{
+            say!(self.out, "  forwarding changed for {}", block);
+        }
+        say!(self.out, "{} address(es) forward differently", changed_addresses(&blocks));
+    }
+
+    fn generate(&mut self, count: &str, seed: &str){
+        let (Ok(count), Ok(seed)) = (count.parse(), seed.parse()) else{
+            say!(self.out, "usage: gen <count> [seed]");
+            return;
+        };
+
+        let routes = generate_routes(&RouteGenConfig::new(count, seed));
+        let rejected = self.insert_all(&routes);
+        say!(self.out, "generated {} route(s) with seed {}", routes.len() - rejected, seed);
+    }
+
+    fn switch_backend(&mut self, name: &str){
+        let Some(mut table) = backend_by_name(name) else{
+            say!(self.out, "unknown backend '{}', choose one of: {}", name, BACKEND_NAMES.join(", "));
+            return;
+        };
+
+        //routes() comes back in address order, which would degrade the bst into a list
+        let mut routes = self.table.routes();
+        Rng::new(routes.len() as u64).shuffle(&mut routes);
+
+        for route in routes{
+            if let Err(e) = table.insert(route.prefix, route.prefix_len, route.next_hop){
+                say!(self.out, "cannot switch to {}: {}, staying on {}", name, e, self.table.name());
+                return;
+            }
+        }
+        self.table = table;
+        say!(self.out, "using {} backend", self.table.name());
+    }
+}
+
+pub fn run(backend: &str){
+    let Some(table) = backend_by_name(backend) else{
+        println!("unknown backend '{}', choose one of: {}", backend, BACKEND_NAMES.join(", "));
+        return;
+    };
+
+    let mut shell = Shell::new(table);
+    println!("route table shell ({} backend), type 'help' for commands", backend);
+
+    let stdin = io::stdin();
+    loop{
+        print!("> ");
+        io::stdout().flush().ok();
+
+        let mut line = String::new();
+        match stdin.lock().read_line(&mut line){
+            Ok(0) | Err(_) => break,
+            Ok(_) => {
+                if !shell.execute(&line){
+                    break;
+                }
+            }
+        }
+    }
+}
+
+//"prefix/len next_hop" per line, blank lines and # comments skipped
+fn read_route_file(path: &str, out: &mut impl Write) -> Option<Vec<Route>>{
+    let contents = match fs::read_to_string(path){
+        Ok(contents) => contents,
+        Err(e) => {
+            say!(out, "cannot read {}: {}", path, e);
+            return None;
+        }
+    };
+
+    let mut routes = Vec::new();
+    for (n, line) in contents.lines().enumerate(){
+        let line = line.trim();
+        if line.is_empty() || line.starts_with('#'){
+            continue;
+        }
+
+        let parsed = line
+            .split_once(char::is_whitespace)
+            .and_then(|(prefix, hop)| Some((parse_prefix(prefix)?, hop.trim())));
+        match parsed{
+            Some(((prefix, len), hop)) => routes.push(Route::new(prefix, len, hop.to_string())),
+            None => say!(out, "  {}:{}: skipping '{}'", path, n + 1, line),
+        }
+    }
+    Some(routes)
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+
+    fn output(shell: &mut Shell<Vec<u8>>, line: &str) -> String{
+        assert!(shell.execute(line));
+        String::from_utf8(std::mem::take(&mut shell.out)).unwrap()
+    }
+
+    fn shell(backend: &str) -> Shell<Vec<u8>>{
+        Shell::with_output(backend_by_name(backend).unwrap(), Vec::new())
+    }
+
+    #[test]
+    fn add_replace_and_delete(){
+        let mut shell = shell("trie");
+        assert_eq!(output(&mut shell, "add 10.0.0.0/8 eth0"), "added 10.0.0.0/8 -> eth0\n");
+        assert_eq!(output(&mut shell, "add 10.0.0.0/8 eth1"), "replaced 10.0.0.0/8 (eth0 -> eth1)\n");
+        assert_eq!(output(&mut shell, "del 10.0.0.0/8"), "removed 10.0.0.0/8 -> eth1\n");
+        assert_eq!(output(&mut shell, "del 10.0.0.0/8"), "no route for 10.0.0.0/8\n");
+        assert_eq!(output(&mut shell, "add 10.0.0/8 eth0"), "bad prefix '10.0.0/8', expected a.b.c.d/len\n");
+    }
+
+    #[test]
+    fn lookup_explains_the_longest_match(){
+        for backend in ["trie", "bst"]{
+            let mut shell = shell(backend);
+            output(&mut shell, "add 10.0.0.0/8 core");
+            output(&mut shell, "add 10.1.0.0/16 edge");
+            assert_eq!(
+                output(&mut shell, "lookup 10.1.2.3"),
+                format!(
+                    "10.1.2.3 matched 10.1.0.0/16 -> edge [{}]\n  why: longest of 2 covering prefix(es): 10.0.0.0/8, 10.1.0.0/16\n",
+                    backend
+                )
+            );
+            assert_eq!(
+                output(&mut shell, "lookup 192.168.0.1"),
+                "192.168.0.1 has no route: no prefix in the table covers it\n"
+            );
+            assert!(output(&mut shell, "show stats").contains("lookups: 2 (1 hit, 1 miss)"));
+        }
+    }
+
+    #[test]
+    fn unknown_commands_are_reported(){
+        let mut shell = shell("trie");
+        assert_eq!(output(&mut shell, "  frobnicate now "), "unknown command 'frobnicate now', try 'help'\n");
+        assert_eq!(output(&mut shell, "lookup"), "unknown command 'lookup', try 'help'\n");
+        assert_eq!(output(&mut shell, ""), "");
+        assert!(!shell.execute("quit"));
+    }
+}
@@ -0,0 +1,262 @@
+use std::collections::HashMap;
+
+use crate::route_counters::PrefixCounters;
+use crate::route_table::{Route, RouteTable};
+use crate::utils::prefix_mask;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TcamLayout{
+    //one sorted block, everything below the insertion point shifts down
+    Naive,
+    //shah-gupta: one block per prefix length with free space at the bottom,
+    //at most one move per non-empty length group between the target and the free space
+    PrefixLengthOrdered,
+}
+
+#[derive(Debug, Clone)]
+struct TcamEntry{
+    value: u32,
+    mask: u32,
+    prefix_len: u8,
+    next_hop: String,
+    //per-entry hit counters, as on most forwarding asics
+    counters: PrefixCounters,
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct TcamStats{
+    pub inserts: usize,
+    pub deletes: usize,
+    pub moves: usize,
+    pub max_moves: usize,
+}
+
+//slot 0 has the highest priority, lookup returns the first matching slot
+pub struct Tcam{
+    slots: Vec<Option<TcamEntry>>,
+    layout: TcamLayout,
+    used: usize,
+    //group of prefix length l spans bounds[32 - l]..bounds[33 - l], bounds[33] is the first free slot
+    bounds: [usize; 34],
+    positions: HashMap<(u32, u8), usize>,
+    pub stats: TcamStats,
+}
+
+impl Tcam{
+    pub fn new(capacity: usize, layout: TcamLayout) -> Self{
+        Tcam{
+            slots: vec![None; capacity],
+            layout,
+            used: 0,
+            bounds: [0; 34],
+            positions: HashMap::new(),
+            stats: TcamStats::default(),
+        }
+    }
+
+    pub fn len(&self) -> usize{
+        self.used
+    }
+
+    pub fn capacity(&self) -> usize{
+        self.slots.len()
+    }
+
+    //returns the number of existing entries that had to move
+    pub fn insert_entry(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<usize, String>{
+        let mask = prefix_mask(prefix_len);
+        let value = prefix & mask;
+
+        if let Some(&pos) = self.positions.get(&(value, prefix_len)){
+            //same value/mask, rewriting the action is free
+            if let Some(entry) = self.slots[pos].as_mut(){
+                entry.next_hop = next_hop;
+                entry.counters = PrefixCounters::default();
+            }
+            return Ok(0);
+        }
+        if self.used == self.slots.len(){
+            return Err(format!("tcam full ({} entries)", self.slots.len()));
+        }
+
+        let group = 32 - prefix_len as usize;
+        let entry = TcamEntry{ value, mask, prefix_len, next_hop, counters: PrefixCounters::default() };
+        let moves = match self.layout{
+            TcamLayout::Naive => {
+                //after every entry of equal or greater length
+                let pos = self.bounds[group + 1];
+                for i in (pos..self.used).rev(){
+                    self.move_entry(i, i + 1);
+                }
+                self.place(pos, entry);
+                self.used - pos
+            }
+            TcamLayout::PrefixLengthOrdered => {
+                //walk the hole up from the free space, moving the first entry of each group to its end
+                let mut hole = self.bounds[33];
+                let mut moves = 0;
+                for g in (group + 1..=32).rev(){
+                    if self.bounds[g] < self.bounds[g + 1]{
+                        self.move_entry(self.bounds[g], hole);
+                        moves += 1;
+                    }
+                    hole = self.bounds[g];
+                }
+                self.place(hole, entry);
+                moves
+            }
+        };
+
+        for b in &mut self.bounds[group + 1..]{
+            *b += 1;
+        }
+        self.used += 1;
+        self.record(moves);
+        self.stats.inserts += 1;
+        Ok(moves)
+    }
+
+    //returns the removed next hop and the number of entries that had to move
+    pub fn remove_entry(&mut self, prefix: u32, prefix_len: u8) -> Option<(String, usize)>{
+        let pos = self.positions.remove(&(prefix & prefix_mask(prefix_len), prefix_len))?;
+        let removed = self.slots[pos].take()?.next_hop;
+        let group = 32 - prefix_len as usize;
+
+        let moves = match self.layout{
+            TcamLayout::Naive => {
+                for i in pos + 1..self.used{
+                    self.move_entry(i, i - 1);
+                }
+                self.used - pos - 1
+            }
+            TcamLayout::PrefixLengthOrdered => {
+                //fill the hole with the group's last entry, then pull the hole down to the free space
+                let mut moves = 0;
+                let mut hole = pos;
+                for g in group..=32{
+                    let last = self.bounds[g + 1] - 1;
+                    if last != hole && self.bounds[g] < self.bounds[g + 1]{
+                        self.move_entry(last, hole);
+                        moves += 1;
+                    }
+                    hole = last;
+                }
+                moves
+            }
+        };
+
+        for b in &mut self.bounds[group + 1..]{
+            *b -= 1;
+        }
+        self.used -= 1;
+        self.record(moves);
+        self.stats.deletes += 1;
+        Some((removed, moves))
+    }
+
+    fn move_entry(&mut self, from: usize, to: usize){
+        let entry = self.slots[from].take();
+        if let Some(ref e) = entry{
+            self.positions.insert((e.value, e.prefix_len), to);
+        }
+        self.slots[to] = entry;
+    }
+
+    fn place(&mut self, pos: usize, entry: TcamEntry){
+        self.positions.insert((entry.value, entry.prefix_len), pos);
+        self.slots[pos] = Some(entry);
+    }
+
+    fn record(&mut self, moves: usize){
+        self.stats.moves += moves;
+        self.stats.max_moves = self.stats.max_moves.max(moves);
+    }
+}
+
+impl RouteTable for Tcam{
+    fn name(&self) -> &'static str{
+        match self.layout{
+            TcamLayout::Naive => "tcam",
+            TcamLayout::PrefixLengthOrdered => "tcam-plo",
+        }
+    }
+
+    //a full tcam refuses the route, the caller decides whether that is fatal
+    fn insert(&mut self, prefix: u32, prefix_len: u8, next_hop: String) -> Result<(), String>{
+        self.insert_entry(prefix, prefix_len, next_hop).map(|_| ())
+    }
+
+    fn remove(&mut self, prefix: u32, prefix_len: u8) -> Option<String>{
+        self.remove_entry(prefix, prefix_len).map(|(hop, _)| hop)
+    }
+
+    //every slot is compared in parallel in hardware, the priority encoder picks the lowest index
+    fn lookup_route(&self, ip: u32) -> Option<Route>{
+        self.slots[..self.bounds[33]]
+            .iter()
+            .flatten()
+            .find(|e| ip & e.mask == e.value)
+            .map(|e| Route::new(e.value, e.prefix_len, e.next_hop.clone()))
+    }
+
+    fn lookup_counted(&mut self, ip: u32, packet_len: u32) -> Option<Route>{
+        let used = self.bounds[33];
+        let entry = self.slots[..used].iter_mut().flatten().find(|e| ip & e.mask == e.value)?;
+        entry.counters.hit(packet_len);
+        Some(Route::new(entry.value, entry.prefix_len, entry.next_hop.clone()))
+    }
+
+    fn counters(&self) -> Vec<(Route, PrefixCounters)>{
+        self.slots
+            .iter()
+            .flatten()
+            .filter(|e| e.counters.packets > 0)
+            .map(|e| (Route::new(e.value, e.prefix_len, e.next_hop.clone()), e.counters))
+            .collect()
+    }
+
+    fn reset_counters(&mut self){
+        for entry in self.slots.iter_mut().flatten(){
+            entry.counters = PrefixCounters::default();
+        }
+    }
+
+    fn routes(&self) -> Vec<Route>{
+        self.slots
+            .iter()
+            .flatten()
+            .map(|e| Route::new(e.value, e.prefix_len, e.next_hop.clone()))
+            .collect()
+    }
+
+    fn node_count(&self) -> usize{
+        self.used
+    }
+}
+
+#[cfg(test)]
+mod tests{
+    use super::*;
+    use crate::route_table::reference::check_against_trie;
+
+    #[test]
+    fn both_layouts_match_trie(){
+        for layout in [TcamLayout::Naive, TcamLayout::PrefixLengthOrdered]{
+            check_against_trie(&mut Tcam::new(4_096, layout), 2_000, 30);
+        }
+    }
+
+    //a full tcam refuses new routes through the trait but still takes rewrites of existing ones
+    #[test]
+    fn full_tcam_rejects_inserts(){
+        let mut tcam = Tcam::new(2, TcamLayout::PrefixLengthOrdered);
+        let table: &mut dyn RouteTable = &mut tcam;
+        assert!(table.insert(0x0A00_0000, 8, "a".to_string()).is_ok());
+        assert!(table.insert(0x0A01_0000, 16, "b".to_string()).is_ok());
+        assert!(table.insert(0x0A01_0100, 24, "c".to_string()).is_err());
+        assert!(table.insert(0x0A00_0000, 8, "d".to_string()).is_ok());
+        assert_eq!(table.routes().len(), 2);
+        assert_eq!(table.lookup_route(0x0A01_0101).map(|r| r.prefix_len), Some(16));
+        assert_eq!(table.lookup_route(0x0A02_0000).map(|r| r.next_hop), Some("d".to_string()));
+    }
+}
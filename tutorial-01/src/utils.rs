@@ -11,4 +11,80 @@ pub fn u32_to_ip(ip: u32) -> String {
         (ip >> 8) & 0xFF,
         ip & 0xFF
     )
-}
\ No newline at end of file
+}
+
+//same as ip_to_u32 but for user input, so bad octets give None instead of a panic
+pub fn parse_ip(ip: &str) -> Option<u32>{
+    let parts: Vec<&str> = ip.trim().split('.').collect();
+    if parts.len() != 4{
+        return None;
+    }
+
+    let mut value = 0u32;
+    for part in parts{
+        let octet: u8 = part.parse().ok()?;
+        value = (value << 8) | octet as u32;
+    }
+    Some(value)
+}
+
+//"10.0.0.0/8" -> (prefix, len), host bits are cleared
+pub fn parse_prefix(s: &str) -> Option<(u32, u8)>{
+    let (ip, len) = s.trim().split_once('/')?;
+    let len: u8 = len.parse().ok()?;
+    if len > 32{
+        return None;
+    }
+    Some((parse_ip(ip)? & prefix_mask(len), len))
+}
+
+pub fn prefix_mask(prefix_len: u8) -> u32{
+    prefix_mask_bits(prefix_len, 32)
+}
+
+//mask for a prefix of a narrower field (e.g. 16-bit ports) kept in the low `width` bits
+pub fn prefix_mask_bits(prefix_len: u8, width: u8) -> u32{
+    if prefix_len == 0{
+        0
+    }else{
+        (!0u32 << (32 - prefix_len)) >> (32 - width)
+    }
+}
+
+//small xorshift64* generator so generated tables and traces are reproducible without extra crates
+pub struct Rng(u64);
+
+impl Rng{
+    pub fn new(seed: u64) -> Self{
+        //xorshift gets stuck on zero, so mix the seed first
+        Rng(seed.wrapping_mul(0x9E37_79B9_7F4A_7C15) | 1)
+    }
+
+    pub fn next_u64(&mut self) -> u64{
+        self.0 ^= self.0 >> 12;
+        self.0 ^= self.0 << 25;
+        self.0 ^= self.0 >> 27;
+        self.0.wrapping_mul(0x2545_F491_4F6C_DD1D)
+    }
+
+    pub fn next_u32(&mut self) -> u32{
+        (self.next_u64() >> 32) as u32
+    }
+
+    //uniform in 0..n, which has to be non-empty
+    pub fn below(&mut self, n: u64) -> u64{
+        assert!(n > 0, "Rng::below(0): empty range");
+        self.next_u64() % n
+    }
+
+    //uniform in [0, 1)
+    pub fn next_f64(&mut self) -> f64{
+        (self.next_u64() >> 11) as f64 / (1u64 << 53) as f64
+    }
+
+    pub fn shuffle<T>(&mut self, items: &mut [T]){
+        for i in (1..items.len()).rev(){
+            items.swap(i, self.below(i as u64 + 1) as usize);
+        }
+    }
+}
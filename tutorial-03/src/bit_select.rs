@@ -0,0 +1,186 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::{Crc32, IpAddress, MacAddress};
+
+//which 32-bit value the table index is cut from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HashSource {
+    //the ieee crc over the mac, optionally without the final inversion and/or
+    //bit-reversed (the msb-first form that big-endian hash logic sees)
+    Crc { inverted: bool, reversed: bool },
+    //no crc at all: a window of the address read as a little-endian number,
+    //so bit 47 is the top bit of the last octet
+    AddressBits,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Slice {
+    Top,
+    Low,
+    //address window whose lowest bit is this address bit
+    From(u8),
+}
+
+//how a controller turns a mac into a hash table index
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BitSelection {
+    pub name: &'static str,
+    pub size_bits: u8,
+    pub source: HashSource,
+    pub slice: Slice,
+}
+
+impl BitSelection {
+    //what this simulator has always done: top bits of the final inverted crc
+    pub fn classic(size_bits: u8) -> Self {
+        Self {
+            name: "classic",
+            size_bits,
+            source: HashSource::Crc {
+                inverted: true,
+                reversed: false,
+            },
+            slice: Slice::Top,
+        }
+    }
+
+    pub fn entries(&self) -> usize {
+        1 << self.size_bits
+    }
+
+    pub fn index(&self, crc: &Crc32, mac: &MacAddress) -> usize {
+        let mask = (1u64 << self.size_bits) - 1;
+        let value: u64 = match self.source {
+            HashSource::Crc { inverted, reversed } => {
+                let mut v = crc.compute(&mac.0);
+                if !inverted {
+                    v = !v;
+                }
+                if reversed {
+                    v = v.reverse_bits();
+                }
+                v as u64
+            }
+            HashSource::AddressBits => mac.0.iter().rev().fold(0u64, |acc, &b| acc << 8 | b as u64),
+        };
+        let width = match self.source {
+            HashSource::Crc { .. } => 32,
+            HashSource::AddressBits => 48,
+        };
+        let index = match self.slice {
+            Slice::Top => value >> (width - self.size_bits as u32),
+            Slice::Low => value,
+            Slice::From(bit) => value >> bit,
+        };
+        (index & mask) as usize
+    }
+}
+
+impl fmt::Display for BitSelection {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let bits = self.size_bits;
+        match (self.source, self.slice) {
+            (HashSource::AddressBits, Slice::From(low)) => {
+                write!(f, "address bits {}..{}", low + bits - 1, low)
+            }
+            (HashSource::AddressBits, _) => write!(f, "address bits"),
+            (HashSource::Crc { inverted, reversed }, slice) => {
+                let form = match (inverted, reversed) {
+                    (true, false) => "crc",
+                    (false, false) => "non-inverted crc",
+                    (true, true) => "bit-reversed crc",
+                    (false, true) => "bit-reversed non-inverted crc",
+                };
+                match slice {
+                    Slice::Top => write!(f, "top {} bits of {}", bits, form),
+                    Slice::Low => write!(f, "low {} bits of {}", bits, form),
+                    Slice::From(low) => write!(f, "bits {}..{} of {}", low + bits - 1, low, form),
+                }
+            }
+        }
+    }
+}
+
+//modeled on the hash filters of common controller families as their open
+//drivers program them; treat them as representative, not datasheet-exact
+pub const NIC_PRESETS: [BitSelection; 6] = [
+    BitSelection {
+        name: "ne2000/rtl8139",
+        size_bits: 6,
+        source: HashSource::Crc {
+            inverted: false,
+            reversed: true,
+        },
+        slice: Slice::Top,
+    },
+    BitSelection {
+        name: "sun hme",
+        size_bits: 6,
+        source: HashSource::Crc {
+            inverted: false,
+            reversed: false,
+        },
+        slice: Slice::Top,
+    },
+    BitSelection {
+        name: "tigon3",
+        size_bits: 7,
+        source: HashSource::Crc {
+            inverted: false,
+            reversed: false,
+        },
+        slice: Slice::Low,
+    },
+    BitSelection {
+        name: "dec 21143",
+        size_bits: 9,
+        source: HashSource::Crc {
+            inverted: false,
+            reversed: false,
+        },
+        slice: Slice::Low,
+    },
+    BitSelection {
+        name: "e1000 mta",
+        size_bits: 12,
+        source: HashSource::AddressBits,
+        slice: Slice::From(36),
+    },
+    BitSelection {
+        name: "classic 4096",
+        size_bits: 12,
+        source: HashSource::Crc {
+            inverted: true,
+            reversed: false,
+        },
+        slice: Slice::Top,
+    },
+];
+
+//subscribed groups that land on a shared index, by index. groups that map to
+//the same mac cannot be told apart by any hash, so a bucket only counts once
+//it holds at least two different macs
+pub fn collisions(
+    selection: &BitSelection,
+    crc: &Crc32,
+    groups: &[IpAddress],
+) -> Vec<(usize, Vec<IpAddress>)> {
+    let mut buckets: BTreeMap<usize, Vec<IpAddress>> = BTreeMap::new();
+    for group in groups {
+        let mac = MacAddress::from_multicast_ip(group);
+        buckets
+            .entry(selection.index(crc, &mac))
+            .or_default()
+            .push(*group);
+    }
+    buckets
+        .into_iter()
+        .filter(|(_, groups)| {
+            let first = MacAddress::from_multicast_ip(&groups[0]);
+            groups
+                .iter()
+                .any(|g| MacAddress::from_multicast_ip(g) != first)
+        })
+        .collect()
+}
@@ -0,0 +1,56 @@
+use std::fmt;
+
+//how the k indices of a bloom-style hash table are derived from a mac
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IndexScheme {
+    //consecutive bit slices of the crc of the mac and then of the reversed mac,
+    //double hashing once k slices need more than those 64 bits
+    CrcSlices,
+    //h1 + i * h2 from two crcs (kirsch and mitzenmacher), any k without extra crcs
+    DoubleHashing,
+}
+
+impl fmt::Display for IndexScheme {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IndexScheme::CrcSlices => f.pad("crc slices"),
+            IndexScheme::DoubleHashing => f.pad("double hash"),
+        }
+    }
+}
+
+//(1 - e^(-kn/m))^k for n entries in m bits with k hashes
+pub fn bloom_fpr(size_bits: u8, k: u8, n: usize) -> f64 {
+    let m = (1u64 << size_bits) as f64;
+    let k = k as f64;
+    (1.0 - (-k * n as f64 / m).exp()).powf(k)
+}
+
+//largest table bloom_params sizes for, 64k entries is far beyond any nic's hash filter
+pub const MAX_SIZE_BITS: u8 = 16;
+
+//smallest power-of-two table that can meet the target rate for n groups,
+//m >= -n ln p / (ln 2)^2, then the fewest hashes that meet it in that table
+//(each one is another crc slice the hardware has to check), at most m/n ln 2.
+//past MAX_SIZE_BITS the table stops growing and the target may be missed
+pub fn bloom_params(n: usize, target_fpr: f64) -> (u8, u8) {
+    assert!(
+        0.0 < target_fpr && target_fpr < 1.0,
+        "target false positive rate must be in (0, 1), got {}",
+        target_fpr
+    );
+    let ln2 = std::f64::consts::LN_2;
+    let min_bits = -(n.max(1) as f64) * target_fpr.ln() / (ln2 * ln2);
+    let mut size_bits = 1;
+    while size_bits < MAX_SIZE_BITS && ((1u64 << size_bits) as f64) < min_bits {
+        size_bits += 1;
+    }
+
+    let best_k = ((1u64 << size_bits) as f64 / n.max(1) as f64 * ln2)
+        .round()
+        .max(1.0) as u8;
+    let k = (1..=best_k)
+        .find(|&k| bloom_fpr(size_bits, k, n) <= target_fpr)
+        .unwrap_or(best_k);
+    (size_bits, k)
+}
@@ -0,0 +1,313 @@
+//rocksoft model crc ("a painless guide to crc error detection algorithms", ross williams):
+//any crc up to 64 bits is described by its width, polynomial, initial register,
+//whether input bytes and the output are reflected, and a final xor
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CrcParams {
+    pub name: &'static str,
+    pub width: u8,
+    //normal (msb-first) form without the implicit top bit
+    pub poly: u64,
+    pub init: u64,
+    pub refin: bool,
+    pub refout: bool,
+    pub xorout: u64,
+    //crc of the ascii string "123456789"
+    pub check: u64,
+}
+
+pub const CRC_32: CrcParams = CrcParams {
+    name: "CRC-32",
+    width: 32,
+    poly: 0x04C11DB7,
+    init: 0xFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFF,
+    check: 0xCBF43926,
+};
+
+//castagnoli, used by iscsi, sctp, ext4 and btrfs; sse4.2 has an instruction for it
+pub const CRC_32C: CrcParams = CrcParams {
+    name: "CRC-32C",
+    width: 32,
+    poly: 0x1EDC6F41,
+    init: 0xFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFF,
+    check: 0xE3069283,
+};
+
+pub const CRC_32_BZIP2: CrcParams = CrcParams {
+    name: "CRC-32/BZIP2",
+    width: 32,
+    poly: 0x04C11DB7,
+    init: 0xFFFFFFFF,
+    refin: false,
+    refout: false,
+    xorout: 0xFFFFFFFF,
+    check: 0xFC891918,
+};
+
+//the crc in mpeg-2 transport stream tables
+pub const CRC_32_MPEG2: CrcParams = CrcParams {
+    name: "CRC-32/MPEG-2",
+    width: 32,
+    poly: 0x04C11DB7,
+    init: 0xFFFFFFFF,
+    refin: false,
+    refout: false,
+    xorout: 0,
+    check: 0x0376E6E7,
+};
+
+//often just called crc-16/ccitt, which is also used for the kermit variant below
+pub const CRC_16_CCITT_FALSE: CrcParams = CrcParams {
+    name: "CRC-16/CCITT-FALSE",
+    width: 16,
+    poly: 0x1021,
+    init: 0xFFFF,
+    refin: false,
+    refout: false,
+    xorout: 0,
+    check: 0x29B1,
+};
+
+pub const CRC_16_KERMIT: CrcParams = CrcParams {
+    name: "CRC-16/KERMIT",
+    width: 16,
+    poly: 0x1021,
+    init: 0,
+    refin: true,
+    refout: true,
+    xorout: 0,
+    check: 0x2189,
+};
+
+pub const CRC_16_XMODEM: CrcParams = CrcParams {
+    name: "CRC-16/XMODEM",
+    width: 16,
+    poly: 0x1021,
+    init: 0,
+    refin: false,
+    refout: false,
+    xorout: 0,
+    check: 0x31C3,
+};
+
+pub const CRC_16_ARC: CrcParams = CrcParams {
+    name: "CRC-16/ARC",
+    width: 16,
+    poly: 0x8005,
+    init: 0,
+    refin: true,
+    refout: true,
+    xorout: 0,
+    check: 0xBB3D,
+};
+
+pub const CRC_16_MODBUS: CrcParams = CrcParams {
+    name: "CRC-16/MODBUS",
+    width: 16,
+    poly: 0x8005,
+    init: 0xFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0,
+    check: 0x4B37,
+};
+
+pub const CRC_8_SMBUS: CrcParams = CrcParams {
+    name: "CRC-8/SMBUS",
+    width: 8,
+    poly: 0x07,
+    init: 0,
+    refin: false,
+    refout: false,
+    xorout: 0,
+    check: 0xF4,
+};
+
+//1-wire; reflected, so its bit order matches ethernet's lsb-first wire order
+pub const CRC_8_MAXIM_DOW: CrcParams = CrcParams {
+    name: "CRC-8/MAXIM-DOW",
+    width: 8,
+    poly: 0x31,
+    init: 0,
+    refin: true,
+    refout: true,
+    xorout: 0,
+    check: 0xA1,
+};
+
+//narrower than a byte, the table still works one byte at a time
+pub const CRC_5_USB: CrcParams = CrcParams {
+    name: "CRC-5/USB",
+    width: 5,
+    poly: 0x05,
+    init: 0x1F,
+    refin: true,
+    refout: true,
+    xorout: 0x1F,
+    check: 0x19,
+};
+
+pub const CRC_64_XZ: CrcParams = CrcParams {
+    name: "CRC-64/XZ",
+    width: 64,
+    poly: 0x42F0E1EBA9EA3693,
+    init: 0xFFFFFFFFFFFFFFFF,
+    refin: true,
+    refout: true,
+    xorout: 0xFFFFFFFFFFFFFFFF,
+    check: 0x995DC9BBDF1939FA,
+};
+
+pub const PRESETS: [CrcParams; 13] = [
+    CRC_32,
+    CRC_32C,
+    CRC_32_BZIP2,
+    CRC_32_MPEG2,
+    CRC_16_CCITT_FALSE,
+    CRC_16_KERMIT,
+    CRC_16_XMODEM,
+    CRC_16_ARC,
+    CRC_16_MODBUS,
+    CRC_8_SMBUS,
+    CRC_8_MAXIM_DOW,
+    CRC_5_USB,
+    CRC_64_XZ,
+];
+
+fn reflect(value: u64, width: u8) -> u64 {
+    value.reverse_bits() >> (64 - width)
+}
+
+fn mask(width: u8) -> u64 {
+    u64::MAX >> (64 - width)
+}
+
+//table-driven engine. the register is a u64 whatever the width: reflected
+//crcs keep it in the low bits and shift right, the others keep it in the top
+//bits and shift left, so one byte (or eight) always lines up with the register
+//edge the data enters at
+pub struct Crc {
+    params: CrcParams,
+    //tables[0] is the classic one-byte table, tables[k] advances a byte that
+    //sits k positions further from the end of an 8-byte block
+    tables: Box<[[u64; 256]; 8]>,
+}
+
+impl Crc {
+    pub fn new(params: CrcParams) -> Self {
+        assert!((1..=64).contains(&params.width));
+        let mut tables = Box::new([[0u64; 256]; 8]);
+        let top = 64 - params.width as u32;
+        for i in 0..256 {
+            tables[0][i] = if params.refin {
+                let poly = reflect(params.poly, params.width);
+                (0..8).fold(i as u64, |crc, _| {
+                    if crc & 1 != 0 {
+                        (crc >> 1) ^ poly
+                    } else {
+                        crc >> 1
+                    }
+                })
+            } else {
+                let poly = params.poly << top;
+                (0..8).fold((i as u64) << 56, |crc, _| {
+                    if crc >> 63 != 0 {
+                        (crc << 1) ^ poly
+                    } else {
+                        crc << 1
+                    }
+                })
+            };
+        }
+        for k in 1..8 {
+            for i in 0..256 {
+                let prev = tables[k - 1][i];
+                tables[k][i] = if params.refin {
+                    (prev >> 8) ^ tables[0][(prev & 0xFF) as usize]
+                } else {
+                    (prev << 8) ^ tables[0][(prev >> 56) as usize]
+                };
+            }
+        }
+        Self { params, tables }
+    }
+
+    pub fn params(&self) -> &CrcParams {
+        &self.params
+    }
+
+    fn start(&self) -> u64 {
+        let p = &self.params;
+        if p.refin {
+            reflect(p.init, p.width)
+        } else {
+            p.init << (64 - p.width)
+        }
+    }
+
+    fn finish(&self, register: u64) -> u64 {
+        let p = &self.params;
+        //the register holds the crc reflected when refin is set
+        let value = if p.refin {
+            register
+        } else {
+            register >> (64 - p.width)
+        };
+        let value = if p.refin != p.refout {
+            reflect(value, p.width)
+        } else {
+            value
+        };
+        (value ^ p.xorout) & mask(p.width)
+    }
+
+    fn update_bytewise(&self, mut register: u64, data: &[u8]) -> u64 {
+        let table = &self.tables[0];
+        for &byte in data {
+            register = if self.params.refin {
+                (register >> 8) ^ table[((register ^ byte as u64) & 0xFF) as usize]
+            } else {
+                (register << 8) ^ table[((register >> 56) ^ byte as u64) as usize]
+            };
+        }
+        register
+    }
+
+    //slicing-by-8: fold 8 input bytes into the register at once, then look up
+    //all 8 register bytes in parallel tables instead of one after another
+    fn update_sliced(&self, mut register: u64, data: &[u8]) -> u64 {
+        let t = &self.tables;
+        let mut blocks = data.chunks_exact(8);
+        for block in &mut blocks {
+            let block: [u8; 8] = block.try_into().unwrap();
+            let b = if self.params.refin {
+                (register ^ u64::from_le_bytes(block)).to_le_bytes()
+            } else {
+                (register ^ u64::from_be_bytes(block)).to_be_bytes()
+            };
+            register = t[7][b[0] as usize]
+                ^ t[6][b[1] as usize]
+                ^ t[5][b[2] as usize]
+                ^ t[4][b[3] as usize]
+                ^ t[3][b[4] as usize]
+                ^ t[2][b[5] as usize]
+                ^ t[1][b[6] as usize]
+                ^ t[0][b[7] as usize];
+        }
+        self.update_bytewise(register, blocks.remainder())
+    }
+
+    pub fn checksum(&self, data: &[u8]) -> u64 {
+        self.finish(self.update_sliced(self.start(), data))
+    }
+
+    //the one-table loop, kept as the reference the fast path is measured against
+    pub fn checksum_bytewise(&self, data: &[u8]) -> u64 {
+        self.finish(self.update_bytewise(self.start(), data))
+    }
+}
@@ -0,0 +1,178 @@
+use std::fmt;
+
+use crate::rng::Rng;
+use crate::{Crc32, MacAddress};
+
+pub const ETHERTYPE_IPV4: u16 = 0x0800;
+pub const ETHERTYPE_VLAN: u16 = 0x8100;
+
+//shortest frame on the wire, fcs included; shorter payloads are zero padded
+pub const MIN_FRAME: usize = 64;
+const FCS_LEN: usize = 4;
+
+//802.1q tag: priority, drop eligible indicator and vlan id
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct VlanTag {
+    pub pcp: u8,
+    pub dei: bool,
+    pub vid: u16,
+}
+
+impl VlanTag {
+    fn tci(&self) -> u16 {
+        (self.pcp as u16 & 0x7) << 13 | (self.dei as u16) << 12 | (self.vid & 0xFFF)
+    }
+
+    fn from_tci(tci: u16) -> Self {
+        Self {
+            pcp: (tci >> 13) as u8,
+            dei: tci & 0x1000 != 0,
+            vid: tci & 0xFFF,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct EthernetFrame {
+    pub dst: MacAddress,
+    pub src: MacAddress,
+    pub vlan: Option<VlanTag>,
+    pub ethertype: u16,
+    //after parsing this includes any padding, the frame itself does not say
+    //where a short payload ended
+    pub payload: Vec<u8>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FrameError {
+    //shorter than the 64 byte minimum, what a collision leaves behind
+    Runt(usize),
+    BadFcs { computed: u32, received: u32 },
+}
+
+impl fmt::Display for FrameError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FrameError::Runt(len) => write!(f, "runt frame of {} bytes", len),
+            FrameError::BadFcs { computed, received } => {
+                write!(
+                    f,
+                    "fcs mismatch: computed {:08x}, received {:08x}",
+                    computed, received
+                )
+            }
+        }
+    }
+}
+
+impl EthernetFrame {
+    //the bytes on the wire after the preamble: header, padded payload, fcs.
+    //the crc goes out least significant byte first, like the rest of the
+    //frame it is sent lsb first within each byte
+    pub fn to_bytes(&self, crc: &Crc32) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(18 + self.payload.len() + FCS_LEN);
+        bytes.extend_from_slice(&self.dst.0);
+        bytes.extend_from_slice(&self.src.0);
+        if let Some(tag) = self.vlan {
+            bytes.extend_from_slice(&ETHERTYPE_VLAN.to_be_bytes());
+            bytes.extend_from_slice(&tag.tci().to_be_bytes());
+        }
+        bytes.extend_from_slice(&self.ethertype.to_be_bytes());
+        bytes.extend_from_slice(&self.payload);
+        if bytes.len() < MIN_FRAME - FCS_LEN {
+            bytes.resize(MIN_FRAME - FCS_LEN, 0);
+        }
+        let fcs = crc.compute(&bytes);
+        bytes.extend_from_slice(&fcs.to_le_bytes());
+        bytes
+    }
+
+    pub fn parse(bytes: &[u8], crc: &Crc32) -> Result<Self, FrameError> {
+        if bytes.len() < MIN_FRAME {
+            return Err(FrameError::Runt(bytes.len()));
+        }
+        let (body, fcs) = bytes.split_at(bytes.len() - FCS_LEN);
+        let received = u32::from_le_bytes(fcs.try_into().unwrap());
+        let computed = crc.compute(body);
+        if computed != received {
+            return Err(FrameError::BadFcs { computed, received });
+        }
+
+        let mac = |at: usize| MacAddress(body[at..at + 6].try_into().unwrap());
+        let word = |at: usize| u16::from_be_bytes([body[at], body[at + 1]]);
+        let (vlan, header) = if word(12) == ETHERTYPE_VLAN {
+            (Some(VlanTag::from_tci(word(14))), 18)
+        } else {
+            (None, 14)
+        };
+        Ok(Self {
+            dst: mac(0),
+            src: mac(6),
+            vlan,
+            ethertype: word(header - 2),
+            payload: body[header..].to_vec(),
+        })
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ErrorPattern {
+    SingleBit,
+    //the first and last bit of the span flip, the ones between at random
+    Burst(usize),
+    //this many distinct bits anywhere in the frame
+    Random(usize),
+}
+
+impl fmt::Display for ErrorPattern {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ErrorPattern::SingleBit => f.pad("single bit"),
+            ErrorPattern::Burst(len) => f.pad(&format!("burst of {}", len)),
+            ErrorPattern::Random(n) => f.pad(&format!("{} random bits", n)),
+        }
+    }
+}
+
+//bit i is bit i % 8 of byte i / 8, which is also the order bits go out on
+//the wire, so a burst here is a burst on the line
+fn flip(bytes: &mut [u8], bit: usize) {
+    bytes[bit / 8] ^= 1 << (bit % 8);
+}
+
+//panics if the pattern needs more bits than the frame has
+pub fn inject_errors(bytes: &mut [u8], pattern: ErrorPattern, rng: &mut Rng) {
+    let bits = bytes.len() * 8;
+    match pattern {
+        ErrorPattern::SingleBit => flip(bytes, rng.below(bits as u64) as usize),
+        ErrorPattern::Burst(len) => {
+            assert!(
+                (1..=bits).contains(&len),
+                "burst of {} bits in a {} bit frame",
+                len,
+                bits
+            );
+            let start = rng.below((bits - len + 1) as u64) as usize;
+            flip(bytes, start);
+            for bit in start + 1..start + len - 1 {
+                if rng.below(2) == 0 {
+                    flip(bytes, bit);
+                }
+            }
+            if len > 1 {
+                flip(bytes, start + len - 1);
+            }
+        }
+        ErrorPattern::Random(n) => {
+            assert!(n <= bits, "{} random bits in a {} bit frame", n, bits);
+            let mut chosen = Vec::with_capacity(n);
+            while chosen.len() < n {
+                let bit = rng.below(bits as u64) as usize;
+                if !chosen.contains(&bit) {
+                    chosen.push(bit);
+                    flip(bytes, bit);
+                }
+            }
+        }
+    }
+}
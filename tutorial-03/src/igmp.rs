@@ -0,0 +1,471 @@
+use std::collections::BTreeMap;
+use std::fmt;
+
+use crate::rng::Rng;
+use crate::{IpAddress, MulticastFilterSimulator, MulticastPacket};
+
+//rfc 2236 section 8 defaults, in milliseconds of simulated time
+pub const ROBUSTNESS: u64 = 2;
+pub const QUERY_INTERVAL: u64 = 125_000;
+pub const QUERY_RESPONSE_INTERVAL: u64 = 10_000;
+pub const GROUP_MEMBERSHIP_INTERVAL: u64 = ROBUSTNESS * QUERY_INTERVAL + QUERY_RESPONSE_INTERVAL;
+pub const OTHER_QUERIER_PRESENT_INTERVAL: u64 =
+    ROBUSTNESS * QUERY_INTERVAL + QUERY_RESPONSE_INTERVAL / 2;
+const STARTUP_QUERY_INTERVAL: u64 = QUERY_INTERVAL / 4;
+pub const LAST_MEMBER_QUERY_INTERVAL: u64 = 1_000;
+const UNSOLICITED_REPORT_INTERVAL: u64 = 10_000;
+
+const ALL_SYSTEMS: IpAddress = IpAddress::V4([224, 0, 0, 1]);
+const ALL_ROUTERS: IpAddress = IpAddress::V4([224, 0, 0, 2]);
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum IgmpMessage {
+    //no group is a general query, max_resp is in milliseconds
+    Query {
+        group: Option<IpAddress>,
+        max_resp: u64,
+    },
+    Report(IpAddress),
+    Leave(IpAddress),
+}
+
+impl IgmpMessage {
+    fn destination(&self) -> IpAddress {
+        match self {
+            IgmpMessage::Query { group: None, .. } => ALL_SYSTEMS,
+            IgmpMessage::Query {
+                group: Some(group), ..
+            } => *group,
+            IgmpMessage::Report(group) => *group,
+            IgmpMessage::Leave(_) => ALL_ROUTERS,
+        }
+    }
+}
+
+impl fmt::Display for IgmpMessage {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            IgmpMessage::Query {
+                group: None,
+                max_resp,
+            } => write!(f, "general query (max resp {}s)", max_resp / 1000),
+            IgmpMessage::Query {
+                group: Some(group), ..
+            } => write!(f, "group-specific query {}", group),
+            IgmpMessage::Report(group) => write!(f, "report {}", group),
+            IgmpMessage::Leave(group) => write!(f, "leave {}", group),
+        }
+    }
+}
+
+//rfc 2236 section 6: a member is either waiting out a random delay before
+//reporting, or idle until the next query
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum HostState {
+    Delaying { until: u64 },
+    Idle,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct HostGroup {
+    state: HostState,
+    //only the host that sent the last report for a group sends a leave
+    last_reporter: bool,
+}
+
+pub struct IgmpHost {
+    pub name: String,
+    pub addr: IpAddress,
+    pub nic: MulticastFilterSimulator,
+    pub up: bool,
+    groups: BTreeMap<IpAddress, HostGroup>,
+    suppressed: u64,
+}
+
+impl IgmpHost {
+    pub fn new(name: &str, addr: IpAddress, hash_bits: u8) -> Self {
+        let mut nic = MulticastFilterSimulator::new(hash_bits);
+        //every ipv4 multicast host listens on all-systems, it is never reported
+        nic.subscribe(ALL_SYSTEMS);
+        Self {
+            name: name.to_string(),
+            addr,
+            nic,
+            up: true,
+            groups: BTreeMap::new(),
+            suppressed: 0,
+        }
+    }
+
+    pub fn groups(&self) -> Vec<IpAddress> {
+        self.groups.keys().copied().collect()
+    }
+
+    //unsolicited report right away, repeated once after a random delay in case it was lost
+    fn join(&mut self, now: u64, group: IpAddress, rng: &mut Rng) -> Vec<IgmpMessage> {
+        if self.groups.contains_key(&group) {
+            return vec![];
+        }
+        self.nic.subscribe(group);
+        let until = now + rng.below(UNSOLICITED_REPORT_INTERVAL);
+        self.groups.insert(
+            group,
+            HostGroup {
+                state: HostState::Delaying { until },
+                last_reporter: true,
+            },
+        );
+        vec![IgmpMessage::Report(group)]
+    }
+
+    fn leave(&mut self, group: IpAddress) -> Vec<IgmpMessage> {
+        let Some(membership) = self.groups.remove(&group) else {
+            return vec![];
+        };
+        self.nic.unsubscribe(group);
+        if membership.last_reporter {
+            vec![IgmpMessage::Leave(group)]
+        } else {
+            vec![]
+        }
+    }
+
+    fn receive(&mut self, now: u64, msg: IgmpMessage, rng: &mut Rng) {
+        match msg {
+            IgmpMessage::Query { group, max_resp } => {
+                for (g, membership) in self.groups.iter_mut() {
+                    if group.is_some_and(|q| q != *g) {
+                        continue;
+                    }
+                    //start a timer, or shorten one that would fire after the new deadline
+                    let due = now + rng.below(max_resp.max(1));
+                    match membership.state {
+                        HostState::Delaying { until } if until <= now + max_resp => {}
+                        _ => membership.state = HostState::Delaying { until: due },
+                    }
+                }
+            }
+            IgmpMessage::Report(group) => {
+                //someone else answered for the group, our report would be redundant
+                if let Some(membership) = self.groups.get_mut(&group)
+                    && let HostState::Delaying { .. } = membership.state
+                {
+                    membership.state = HostState::Idle;
+                    membership.last_reporter = false;
+                    self.suppressed += 1;
+                }
+            }
+            IgmpMessage::Leave(_) => {}
+        }
+    }
+
+    fn next_timer(&self) -> Option<u64> {
+        self.groups
+            .values()
+            .filter_map(|m| match m.state {
+                HostState::Delaying { until } => Some(until),
+                HostState::Idle => None,
+            })
+            .min()
+    }
+
+    fn fire(&mut self, now: u64) -> Vec<IgmpMessage> {
+        let mut out = vec![];
+        for (group, membership) in self.groups.iter_mut() {
+            if let HostState::Delaying { until } = membership.state
+                && until <= now
+            {
+                membership.state = HostState::Idle;
+                membership.last_reporter = true;
+                out.push(IgmpMessage::Report(*group));
+            }
+        }
+        out
+    }
+}
+
+#[derive(Debug, Clone, Copy)]
+struct RouterGroup {
+    expires: u64,
+    //pending group-specific queries after a leave
+    queries_left: u64,
+    next_query: u64,
+}
+
+//rfc 2236 section 7: every router starts as querier and yields to any lower address it hears querying
+pub struct IgmpRouter {
+    pub name: String,
+    pub addr: IpAddress,
+    pub up: bool,
+    querier: bool,
+    other_querier_until: u64,
+    next_query: u64,
+    startup_left: u64,
+    groups: BTreeMap<IpAddress, RouterGroup>,
+    events: Vec<String>,
+}
+
+impl IgmpRouter {
+    pub fn new(name: &str, addr: IpAddress) -> Self {
+        Self {
+            name: name.to_string(),
+            addr,
+            up: true,
+            querier: true,
+            other_querier_until: 0,
+            next_query: 0,
+            startup_left: ROBUSTNESS - 1,
+            groups: BTreeMap::new(),
+            events: vec![],
+        }
+    }
+
+    pub fn is_querier(&self) -> bool {
+        self.querier
+    }
+
+    pub fn groups(&self) -> Vec<IpAddress> {
+        self.groups.keys().copied().collect()
+    }
+
+    fn receive(&mut self, now: u64, src: IpAddress, msg: IgmpMessage) -> Vec<IgmpMessage> {
+        match msg {
+            IgmpMessage::Query { group, .. } => {
+                if src < self.addr {
+                    if self.querier {
+                        self.events.push(format!("yields querier to {}", src));
+                    }
+                    self.querier = false;
+                    self.startup_left = 0;
+                    self.other_querier_until = now + OTHER_QUERIER_PRESENT_INTERVAL;
+                    //last member queries are the querier's job, and it is not us any more
+                    for state in self.groups.values_mut() {
+                        state.queries_left = 0;
+                    }
+                }
+                //a non-querier follows the querier's last member queries
+                if let Some(group) = group
+                    && !self.querier
+                    && let Some(state) = self.groups.get_mut(&group)
+                {
+                    state.expires = state
+                        .expires
+                        .min(now + ROBUSTNESS * LAST_MEMBER_QUERY_INTERVAL);
+                }
+                vec![]
+            }
+            IgmpMessage::Report(group) => {
+                let state = self.groups.entry(group).or_insert(RouterGroup {
+                    expires: 0,
+                    queries_left: 0,
+                    next_query: 0,
+                });
+                state.expires = now + GROUP_MEMBERSHIP_INTERVAL;
+                state.queries_left = 0;
+                vec![]
+            }
+            IgmpMessage::Leave(group) => {
+                if !self.querier {
+                    return vec![];
+                }
+                let Some(state) = self.groups.get_mut(&group) else {
+                    return vec![];
+                };
+                state.expires = now + ROBUSTNESS * LAST_MEMBER_QUERY_INTERVAL;
+                state.queries_left = ROBUSTNESS - 1;
+                state.next_query = now + LAST_MEMBER_QUERY_INTERVAL;
+                vec![IgmpMessage::Query {
+                    group: Some(group),
+                    max_resp: LAST_MEMBER_QUERY_INTERVAL,
+                }]
+            }
+        }
+    }
+
+    fn next_timer(&self) -> Option<u64> {
+        let own = if self.querier {
+            self.next_query
+        } else {
+            self.other_querier_until
+        };
+        let groups = self.groups.values().flat_map(|g| {
+            let query = (g.queries_left > 0).then_some(g.next_query);
+            std::iter::once(g.expires).chain(query)
+        });
+        groups.chain(std::iter::once(own)).min()
+    }
+
+    fn fire(&mut self, now: u64) -> Vec<IgmpMessage> {
+        let mut out = vec![];
+
+        if !self.querier && self.other_querier_until <= now {
+            self.querier = true;
+            self.events
+                .push("other querier timed out, taking over".to_string());
+            self.next_query = now;
+        }
+        if self.querier && self.next_query <= now {
+            out.push(IgmpMessage::Query {
+                group: None,
+                max_resp: QUERY_RESPONSE_INTERVAL,
+            });
+            if self.startup_left > 0 {
+                self.startup_left -= 1;
+                self.next_query = now + STARTUP_QUERY_INTERVAL;
+            } else {
+                self.next_query = now + QUERY_INTERVAL;
+            }
+        }
+
+        let mut expired = vec![];
+        for (group, state) in self.groups.iter_mut() {
+            if state.expires <= now {
+                expired.push(*group);
+            } else if state.queries_left > 0 && state.next_query <= now {
+                state.queries_left -= 1;
+                state.next_query = now + LAST_MEMBER_QUERY_INTERVAL;
+                out.push(IgmpMessage::Query {
+                    group: Some(*group),
+                    max_resp: LAST_MEMBER_QUERY_INTERVAL,
+                });
+            }
+        }
+        for group in expired {
+            self.groups.remove(&group);
+            self.events.push(format!("no members left for {}", group));
+        }
+        out
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct IgmpStats {
+    pub queries: u64,
+    pub reports: u64,
+    pub leaves: u64,
+    pub suppressed: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Node {
+    Host(usize),
+    Router(usize),
+}
+
+//one shared segment: every message is multicast to all other nodes, hosts only
+//see what their nic filter lets through, routers listen to all igmp
+pub struct Lan {
+    pub now: u64,
+    pub hosts: Vec<IgmpHost>,
+    pub routers: Vec<IgmpRouter>,
+    pub log: Vec<(u64, String)>,
+    pub stats: IgmpStats,
+    rng: Rng,
+}
+
+impl Lan {
+    pub fn new(seed: u64) -> Self {
+        Self {
+            now: 0,
+            hosts: vec![],
+            routers: vec![],
+            log: vec![],
+            stats: IgmpStats::default(),
+            rng: Rng::new(seed),
+        }
+    }
+
+    pub fn join(&mut self, host: usize, group: IpAddress) {
+        let msgs = self.hosts[host].join(self.now, group, &mut self.rng);
+        self.send(Node::Host(host), msgs);
+    }
+
+    pub fn leave(&mut self, host: usize, group: IpAddress) {
+        let msgs = self.hosts[host].leave(group);
+        self.send(Node::Host(host), msgs);
+    }
+
+    //advance the clock, firing timers in order
+    pub fn run_until(&mut self, end: u64) {
+        loop {
+            let host_timers = self
+                .hosts
+                .iter()
+                .enumerate()
+                .filter(|(_, h)| h.up)
+                .filter_map(|(i, h)| Some((h.next_timer()?, Node::Host(i))));
+            let router_timers = self
+                .routers
+                .iter()
+                .enumerate()
+                .filter(|(_, r)| r.up)
+                .filter_map(|(i, r)| Some((r.next_timer()?, Node::Router(i))));
+            let Some((at, node)) = host_timers.chain(router_timers).min_by_key(|&(at, _)| at)
+            else {
+                break;
+            };
+            if at > end {
+                break;
+            }
+            self.now = self.now.max(at);
+
+            let msgs = match node {
+                Node::Host(i) => self.hosts[i].fire(self.now),
+                Node::Router(i) => self.routers[i].fire(self.now),
+            };
+            self.send(node, msgs);
+        }
+        self.now = end;
+        self.stats.suppressed = self.hosts.iter().map(|h| h.suppressed).sum();
+    }
+
+    fn send(&mut self, from: Node, msgs: Vec<IgmpMessage>) {
+        let mut pending: Vec<(Node, IgmpMessage)> = msgs.into_iter().map(|m| (from, m)).collect();
+        //state changes behind a timer are logged ahead of what it sends
+        self.collect_router_events();
+        while !pending.is_empty() {
+            for (from, msg) in std::mem::take(&mut pending) {
+                let (name, src) = match from {
+                    Node::Host(i) => (self.hosts[i].name.clone(), self.hosts[i].addr),
+                    Node::Router(i) => (self.routers[i].name.clone(), self.routers[i].addr),
+                };
+                self.log.push((self.now, format!("{} -> {}", name, msg)));
+                match msg {
+                    IgmpMessage::Query { .. } => self.stats.queries += 1,
+                    IgmpMessage::Report(_) => self.stats.reports += 1,
+                    IgmpMessage::Leave(_) => self.stats.leaves += 1,
+                }
+
+                for (i, host) in self.hosts.iter_mut().enumerate() {
+                    if from == Node::Host(i) || !host.up {
+                        continue;
+                    }
+                    if host
+                        .nic
+                        .process(MulticastPacket::new(src, msg.destination()))
+                    {
+                        host.receive(self.now, msg, &mut self.rng);
+                    }
+                }
+                for (i, router) in self.routers.iter_mut().enumerate() {
+                    if from == Node::Router(i) || !router.up {
+                        continue;
+                    }
+                    let replies = router.receive(self.now, src, msg);
+                    pending.extend(replies.into_iter().map(|m| (Node::Router(i), m)));
+                }
+            }
+            self.collect_router_events();
+        }
+        self.collect_router_events();
+    }
+
+    fn collect_router_events(&mut self) {
+        for router in self.routers.iter_mut() {
+            for event in router.events.drain(..) {
+                self.log
+                    .push((self.now, format!("{}: {}", router.name, event)));
+            }
+        }
+    }
+}
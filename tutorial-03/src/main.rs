@@ -1,37 +1,41 @@
 use std::collections::{HashMap, HashSet};
 use std::fmt;
+use std::net::IpAddr;
 
-//crc32 ieee 802.3
+mod bit_select;
+mod bloom;
+mod crc;
+mod ethernet;
+mod igmp;
+mod perfect_filter;
+mod rng;
+mod snooping;
+mod source_filter;
+
+use bit_select::{BitSelection, HashSource, NIC_PRESETS, Slice, collisions};
+use bloom::{IndexScheme, bloom_fpr, bloom_params};
+use crc::{CRC_8_MAXIM_DOW, CRC_16_CCITT_FALSE, CRC_32, CRC_32C, CRC_64_XZ, Crc, PRESETS};
+use ethernet::{ETHERTYPE_IPV4, ErrorPattern, EthernetFrame, VlanTag, inject_errors};
+use igmp::{IgmpHost, IgmpMessage, IgmpRouter, Lan, QUERY_INTERVAL, QUERY_RESPONSE_INTERVAL};
+use perfect_filter::{OverflowPolicy, PerfectFilter};
+use rng::Rng;
+use snooping::{SnoopingStats, SnoopingSwitch};
+use source_filter::SourceFilter;
+
+//crc32 ieee 802.3, the rocksoft engine fixed to the ethernet parameters
 struct Crc32 {
-    table: [u32; 256],
+    crc: Crc,
 }
 
 impl Crc32 {
-    const POLYNOMIAL: u32 = 0xEDB88320;
-
     fn new() -> Self {
-        let mut table = [0u32; 256];
-        for i in 0..256 {
-            let mut crc = i as u32;
-            for _ in 0..8 {
-                if crc & 1 != 0 {
-                    crc = (crc >> 1) ^ Self::POLYNOMIAL;
-                } else {
-                    crc >>= 1;
-                }
-            }
-            table[i] = crc;
+        Self {
+            crc: Crc::new(CRC_32),
         }
-        Self { table }
     }
 
     fn compute(&self, data: &[u8]) -> u32 {
-        let mut crc = 0xFFFFFFFF;
-        for &byte in data {
-            let index = ((crc ^ byte as u32) & 0xFF) as usize;
-            crc = (crc >> 8) ^ self.table[index];
-        }
-        !crc
+        self.crc.checksum(data) as u32
     }
 
     fn hash_to_index(&self, mac: &MacAddress, bits: u8) -> usize {
@@ -45,9 +49,12 @@ impl Crc32 {
 struct MacAddress([u8; 6]);
 
 impl MacAddress {
+    //ipv4: 01:00:5e + low 23 bits (rfc 1112), ipv6: 33:33 + low 32 bits (rfc 2464)
     fn from_multicast_ip(ip: &IpAddress) -> Self {
-        let b = ip.0;
-        Self([0x01, 0x00, 0x5E, b[1] & 0x7F, b[2], b[3]])
+        match ip {
+            IpAddress::V4(b) => Self([0x01, 0x00, 0x5E, b[1] & 0x7F, b[2], b[3]]),
+            IpAddress::V6(b) => Self([0x33, 0x33, b[12], b[13], b[14], b[15]]),
+        }
     }
 }
 
@@ -61,133 +68,411 @@ impl fmt::Display for MacAddress {
     }
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
-struct IpAddress([u8; 4]);
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+enum IpAddress {
+    V4([u8; 4]),
+    V6([u8; 16]),
+}
 
 impl IpAddress {
     fn new(a: u8, b: u8, c: u8, d: u8) -> Self {
-        Self([a, b, c, d])
+        Self::V4([a, b, c, d])
+    }
+
+    //either family in the usual text form, "ff02::1:ff00:1" or "224.0.0.1"
+    fn parse(s: &str) -> Option<Self> {
+        match s.parse::<IpAddr>().ok()? {
+            IpAddr::V4(ip) => Some(Self::V4(ip.octets())),
+            IpAddr::V6(ip) => Some(Self::V6(ip.octets())),
+        }
+    }
+
+    fn is_v6(&self) -> bool {
+        matches!(self, Self::V6(_))
     }
 
     fn is_multicast(&self) -> bool {
-        self.0[0] >= 224 && self.0[0] <= 239
+        match self {
+            Self::V4(b) => b[0] >= 224 && b[0] <= 239,
+            Self::V6(b) => b[0] == 0xFF,
+        }
+    }
+
+    //ff02::1:ffXX:XXXX, the group neighbor discovery sends to for a unicast address (rfc 4291)
+    fn solicited_node(&self) -> Option<Self> {
+        match self {
+            Self::V6(b) if !self.is_multicast() => {
+                let mut group = [0u8; 16];
+                group[..2].copy_from_slice(&[0xFF, 0x02]);
+                group[11..13].copy_from_slice(&[0x01, 0xFF]);
+                group[13..].copy_from_slice(&b[13..]);
+                Some(Self::V6(group))
+            }
+            _ => None,
+        }
+    }
+
+    fn is_solicited_node(&self) -> bool {
+        match self {
+            Self::V6(b) => b[..13] == [0xFF, 0x02, 0, 0, 0, 0, 0, 0, 0, 0, 0, 0x01, 0xFF],
+            Self::V4(_) => false,
+        }
     }
 }
 
 impl fmt::Display for IpAddress {
     fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
-        write!(f, "{}.{}.{}.{}", self.0[0], self.0[1], self.0[2], self.0[3])
+        match self {
+            Self::V4(b) => write!(f, "{}.{}.{}.{}", b[0], b[1], b[2], b[3]),
+            Self::V6(b) => write!(f, "{}", std::net::Ipv6Addr::from(*b)),
+        }
     }
 }
 
 #[derive(Clone)]
 struct MulticastPacket {
+    src_ip: IpAddress,
     dst_ip: IpAddress,
     dst_mac: MacAddress,
 }
 
 impl MulticastPacket {
-    fn new(dst_ip: IpAddress) -> Self {
+    fn new(src_ip: IpAddress, dst_ip: IpAddress) -> Self {
         let dst_mac = MacAddress::from_multicast_ip(&dst_ip);
-        Self { dst_ip, dst_mac }
+        Self {
+            src_ip,
+            dst_ip,
+            dst_mac,
+        }
     }
 }
 
+//bits of crc output the crc slices scheme can cut indices from
+const SLICE_BITS: u32 = 64;
+
+//with k > 1 the table is a bloom filter: a mac sets k bits and passes only if all are set
 struct HardwareHashTable {
     bits: Vec<bool>,
+    //software shadow of the bitmap: how many joined groups use each index,
+    //so a bit is only cleared once the last of them is gone
+    refs: Vec<u32>,
     size_bits: u8,
+    k: u8,
+    scheme: IndexScheme,
+    //how the first index is cut from the mac, the part that differs between nic families
+    selection: BitSelection,
     crc: Crc32,
 }
 
 impl HardwareHashTable {
     fn new(size_bits: u8) -> Self {
+        Self::with_hashes(size_bits, 1, IndexScheme::CrcSlices)
+    }
+
+    fn with_hashes(size_bits: u8, k: u8, scheme: IndexScheme) -> Self {
         Self {
             bits: vec![false; 1 << size_bits],
+            refs: vec![0; 1 << size_bits],
             size_bits,
+            k: k.max(1),
+            scheme,
+            selection: BitSelection::classic(size_bits),
             crc: Crc32::new(),
         }
     }
 
+    //a single-hash table indexed the way a particular controller does it
+    fn with_selection(selection: BitSelection) -> Self {
+        let mut table = Self::new(selection.size_bits);
+        table.selection = selection;
+        table
+    }
+
+    //the first index comes from the bit selection, the plain top-bits hash
+    //unless told otherwise, so k = 1 is the classic table
+    fn indices(&self, mac: &MacAddress) -> Vec<usize> {
+        let mask = (1usize << self.size_bits) - 1;
+        let h1 = self.crc.compute(&mac.0);
+        let first = self.selection.index(&self.crc, mac);
+        //second crc over the reversed mac
+        let mut reversed = mac.0;
+        reversed.reverse();
+        let h2 = self.crc.compute(&reversed);
+        let slices = self.k as u32 * self.size_bits as u32;
+        match self.scheme {
+            //the two crcs back to back, cut into consecutive slices; a second crc
+            //instead of wrapping round, so no slice repeats an earlier one
+            IndexScheme::CrcSlices if slices <= SLICE_BITS => {
+                let wide = (h1 as u64) << 32 | h2 as u64;
+                std::iter::once(first)
+                    .chain((1..self.k as u32).map(|i| {
+                        let slice = wide << (i * self.size_bits as u32);
+                        (slice >> (64 - self.size_bits)) as usize & mask
+                    }))
+                    .collect()
+            }
+            //more slices than the two crcs hold fall back to double hashing
+            IndexScheme::CrcSlices | IndexScheme::DoubleHashing => {
+                //forced odd so the steps cover the whole table
+                let h2 = h2 | 1;
+                (0..self.k as usize)
+                    .map(|i| first.wrapping_add(i.wrapping_mul(h2 as usize)) & mask)
+                    .collect()
+            }
+        }
+    }
+
     fn add_mac(&mut self, mac: &MacAddress) {
-        let idx = self.crc.hash_to_index(mac, self.size_bits);
-        self.bits[idx] = true;
+        for idx in self.indices(mac) {
+            self.refs[idx] += 1;
+            self.bits[idx] = true;
+        }
+    }
+
+    //one call per add_mac; removing a mac that was never added is ignored
+    fn remove_mac(&mut self, mac: &MacAddress) {
+        let indices = self.indices(mac);
+        if indices.iter().any(|&idx| self.refs[idx] == 0) {
+            return;
+        }
+        for idx in indices {
+            self.refs[idx] -= 1;
+        }
+        self.bits = self.shadow_bitmap();
+    }
+
+    //what the driver writes back to the nic, recomputed from the counts
+    fn shadow_bitmap(&self) -> Vec<bool> {
+        self.refs.iter().map(|&r| r > 0).collect()
     }
 
     fn check_mac(&self, mac: &MacAddress) -> bool {
-        let idx = self.crc.hash_to_index(mac, self.size_bits);
-        self.bits[idx]
+        self.indices(mac).iter().all(|&idx| self.bits[idx])
     }
 }
 
+//per group, each socket's source filter and the interface state merged from them
 struct SoftwareFilter {
-    subscribed: HashSet<IpAddress>,
+    sockets: HashMap<IpAddress, HashMap<u32, SourceFilter>>,
+    interface: HashMap<IpAddress, SourceFilter>,
 }
 
 impl SoftwareFilter {
     fn new() -> Self {
         Self {
-            subscribed: HashSet::new(),
+            sockets: HashMap::new(),
+            interface: HashMap::new(),
         }
     }
 
-    fn subscribe(&mut self, ip: IpAddress) {
-        self.subscribed.insert(ip);
+    fn unsubscribe(&mut self, ip: &IpAddress) {
+        self.sockets.remove(ip);
+        self.interface.remove(ip);
+    }
+
+    //include {} on a socket drops that socket from the group
+    fn set_filter(&mut self, group: IpAddress, socket: u32, filter: SourceFilter) {
+        let sockets = self.sockets.entry(group).or_default();
+        if filter.is_empty() {
+            sockets.remove(&socket);
+        } else {
+            sockets.insert(socket, filter);
+        }
+
+        let merged = SourceFilter::merge(sockets.values());
+        if sockets.is_empty() {
+            self.sockets.remove(&group);
+        }
+        if merged.is_empty() {
+            self.interface.remove(&group);
+        } else {
+            self.interface.insert(group, merged);
+        }
     }
 
     fn is_subscribed(&self, ip: &IpAddress) -> bool {
-        self.subscribed.contains(ip)
+        self.interface.contains_key(ip)
+    }
+
+    fn accepts(&self, group: &IpAddress, src: &IpAddress) -> bool {
+        self.interface
+            .get(group)
+            .is_some_and(|filter| filter.allows(src))
+    }
+
+    fn state(&self, group: &IpAddress) -> Option<&SourceFilter> {
+        self.interface.get(group)
+    }
+
+    fn groups(&self) -> Vec<IpAddress> {
+        self.interface.keys().copied().collect()
     }
 }
 
-#[derive(Default)]
+#[derive(Default, Clone, Copy)]
 struct SimulationStats {
     total: usize,
     hw_dropped: usize,
     hw_passed: usize,
     sw_accepted: usize,
     sw_dropped: usize,
+    //group joined but the source is filtered out
+    sw_source_dropped: usize,
 }
 
+//one nic filter shared by both families: ipv4 and ipv6 groups only differ in how they map to a mac
 struct MulticastFilterSimulator {
+    //optional exact-match slots in front of the hash table, which then only holds their overflow
+    perfect: Option<PerfectFilter>,
     hw: HardwareHashTable,
     sw: SoftwareFilter,
     stats: SimulationStats,
+    //the same counters split by family, [ipv4, ipv6]
+    family_stats: [SimulationStats; 2],
     mac_to_ips: HashMap<MacAddress, Vec<IpAddress>>,
 }
 
 impl MulticastFilterSimulator {
     fn new(bits: u8) -> Self {
         Self {
+            perfect: None,
             hw: HardwareHashTable::new(bits),
             sw: SoftwareFilter::new(),
             stats: SimulationStats::default(),
+            family_stats: [SimulationStats::default(); 2],
             mac_to_ips: HashMap::new(),
         }
     }
 
+    fn with_selection(selection: BitSelection) -> Self {
+        let mut sim = Self::new(selection.size_bits);
+        sim.hw = HardwareHashTable::with_selection(selection);
+        sim
+    }
+
+    fn new_hybrid(slots: usize, bits: u8, policy: OverflowPolicy) -> Self {
+        let mut sim = Self::new(bits);
+        sim.perfect = Some(PerfectFilter::new(slots, policy));
+        sim
+    }
+
+    //a plain join is an any-source filter on socket 0
     fn subscribe(&mut self, ip: IpAddress) {
-        assert!(ip.is_multicast());
-        self.sw.subscribe(ip);
+        self.set_source_filter(0, ip, SourceFilter::any_source());
+    }
+
+    //igmpv3/mldv2 socket api: the nic only hears about a group while the
+    //merged interface state is not include {}
+    fn set_source_filter(&mut self, socket: u32, group: IpAddress, filter: SourceFilter) {
+        assert!(group.is_multicast());
+        let was_member = self.sw.is_subscribed(&group);
+        self.sw.set_filter(group, socket, filter);
+        match (was_member, self.sw.is_subscribed(&group)) {
+            (false, true) => {
+                let mac = MacAddress::from_multicast_ip(&group);
+                let hashed = self.hashed_overflow();
+                match &mut self.perfect {
+                    Some(perfect) => {
+                        perfect.add(mac);
+                        self.reprogram_hash(&hashed);
+                    }
+                    None => self.hw.add_mac(&mac),
+                }
+                self.mac_to_ips.entry(mac).or_default().push(group);
+            }
+            (true, false) => self.remove_group(group),
+            _ => {}
+        }
+    }
+
+    //an ipv6 interface joins the solicited-node group of each of its unicast addresses
+    fn add_unicast_v6(&mut self, addr: IpAddress) {
+        if let Some(group) = addr.solicited_node()
+            && !self.sw.is_subscribed(&group)
+        {
+            self.subscribe(group);
+        }
+    }
+
+    fn unsubscribe(&mut self, ip: IpAddress) {
+        if self.sw.is_subscribed(&ip) {
+            self.sw.unsubscribe(&ip);
+            self.remove_group(ip);
+        }
+    }
+
+    //bits can be shared between groups, the counting shadow table decides when one clears
+    fn remove_group(&mut self, ip: IpAddress) {
         let mac = MacAddress::from_multicast_ip(&ip);
-        self.hw.add_mac(&mac);
-        self.mac_to_ips.entry(mac).or_insert_with(Vec::new).push(ip);
+        if let Some(ips) = self.mac_to_ips.get_mut(&mac) {
+            ips.retain(|&other| other != ip);
+            if ips.is_empty() {
+                self.mac_to_ips.remove(&mac);
+            }
+        }
+        let hashed = self.hashed_overflow();
+        match &mut self.perfect {
+            Some(perfect) => {
+                perfect.remove(mac);
+                self.reprogram_hash(&hashed);
+            }
+            None => self.hw.remove_mac(&mac),
+        }
     }
 
-    fn process(&mut self, pkt: MulticastPacket) {
-        self.stats.total += 1;
+    //the macs behind the slots that the hash table holds
+    fn hashed_overflow(&self) -> Vec<MacAddress> {
+        match &self.perfect {
+            Some(perfect) if perfect.policy() == OverflowPolicy::HashTable => perfect.overflow(),
+            _ => vec![],
+        }
+    }
 
-        if !self.hw.check_mac(&pkt.dst_mac) {
-            self.stats.hw_dropped += 1;
-            return;
+    //slot assignments shift as groups come and go; only the macs that moved
+    //in or out of the overflow are added or removed, so the counting shadow
+    //keeps track the same way it does without slots
+    fn reprogram_hash(&mut self, before: &[MacAddress]) {
+        let after = self.hashed_overflow();
+        for mac in before.iter().filter(|mac| !after.contains(mac)) {
+            self.hw.remove_mac(mac);
+        }
+        for mac in after.iter().filter(|mac| !before.contains(mac)) {
+            self.hw.add_mac(mac);
         }
+    }
 
-        self.stats.hw_passed += 1;
+    fn hw_accepts(&self, mac: &MacAddress) -> bool {
+        match &self.perfect {
+            Some(perfect) => {
+                perfect.in_slot(mac) || perfect.all_multicast() || self.hw.check_mac(mac)
+            }
+            None => self.hw.check_mac(mac),
+        }
+    }
 
-        if self.sw.is_subscribed(&pkt.dst_ip) {
-            self.stats.sw_accepted += 1;
-        } else {
-            self.stats.sw_dropped += 1;
+    //true when the packet made it through both stages
+    fn process(&mut self, pkt: MulticastPacket) -> bool {
+        let family = pkt.dst_ip.is_v6() as usize;
+        let hw_pass = self.hw_accepts(&pkt.dst_mac);
+        for stats in [&mut self.stats, &mut self.family_stats[family]] {
+            stats.total += 1;
+
+            if !hw_pass {
+                stats.hw_dropped += 1;
+                continue;
+            }
+
+            stats.hw_passed += 1;
+
+            if !self.sw.is_subscribed(&pkt.dst_ip) {
+                stats.sw_dropped += 1;
+            } else if !self.sw.accepts(&pkt.dst_ip, &pkt.src_ip) {
+                stats.sw_source_dropped += 1;
+            } else {
+                stats.sw_accepted += 1;
+            }
         }
+        hw_pass && self.sw.accepts(&pkt.dst_ip, &pkt.src_ip)
     }
 }
 
@@ -225,11 +510,12 @@ fn main() {
     }
     println!();
 
+    let sender = IpAddress::new(192, 168, 1, 10);
     let mut packets = Vec::new();
 
     for ip in &subs {
         for _ in 0..20 {
-            packets.push(MulticastPacket::new(*ip));
+            packets.push(MulticastPacket::new(sender, *ip));
         }
     }
 
@@ -248,7 +534,7 @@ fn main() {
 
     for ip in &others {
         for _ in 0..30 {
-            packets.push(MulticastPacket::new(*ip));
+            packets.push(MulticastPacket::new(sender, *ip));
         }
     }
 
@@ -284,11 +570,14 @@ fn main() {
             }
         }
     }
-    
+
     if sim.stats.sw_dropped > 0 {
         println!();
         println!("false positive examples (unsubscribed traffic leaked through hw filter):");
-        println!("  {} packets from unsubscribed groups passed hardware filter", sim.stats.sw_dropped);
+        println!(
+            "  {} packets from unsubscribed groups passed hardware filter",
+            sim.stats.sw_dropped
+        );
         println!("  these share hash indices with subscribed groups");
     }
 
@@ -300,4 +589,1778 @@ fn main() {
         let hash_index = crc.hash_to_index(&mac, 4);
         println!("  {} -> {} (hash index: {})", ip, mac, hash_index);
     }
-}
\ No newline at end of file
+
+    ipv6_demo();
+    igmp_demo();
+    source_filter_demo();
+    snooping_demo();
+    hybrid_nic_demo();
+    bloom_demo();
+    bit_selection_demo();
+    crc_demo();
+    ethernet_demo();
+}
+
+fn ipv6_demo() {
+    println!();
+    println!("dual-stack multicast filter");
+    println!();
+
+    let v6 = |s: &str| IpAddress::parse(s).expect("demo address");
+    let mut sim = MulticastFilterSimulator::new(6);
+
+    let groups = [
+        v6("ff02::1"),
+        v6("ff02::fb"),
+        v6("ff05::1:3"),
+        IpAddress::new(224, 0, 0, 1),
+        IpAddress::new(224, 0, 0, 251),
+    ];
+    println!("subscribed groups:");
+    for ip in &groups {
+        println!(
+            "  {:<28} -> {}",
+            ip.to_string(),
+            MacAddress::from_multicast_ip(ip)
+        );
+        sim.subscribe(*ip);
+    }
+
+    //the link-local and global addresses share their low 24 bits, so one group covers both
+    let unicast = [
+        v6("fe80::21b:21ff:fe3c:4d5e"),
+        v6("2001:db8:1::3c:4d5e"),
+        v6("2001:db8:1::7"),
+    ];
+    println!("solicited-node groups for the interface addresses:");
+    for addr in &unicast {
+        let group = addr.solicited_node().expect("unicast address");
+        println!(
+            "  {:<28} -> {} ({})",
+            addr.to_string(),
+            group,
+            MacAddress::from_multicast_ip(&group)
+        );
+        sim.add_unicast_v6(*addr);
+    }
+    println!();
+
+    //other hosts' neighbor solicitations, a group sharing a mac with ff02::fb,
+    //and ipv4 groups that collide in the 23-bit mapping
+    let others = [
+        v6("ff02::2"),
+        v6("ff05::fb"),
+        v6("ff02::1:ff00:8"),
+        v6("ff02::1:ff9a:1b2c"),
+        v6("ff02::1:ff3c:4d5f"),
+        v6("ff0e::101"),
+        IpAddress::new(224, 0, 0, 2),
+        IpAddress::new(239, 0, 0, 251),
+        IpAddress::new(224, 128, 0, 1),
+    ];
+    //each group is sent to from a host of its own family
+    let sender = |group: &IpAddress| {
+        if group.is_v6() {
+            v6("fe80::1")
+        } else {
+            IpAddress::new(192, 168, 1, 1)
+        }
+    };
+    let mut packets = Vec::new();
+    for ip in &sim.sw.groups() {
+        for _ in 0..20 {
+            packets.push(MulticastPacket::new(sender(ip), *ip));
+        }
+    }
+    for ip in &others {
+        for _ in 0..30 {
+            packets.push(MulticastPacket::new(sender(ip), *ip));
+        }
+    }
+    let solicited = packets
+        .iter()
+        .filter(|p| p.dst_ip.is_solicited_node())
+        .count();
+    println!(
+        "processing {} packets ({} to solicited-node groups)...",
+        packets.len(),
+        solicited
+    );
+    for pkt in packets {
+        sim.process(pkt);
+    }
+    println!();
+
+    println!(
+        "{:<8} {:>6} {:>11} {:>10} {:>12} {:>11}",
+        "family", "total", "hw dropped", "hw passed", "sw accepted", "sw dropped"
+    );
+    for (name, stats) in [
+        ("ipv4", sim.family_stats[0]),
+        ("ipv6", sim.family_stats[1]),
+        ("all", sim.stats),
+    ] {
+        println!(
+            "{:<8} {:>6} {:>11} {:>10} {:>12} {:>11}",
+            name,
+            stats.total,
+            stats.hw_dropped,
+            stats.hw_passed,
+            stats.sw_accepted,
+            stats.sw_dropped
+        );
+    }
+
+    println!();
+    println!("groups sharing a mac (only software can tell them apart):");
+    for ip in &others {
+        let mac = MacAddress::from_multicast_ip(ip);
+        if let Some(members) = sim.mac_to_ips.get(&mac) {
+            println!("  {} shares {} with {}", ip, mac, members[0]);
+        }
+    }
+}
+
+fn igmp_demo() {
+    println!();
+    println!("igmpv2 on a simulated lan");
+    println!();
+
+    let g1 = IpAddress::new(239, 1, 1, 1);
+    let g2 = IpAddress::new(239, 2, 2, 2);
+    let mut lan = Lan::new(42);
+    lan.routers
+        .push(IgmpRouter::new("R1", IpAddress::new(10, 0, 0, 1)));
+    lan.routers
+        .push(IgmpRouter::new("R2", IpAddress::new(10, 0, 0, 2)));
+    for i in 0..4 {
+        lan.hosts.push(IgmpHost::new(
+            &format!("H{}", i + 1),
+            IpAddress::new(10, 0, 0, 11 + i),
+            4,
+        ));
+    }
+
+    //times in milliseconds: joins, a leave that is not the last member, the
+    //last members leaving, the querier failing and a host vanishing without a leave
+    lan.run_until(1_000);
+    lan.join(0, g1);
+    lan.join(1, g1);
+    lan.join(2, g2);
+    lan.join(3, g1);
+    lan.run_until(60_000);
+    lan.leave(0, g1);
+    lan.run_until(170_000);
+    lan.leave(1, g1);
+    lan.leave(3, g1);
+    lan.run_until(200_000);
+    lan.routers[0].up = false;
+    lan.run_until(300_000);
+    lan.hosts[2].up = false;
+    lan.run_until(700_000);
+
+    for (at, event) in &lan.log {
+        println!("  {:>8.3}s  {}", *at as f64 / 1000.0, event);
+    }
+    println!();
+
+    println!(
+        "messages: {} queries, {} reports, {} leaves, {} reports suppressed",
+        lan.stats.queries, lan.stats.reports, lan.stats.leaves, lan.stats.suppressed
+    );
+    for router in &lan.routers {
+        let groups: Vec<String> = router.groups().iter().map(|g| g.to_string()).collect();
+        let role = match (router.up, router.is_querier()) {
+            (false, _) => "down",
+            (true, true) => "querier",
+            (true, false) => "non-querier",
+        };
+        println!(
+            "  {} {:<12} {:<11} groups: [{}]",
+            router.name,
+            router.addr.to_string(),
+            role,
+            groups.join(", ")
+        );
+    }
+    for host in &lan.hosts {
+        let groups: Vec<String> = host.groups().iter().map(|g| g.to_string()).collect();
+        let stats = &host.nic.stats;
+        println!(
+            "  {} {:<12} groups: [{}] nic: {} igmp packets, {} dropped in hw, {} in sw",
+            host.name,
+            host.addr.to_string(),
+            groups.join(", "),
+            stats.total,
+            stats.hw_dropped,
+            stats.sw_dropped
+        );
+    }
+}
+
+fn source_filter_demo() {
+    println!();
+    println!("igmpv3/mldv2 source filtering");
+    println!();
+
+    let v6 = |s: &str| IpAddress::parse(s).expect("demo address");
+    let group = IpAddress::new(232, 1, 1, 1);
+    let sources: Vec<IpAddress> = (1..=5).map(|i| IpAddress::new(10, 1, 1, i)).collect();
+    let s = |i: usize| sources[i - 1];
+    let mut sim = MulticastFilterSimulator::new(6);
+
+    //the three-socket example from rfc 3376 section 3.2, then socket 2 closing
+    let steps = [
+        (1, SourceFilter::include(&[s(1), s(2)])),
+        (2, SourceFilter::exclude(&[s(2), s(3)])),
+        (3, SourceFilter::exclude(&[s(3), s(4)])),
+        (2, SourceFilter::include(&[])),
+    ];
+    println!("sockets on {}:", group);
+    for (socket, filter) in steps {
+        println!("  socket {} sets {}", socket, filter);
+        sim.set_source_filter(socket, group, filter);
+        println!(
+            "    interface state: {}",
+            sim.sw
+                .state(&group)
+                .map_or("not a member".to_string(), |f| f.to_string())
+        );
+    }
+
+    //an ssm channel over mldv2, one source wanted
+    let group6 = v6("ff3e::8000:1");
+    let wanted = v6("2001:db8::1");
+    sim.set_source_filter(1, group6, SourceFilter::include(&[wanted]));
+    println!(
+        "  socket 1 on {} sets {}",
+        group6,
+        sim.sw.state(&group6).expect("joined")
+    );
+    println!();
+
+    let mut packets = Vec::new();
+    for src in &sources {
+        for _ in 0..10 {
+            packets.push(MulticastPacket::new(*src, group));
+        }
+    }
+    for src in [wanted, v6("2001:db8::66")] {
+        for _ in 0..10 {
+            packets.push(MulticastPacket::new(src, group6));
+        }
+    }
+    for _ in 0..10 {
+        packets.push(MulticastPacket::new(s(1), IpAddress::new(232, 9, 9, 9)));
+    }
+
+    println!("  {:<20} {:<14} delivered", "source", "group");
+    for pkt in packets {
+        let (src, dst) = (pkt.src_ip, pkt.dst_ip);
+        let delivered = sim.process(pkt);
+        if sim.stats.total.is_multiple_of(10) {
+            println!(
+                "  {:<20} {:<14} {}",
+                src.to_string(),
+                dst.to_string(),
+                delivered
+            );
+        }
+    }
+    println!();
+
+    let stats = sim.stats;
+    println!("  total packets: {}", stats.total);
+    println!("  hardware dropped: {}", stats.hw_dropped);
+    println!("  software accepted: {}", stats.sw_accepted);
+    println!("  software dropped (group): {}", stats.sw_dropped);
+    println!("  software dropped (source): {}", stats.sw_source_dropped);
+}
+
+fn snooping_demo() {
+    println!();
+    println!("igmp snooping switch (8 ports, querier and iptv source on port 0)");
+    println!();
+
+    let runs: Vec<(bool, (SnoopingStats, u64, u64))> = [false, true]
+        .iter()
+        .map(|&f| (f, snooping_run(f, !f)))
+        .collect();
+    println!(
+        "{:<11} {:>12} {:>12} {:>8} {:>12} {:>12}",
+        "leave mode", "bytes sent", "if flooded", "saved", "after leave", "after gone"
+    );
+    for (fast_leave, (stats, after_leave, after_gone)) in runs {
+        println!(
+            "{:<11} {:>12} {:>12} {:>7.1}% {:>12} {:>12}",
+            if fast_leave { "fast" } else { "normal" },
+            stats.bytes_sent,
+            stats.bytes_flooded,
+            stats.savings() * 100.0,
+            after_leave,
+            after_gone
+        );
+    }
+}
+
+//ten minutes of four 1316-byte iptv channels at 50 packets/s. returns the
+//switch stats and the packets that reached a port whose host had left the
+//group, and those that reached a host that was gone
+fn snooping_run(fast_leave: bool, show_table: bool) -> (SnoopingStats, u64, u64) {
+    const PORTS: usize = 8;
+    let channels: Vec<IpAddress> = (1..=4).map(|i| IpAddress::new(239, 10, 0, i)).collect();
+    let source = IpAddress::new(10, 0, 0, 1);
+    let mut switch = SnoopingSwitch::new(PORTS, fast_leave);
+    let mut rng = Rng::new(44);
+
+    //per host port: the channels it wants and whether the host is still alive
+    let mut wants: Vec<Vec<IpAddress>> = vec![vec![]; PORTS];
+    let mut alive = [true; PORTS];
+    //(time, port, message) waiting to reach the switch
+    let mut pending: Vec<(u64, usize, IgmpMessage)> = vec![];
+    let (mut after_leave, mut after_gone) = (0, 0);
+
+    for sec in 0..600u64 {
+        let now = sec * 1000;
+
+        //scripted changes: joins, a leave with members remaining, the last
+        //member leaving, and a host that disappears without a leave
+        let joins: &[(usize, usize)] = match sec {
+            1 => &[(1, 0), (2, 0), (2, 1), (3, 2), (5, 0), (6, 3)],
+            _ => &[],
+        };
+        let leaves: &[(usize, usize)] = match sec {
+            100 => &[(2, 0)],
+            200 => &[(3, 2)],
+            _ => &[],
+        };
+        for &(port, ch) in joins {
+            wants[port].push(channels[ch]);
+            pending.push((now, port, IgmpMessage::Report(channels[ch])));
+        }
+        for &(port, ch) in leaves {
+            wants[port].retain(|&g| g != channels[ch]);
+            pending.push((now, port, IgmpMessage::Leave(channels[ch])));
+            //the querier checks for other members twice, a second apart
+            for k in 0..2 {
+                pending.push((
+                    now + k * 1000,
+                    0,
+                    IgmpMessage::Query {
+                        group: Some(channels[ch]),
+                        max_resp: 1000,
+                    },
+                ));
+            }
+        }
+        if sec == 300 {
+            alive[5] = false;
+        }
+
+        //general queries, every member answers after a random delay
+        if now.is_multiple_of(QUERY_INTERVAL) {
+            pending.push((
+                now,
+                0,
+                IgmpMessage::Query {
+                    group: None,
+                    max_resp: QUERY_RESPONSE_INTERVAL,
+                },
+            ));
+            for port in 1..PORTS {
+                for &group in wants[port].iter().filter(|_| alive[port]) {
+                    pending.push((
+                        now + rng.below(QUERY_RESPONSE_INTERVAL),
+                        port,
+                        IgmpMessage::Report(group),
+                    ));
+                }
+            }
+        }
+
+        pending.sort_by_key(|&(at, _, _)| at);
+        let due = pending
+            .iter()
+            .take_while(|&&(at, _, _)| at < now + 1000)
+            .count();
+        for (at, port, msg) in pending.drain(..due) {
+            //members answer group-specific queries for groups they still want
+            if let IgmpMessage::Query {
+                group: Some(group), ..
+            } = msg
+            {
+                for p in (1..PORTS).filter(|&p| alive[p] && wants[p].contains(&group)) {
+                    switch.receive_igmp(at, p, IgmpMessage::Report(group));
+                }
+            }
+            switch.receive_igmp(at, port, msg);
+        }
+        switch.expire(now);
+
+        for group in &channels {
+            for _ in 0..50 {
+                let pkt = MulticastPacket::new(source, *group);
+                for port in switch.forward(now, 0, &pkt, 1316) {
+                    if !alive[port] {
+                        after_gone += 1;
+                    } else if port != 0 && !wants[port].contains(group) {
+                        after_leave += 1;
+                    }
+                }
+            }
+        }
+
+        if show_table && [50, 101, 150, 250, 580].contains(&sec) {
+            let groups: Vec<String> = switch
+                .groups()
+                .iter()
+                .map(|g| format!("{} -> {:?}", g, switch.member_ports(now, g)))
+                .collect();
+            println!(
+                "  t={:>3}s routers {:?}  {}",
+                sec,
+                switch.router_ports(now),
+                groups.join("  ")
+            );
+        }
+    }
+    if show_table {
+        println!();
+    }
+    (switch.stats, after_leave, after_gone)
+}
+
+fn hybrid_nic_demo() {
+    println!();
+    println!("perfect-match slots in front of the hash table");
+    println!();
+
+    //a fixed mix: 20% of packets for joined groups, the rest for random other groups
+    let configs: [(&str, Option<(usize, OverflowPolicy)>); 3] = [
+        ("hash only", None),
+        ("16 + hash", Some((16, OverflowPolicy::HashTable))),
+        ("16 + allmulti", Some((16, OverflowPolicy::AllMulticast))),
+    ];
+    let counts = [1, 4, 8, 12, 16, 17, 24, 32, 64, 128];
+    const BITS: u8 = 6;
+
+    print!("{:>6}", "groups");
+    for (name, _) in &configs {
+        print!(" {:>15}", name);
+    }
+    println!("   hardware drop rate, about 80% of the traffic is unwanted");
+
+    for &n in &counts {
+        print!("{:>6}", n);
+        for (_, config) in &configs {
+            let mut rng = Rng::new(46);
+            let mut sim = match config {
+                Some((slots, policy)) => {
+                    MulticastFilterSimulator::new_hybrid(*slots, BITS, *policy)
+                }
+                None => MulticastFilterSimulator::new(BITS),
+            };
+            let random_group = |rng: &mut Rng| {
+                let x = rng.next_u64();
+                IpAddress::new(239, (x >> 16) as u8, (x >> 8) as u8, x as u8)
+            };
+
+            let mut joined = Vec::new();
+            while joined.len() < n {
+                let group = random_group(&mut rng);
+                if !joined.contains(&group) {
+                    sim.subscribe(group);
+                    joined.push(group);
+                }
+            }
+            let sender = IpAddress::new(10, 0, 0, 1);
+            for _ in 0..20_000 {
+                let dst = if rng.below(5) == 0 {
+                    joined[rng.below(n as u64) as usize]
+                } else {
+                    random_group(&mut rng)
+                };
+                sim.process(MulticastPacket::new(sender, dst));
+            }
+            print!(
+                " {:>14.1}%",
+                sim.stats.hw_dropped as f64 / sim.stats.total as f64 * 100.0
+            );
+        }
+        println!();
+    }
+}
+
+fn bloom_demo() {
+    println!();
+    println!("k-hash bloom filter in the nic hash table");
+    println!();
+
+    println!(
+        "{:>6} {:>7} {:>8} {:>3} {:>9} {:>11} {:>12}",
+        "groups", "target", "entries", "k", "theory", "crc slices", "double hash"
+    );
+    for n in [16, 64] {
+        for target in [0.1, 0.01, 0.001] {
+            let (bits, k) = bloom_params(n, target);
+            println!(
+                "{:>6} {:>6.1}% {:>8} {:>3} {:>8.3}% {:>10.3}% {:>11.3}%",
+                n,
+                target * 100.0,
+                1 << bits,
+                k,
+                bloom_fpr(bits, k, n) * 100.0,
+                measure_fpr(bits, k, IndexScheme::CrcSlices, n) * 100.0,
+                measure_fpr(bits, k, IndexScheme::DoubleHashing, n) * 100.0
+            );
+        }
+    }
+    println!();
+
+    //the optimum sits near k = m/n ln 2 = 5.5. crc slices stay close to theory
+    //while the two crcs have bits left, k = 8 needs 72 and falls back to double
+    //hashing. that drifts above theory as k grows: members that share h2 share
+    //most of their indices, and in a table this small that happens often
+    println!("64 groups in 512 entries:");
+    println!(
+        "{:>3} {:>9} {:>11} {:>12}",
+        "k", "theory", "crc slices", "double hash"
+    );
+    for k in 1..=8 {
+        println!(
+            "{:>3} {:>8.2}% {:>10.2}% {:>11.2}%",
+            k,
+            bloom_fpr(9, k, 64) * 100.0,
+            measure_fpr(9, k, IndexScheme::CrcSlices, 64) * 100.0,
+            measure_fpr(9, k, IndexScheme::DoubleHashing, 64) * 100.0
+        );
+    }
+}
+
+//share of macs outside n random joined groups that still pass, averaged over a few group sets
+fn measure_fpr(size_bits: u8, k: u8, scheme: IndexScheme, n: usize) -> f64 {
+    let mut rng = Rng::new(47);
+    let random_mac = |rng: &mut Rng| {
+        let x = rng.next_u64();
+        MacAddress::from_multicast_ip(&IpAddress::new(
+            239,
+            (x >> 16) as u8,
+            (x >> 8) as u8,
+            x as u8,
+        ))
+    };
+
+    let (trials, probes) = (10, 20_000);
+    let mut passed = 0;
+    for _ in 0..trials {
+        let mut table = HardwareHashTable::with_hashes(size_bits, k, scheme);
+        let mut joined = HashSet::new();
+        while joined.len() < n {
+            let mac = random_mac(&mut rng);
+            if joined.insert(mac) {
+                table.add_mac(&mac);
+            }
+        }
+
+        let mut probed = 0;
+        while probed < probes {
+            let mac = random_mac(&mut rng);
+            if joined.contains(&mac) {
+                continue;
+            }
+            probed += 1;
+            if table.check_mac(&mac) {
+                passed += 1;
+            }
+        }
+    }
+    passed as f64 / (trials * probes) as f64
+}
+
+//what a typical desktop joins: all-hosts, mdns, ssdp, igmpv3 reports, a few
+//site-local streams, and the ipv6 all-nodes, mdns and solicited-node groups
+fn desktop_groups() -> Vec<IpAddress> {
+    let mut groups = vec![
+        IpAddress::new(224, 0, 0, 1),
+        IpAddress::new(224, 0, 0, 22),
+        IpAddress::new(224, 0, 0, 251),
+        IpAddress::new(224, 0, 0, 252),
+        IpAddress::new(239, 255, 255, 250),
+        IpAddress::new(239, 255, 255, 253),
+    ];
+    for stream in 1..=6 {
+        groups.push(IpAddress::new(239, 192, 0, stream));
+    }
+    for v6 in ["ff02::1", "ff02::fb", "ff02::1:3", "ff02::c", "ff02::16"] {
+        groups.push(IpAddress::parse(v6).unwrap());
+    }
+    for unicast in [
+        "fe80::21b:21ff:fe3c:4d5e",
+        "2001:db8::1a2b",
+        "2001:db8::7:77",
+    ] {
+        groups.push(IpAddress::parse(unicast).unwrap().solicited_node().unwrap());
+    }
+    groups
+}
+
+fn bit_selection_demo() {
+    println!();
+    println!("hash bit selection across nic families");
+    println!();
+
+    let crc = Crc32::new();
+    let groups = desktop_groups();
+    println!("{} subscribed groups", groups.len());
+    println!(
+        "{:<15} {:>7} {:<45} {:>7} {:>9} {:>9}",
+        "preset", "entries", "index", "buckets", "colliding", "hw drop"
+    );
+    for selection in &NIC_PRESETS {
+        let used: HashSet<usize> = groups
+            .iter()
+            .map(|g| selection.index(&crc, &MacAddress::from_multicast_ip(g)))
+            .collect();
+        let colliding: usize = collisions(selection, &crc, &groups)
+            .iter()
+            .map(|(_, g)| g.len())
+            .sum();
+        println!(
+            "{:<15} {:>7} {:<45} {:>7} {:>9} {:>8.1}%",
+            selection.name,
+            selection.entries(),
+            selection.to_string(),
+            used.len(),
+            colliding,
+            selection_drop_rate(*selection, &groups) * 100.0
+        );
+    }
+
+    for selection in &NIC_PRESETS {
+        let report = collisions(selection, &crc, &groups);
+        if report.is_empty() {
+            continue;
+        }
+        println!();
+        println!("{} collisions:", selection.name);
+        for (index, bucket) in report {
+            let names: Vec<String> = bucket.iter().map(|g| g.to_string()).collect();
+            println!("  {:>4}: {}", index, names.join(", "));
+        }
+    }
+
+    //the same groups across table sizes. inverting the crc complements every
+    //index and reversing it turns the top bits into the low bits backwards, so
+    //both only relabel buckets: top or low slice is the choice that matters.
+    //the address window keeps random traffic apart well but puts groups that
+    //end in the same bits together, like 224.0.0.1 and ff02::1
+    println!();
+    println!("hardware drop rate by table size and index");
+    let forms = [
+        (
+            "top of crc",
+            HashSource::Crc {
+                inverted: true,
+                reversed: false,
+            },
+            Slice::Top,
+        ),
+        (
+            "low of crc",
+            HashSource::Crc {
+                inverted: true,
+                reversed: false,
+            },
+            Slice::Low,
+        ),
+        ("address bits", HashSource::AddressBits, Slice::From(36)),
+    ];
+    print!("{:>7}", "entries");
+    for (name, _, _) in &forms {
+        print!(" {:>12}", name);
+    }
+    println!();
+    for size_bits in 6..=12 {
+        print!("{:>7}", 1 << size_bits);
+        for &(name, source, slice) in &forms {
+            let selection = BitSelection {
+                name,
+                size_bits,
+                source,
+                slice,
+            };
+            print!(
+                " {:>11.1}%",
+                selection_drop_rate(selection, &groups) * 100.0
+            );
+        }
+        println!();
+    }
+}
+
+//share of traffic to random unjoined groups of both families that the hash table stops
+fn selection_drop_rate(selection: BitSelection, groups: &[IpAddress]) -> f64 {
+    let mut sim = MulticastFilterSimulator::with_selection(selection);
+    for &group in groups {
+        sim.subscribe(group);
+    }
+
+    let mut rng = Rng::new(48);
+    let sender = IpAddress::new(10, 0, 0, 1);
+    let mut sent = 0;
+    while sent < 20_000 {
+        let x = rng.next_u64();
+        let dst = if x & 1 == 0 {
+            IpAddress::new(
+                224 + (x >> 1) as u8 % 16,
+                (x >> 8) as u8,
+                (x >> 16) as u8,
+                (x >> 24) as u8,
+            )
+        } else {
+            let mut b = [0u8; 16];
+            b[0] = 0xFF;
+            b[1] = 0x02;
+            b[12..].copy_from_slice(&((x >> 32) as u32).to_be_bytes());
+            IpAddress::V6(b)
+        };
+        if groups.contains(&dst) {
+            continue;
+        }
+        sent += 1;
+        sim.process(MulticastPacket::new(sender, dst));
+    }
+    sim.stats.hw_dropped as f64 / sim.stats.total as f64
+}
+
+fn crc_demo() {
+    println!();
+    println!("rocksoft-model crc presets");
+    println!();
+
+    let check = b"123456789";
+    println!(
+        "{:<19} {:>5} {:>18} {:>18} {:>18} {:>18}",
+        "name", "width", "poly", "check", "bytewise", "slicing-by-8"
+    );
+    for params in &PRESETS {
+        let crc = Crc::new(*params);
+        let (bytewise, sliced) = (crc.checksum_bytewise(check), crc.checksum(check));
+        let ok = bytewise == params.check && sliced == params.check;
+        println!(
+            "{:<19} {:>5} {:>#18x} {:>#18x} {:>#18x} {:>#18x} {}",
+            params.name,
+            params.width,
+            params.poly,
+            params.check,
+            bytewise,
+            sliced,
+            if ok { "ok" } else { "MISMATCH" }
+        );
+    }
+
+    println!();
+    if cfg!(debug_assertions) {
+        println!("throughput (debug build, use cargo run --release for real numbers)");
+    } else {
+        println!("throughput");
+    }
+    let mut rng = Rng::new(49);
+    let data: Vec<u8> = (0..4 << 20).map(|_| rng.next_u64() as u8).collect();
+    println!(
+        "{:<19} {:>13} {:>13} {:>8}",
+        "name", "bytewise", "slicing-by-8", "speedup"
+    );
+    for params in [CRC_32, CRC_32C, CRC_16_CCITT_FALSE, CRC_64_XZ] {
+        let crc = Crc::new(params);
+        let bytewise = crc_throughput(&data, |d| crc.checksum_bytewise(d));
+        let sliced = crc_throughput(&data, |d| crc.checksum(d));
+        assert_eq!(crc.checksum_bytewise(&data), crc.checksum(&data));
+        println!(
+            "{:<19} {:>8.0} MB/s {:>8.0} MB/s {:>7.1}x",
+            crc.params().name,
+            bytewise,
+            sliced,
+            sliced / bytewise
+        );
+    }
+}
+
+//best of a few passes over the buffer, in MB/s
+fn crc_throughput(data: &[u8], checksum: impl Fn(&[u8]) -> u64) -> f64 {
+    let mut best = f64::MAX;
+    for _ in 0..3 {
+        let start = std::time::Instant::now();
+        std::hint::black_box(checksum(std::hint::black_box(data)));
+        best = best.min(start.elapsed().as_secs_f64());
+    }
+    data.len() as f64 / best / 1e6
+}
+
+fn ethernet_demo() {
+    println!();
+    println!("ethernet frames with fcs");
+    println!();
+
+    let crc = Crc32::new();
+    let src = MacAddress([0x02, 0x00, 0x5E, 0x10, 0x00, 0x01]);
+    let mut rng = Rng::new(50);
+    let stream = EthernetFrame {
+        dst: MacAddress::from_multicast_ip(&IpAddress::new(239, 192, 0, 1)),
+        src,
+        vlan: Some(VlanTag {
+            pcp: 5,
+            dei: false,
+            vid: 100,
+        }),
+        ethertype: ETHERTYPE_IPV4,
+        payload: (0..1316).map(|_| rng.next_u64() as u8).collect(),
+    };
+    let short = EthernetFrame {
+        dst: MacAddress::from_multicast_ip(&IpAddress::new(224, 0, 0, 22)),
+        src,
+        vlan: None,
+        ethertype: ETHERTYPE_IPV4,
+        payload: vec![0x46; 32],
+    };
+
+    for frame in [&stream, &short] {
+        let bytes = frame.to_bytes(&crc);
+        let fcs = u32::from_le_bytes(bytes[bytes.len() - 4..].try_into().unwrap());
+        print!(
+            "{} -> {}, {} bytes, fcs {:08x}",
+            frame.src,
+            frame.dst,
+            bytes.len(),
+            fcs
+        );
+        match EthernetFrame::parse(&bytes, &crc) {
+            Ok(parsed) => {
+                let tag = parsed
+                    .vlan
+                    .map(|t| format!(", vlan {} pcp {}", t.vid, t.pcp))
+                    .unwrap_or_default();
+                println!(
+                    ", parsed back: ethertype {:04x}{}, {} payload bytes{}",
+                    parsed.ethertype,
+                    tag,
+                    parsed.payload.len(),
+                    if parsed == *frame { "" } else { " (padded)" }
+                );
+            }
+            Err(e) => println!(", {}", e),
+        }
+    }
+
+    let mut bytes = stream.to_bytes(&crc);
+    bytes[100] ^= 0x10;
+    println!(
+        "one flipped bit: {}",
+        EthernetFrame::parse(&bytes, &crc).unwrap_err()
+    );
+    println!(
+        "truncated: {}",
+        EthernetFrame::parse(&bytes[..40], &crc).unwrap_err()
+    );
+
+    //crc-32 catches every single-bit error and every burst up to 32 bits, and
+    //misses anything else with odds of about 1 in 2^32, so no trial here
+    //should get through. the same body with a one-byte crc-8 shows what the
+    //width buys: bursts up to 8 bits are still caught, and since 0x31 has x + 1
+    //as a factor so is every error that flips an odd number of bits. an even
+    //number slips through about 1 time in 128; the interior of a longer burst
+    //is random, so it is even about half the time and missed about 1 in 256.
+    //both crcs are reflected, so a burst on the wire is also a burst in the
+    //order they divide the bits
+    println!();
+    println!("error injection on a {} byte frame", bytes.len());
+    println!(
+        "{:<16} {:>7} {:>17} {:>17}",
+        "errors", "trials", "crc-32 missed", "crc-8 missed"
+    );
+    let patterns = [
+        ErrorPattern::SingleBit,
+        ErrorPattern::Burst(2),
+        ErrorPattern::Burst(8),
+        ErrorPattern::Burst(9),
+        ErrorPattern::Burst(32),
+        ErrorPattern::Burst(33),
+        ErrorPattern::Burst(100),
+        ErrorPattern::Random(2),
+        ErrorPattern::Random(3),
+        ErrorPattern::Random(4),
+        ErrorPattern::Random(32),
+    ];
+    let clean = stream.to_bytes(&crc);
+    let crc8 = Crc::new(CRC_8_MAXIM_DOW);
+    let mut weak = clean[..clean.len() - 4].to_vec();
+    weak.push(crc8.checksum(&weak) as u8);
+
+    let trials = 10_000;
+    for pattern in patterns {
+        let (mut missed, mut missed8) = (0, 0);
+        for _ in 0..trials {
+            let mut bytes = clean.clone();
+            inject_errors(&mut bytes, pattern, &mut rng);
+            if EthernetFrame::parse(&bytes, &crc).is_ok() {
+                missed += 1;
+            }
+
+            let mut bytes = weak.clone();
+            inject_errors(&mut bytes, pattern, &mut rng);
+            let (body, check) = bytes.split_at(bytes.len() - 1);
+            if crc8.checksum(body) as u8 == check[0] {
+                missed8 += 1;
+            }
+        }
+        let percent = |n: usize| n as f64 / trials as f64 * 100.0;
+        println!(
+            "{:<16} {:>7} {:>6} ({:>6.2}%) {:>6} ({:>6.2}%)",
+            pattern,
+            trials,
+            missed,
+            percent(missed),
+            missed8,
+            percent(missed8)
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ethernet::FrameError;
+    use crate::igmp::{
+        LAST_MEMBER_QUERY_INTERVAL, OTHER_QUERIER_PRESENT_INTERVAL, QUERY_INTERVAL, ROBUSTNESS,
+    };
+
+    const GROUP: IpAddress = IpAddress::V4([239, 1, 1, 1]);
+
+    fn igmp_lan(routers: u8, hosts: u8) -> Lan {
+        let mut lan = Lan::new(1);
+        for i in 0..routers {
+            let name = format!("R{}", i + 1);
+            lan.routers
+                .push(IgmpRouter::new(&name, IpAddress::new(10, 0, 0, 1 + i)));
+        }
+        for i in 0..hosts {
+            let name = format!("H{}", i + 1);
+            lan.hosts
+                .push(IgmpHost::new(&name, IpAddress::new(10, 0, 0, 11 + i), 4));
+        }
+        lan
+    }
+
+    //(time, sender) of every logged message that reads `what`, after `since`
+    fn sent(lan: &Lan, what: &str, since: u64) -> Vec<(u64, String)> {
+        lan.log
+            .iter()
+            .filter(|(at, _)| *at > since)
+            .filter_map(|(at, line)| {
+                let (from, msg) = line.split_once(" -> ")?;
+                (msg == what).then(|| (*at, from.to_string()))
+            })
+            .collect()
+    }
+
+    fn last_general_query(lan: &Lan) -> u64 {
+        lan.log
+            .iter()
+            .rev()
+            .find(|(_, line)| line.contains("general query"))
+            .map(|&(at, _)| at)
+            .expect("a general query")
+    }
+
+    #[test]
+    fn second_member_suppresses_its_report() {
+        let mut lan = igmp_lan(1, 2);
+        lan.join(0, GROUP);
+        lan.join(1, GROUP);
+        lan.run_until(100_000);
+        let next_query = last_general_query(&lan) + QUERY_INTERVAL;
+        let suppressed = lan.stats.suppressed;
+
+        lan.run_until(next_query + QUERY_RESPONSE_INTERVAL);
+        assert_eq!(last_general_query(&lan), next_query);
+        let report = format!("report {}", GROUP);
+        assert_eq!(sent(&lan, &report, next_query - 1).len(), 1);
+        assert_eq!(lan.stats.suppressed, suppressed + 1);
+    }
+
+    #[test]
+    fn only_the_last_reporter_sends_a_leave() {
+        let mut lan = igmp_lan(1, 2);
+        lan.join(0, GROUP);
+        lan.join(1, GROUP);
+        lan.run_until(100_000);
+        let report = format!("report {}", GROUP);
+        let (_, reporter) = sent(&lan, &report, 0).pop().unwrap();
+        let (quiet, loud) = if reporter == "H1" { (1, 0) } else { (0, 1) };
+
+        lan.leave(quiet, GROUP);
+        assert_eq!(lan.stats.leaves, 0);
+        lan.leave(loud, GROUP);
+        assert_eq!(lan.stats.leaves, 1);
+        assert_eq!(sent(&lan, &format!("leave {}", GROUP), 0)[0].1, reporter);
+    }
+
+    #[test]
+    fn group_expires_after_unanswered_last_member_queries() {
+        let mut lan = igmp_lan(1, 1);
+        lan.join(0, GROUP);
+        lan.run_until(60_000);
+        assert_eq!(lan.routers[0].groups(), vec![GROUP]);
+
+        lan.leave(0, GROUP);
+        let query = format!("group-specific query {}", GROUP);
+        let last_query = 60_000 + ROBUSTNESS * LAST_MEMBER_QUERY_INTERVAL;
+        lan.run_until(last_query - 1);
+        assert_eq!(lan.routers[0].groups(), vec![GROUP]);
+        lan.run_until(last_query);
+        assert!(lan.routers[0].groups().is_empty());
+        let queries: Vec<u64> = sent(&lan, &query, 0).iter().map(|&(at, _)| at).collect();
+        assert_eq!(queries, vec![60_000, 60_000 + LAST_MEMBER_QUERY_INTERVAL]);
+        assert!(
+            lan.log.iter().any(|(at, line)| *at == last_query
+                && line == &format!("R1: no members left for {}", GROUP))
+        );
+    }
+
+    #[test]
+    fn lowest_address_wins_the_querier_election() {
+        let mut lan = igmp_lan(2, 0);
+        lan.run_until(1_000);
+        assert!(lan.routers[0].is_querier());
+        assert!(!lan.routers[1].is_querier());
+
+        //the querier fails right after a query, the other router waits it out
+        lan.run_until(100_000);
+        let last_heard = last_general_query(&lan);
+        lan.routers[0].up = false;
+        lan.run_until(last_heard + OTHER_QUERIER_PRESENT_INTERVAL - 1);
+        assert!(!lan.routers[1].is_querier());
+        lan.run_until(last_heard + OTHER_QUERIER_PRESENT_INTERVAL);
+        assert!(lan.routers[1].is_querier());
+        let queries = sent(&lan, "general query (max resp 10s)", last_heard);
+        assert_eq!(
+            queries,
+            vec![(
+                last_heard + OTHER_QUERIER_PRESENT_INTERVAL,
+                "R2".to_string()
+            )]
+        );
+    }
+
+    #[test]
+    fn yielding_querier_stops_its_last_member_queries() {
+        let mut lan = igmp_lan(2, 1);
+        lan.routers[0].up = false;
+        lan.join(0, GROUP);
+        lan.run_until(60_000);
+        assert!(lan.routers[1].is_querier());
+
+        //R2 starts the last member queries, then R1 comes up and takes over
+        lan.leave(0, GROUP);
+        lan.routers[0].up = true;
+        lan.run_until(60_000 + 5 * LAST_MEMBER_QUERY_INTERVAL);
+        assert!(!lan.routers[1].is_querier());
+        let query = format!("group-specific query {}", GROUP);
+        assert_eq!(sent(&lan, &query, 0), vec![(60_000, "R2".to_string())]);
+    }
+
+    #[test]
+    fn report_timer_only_resets_to_a_shorter_deadline() {
+        let mut lan = igmp_lan(1, 3);
+        for host in 0..3 {
+            lan.join(host, GROUP);
+        }
+        lan.run_until(100_000);
+        let report = format!("report {}", GROUP);
+        let reporter = |lan: &Lan| {
+            let (_, name) = sent(lan, &report, 0).pop().unwrap();
+            name[1..].parse::<usize>().unwrap() - 1
+        };
+
+        //the general query gives everyone up to 10s, the leave right behind it
+        //brings the others' deadlines in to 1s
+        let query = last_general_query(&lan) + QUERY_INTERVAL;
+        lan.run_until(query);
+        assert_eq!(last_general_query(&lan), query);
+        assert!(sent(&lan, &report, query - 1).is_empty());
+        lan.leave(reporter(&lan), GROUP);
+        lan.run_until(query + LAST_MEMBER_QUERY_INTERVAL);
+        assert_eq!(sent(&lan, &report, query).len(), 1);
+
+        //the other way round: a 1s deadline survives a general query from a router coming up
+        let now = query + 5 * LAST_MEMBER_QUERY_INTERVAL;
+        lan.run_until(now);
+        lan.leave(reporter(&lan), GROUP);
+        lan.routers
+            .push(IgmpRouter::new("R2", IpAddress::new(10, 0, 0, 2)));
+        lan.run_until(now + LAST_MEMBER_QUERY_INTERVAL);
+        assert_eq!(
+            sent(&lan, "general query (max resp 10s)", now - 1),
+            vec![(now, "R2".to_string())]
+        );
+        assert_eq!(sent(&lan, &report, now).len(), 1);
+    }
+
+    #[test]
+    fn fast_leave_prunes_the_port_at_once() {
+        let group = IpAddress::new(239, 1, 2, 3);
+        let pkt = MulticastPacket::new(IpAddress::new(10, 0, 0, 1), group);
+        let last_member_wait = ROBUSTNESS * LAST_MEMBER_QUERY_INTERVAL;
+        for fast_leave in [true, false] {
+            let mut switch = SnoopingSwitch::new(4, fast_leave);
+            switch.receive_igmp(0, 1, IgmpMessage::Report(group));
+            switch.receive_igmp(0, 2, IgmpMessage::Report(group));
+            switch.receive_igmp(5_000, 1, IgmpMessage::Leave(group));
+
+            //without fast-leave the port keeps the traffic until the last member queries go unanswered
+            let right_after = if fast_leave { vec![2] } else { vec![1, 2] };
+            assert_eq!(switch.forward(5_001, 0, &pkt, 100), right_after);
+            assert_eq!(
+                switch.forward(5_000 + last_member_wait - 1, 0, &pkt, 100),
+                right_after
+            );
+            assert_eq!(
+                switch.forward(5_000 + last_member_wait, 0, &pkt, 100),
+                vec![2]
+            );
+        }
+    }
+
+    #[test]
+    fn router_ports_expire_without_queries() {
+        let group = IpAddress::new(239, 1, 2, 3);
+        let pkt = MulticastPacket::new(IpAddress::new(10, 0, 0, 1), group);
+        let query = IgmpMessage::Query {
+            group: None,
+            max_resp: QUERY_RESPONSE_INTERVAL,
+        };
+        let mut switch = SnoopingSwitch::new(4, false);
+        switch.receive_igmp(0, 3, query);
+
+        //unknown groups still reach the router until its querier goes quiet
+        assert_eq!(switch.forward(1, 0, &pkt, 100), vec![3]);
+        assert_eq!(
+            switch.forward(OTHER_QUERIER_PRESENT_INTERVAL - 1, 0, &pkt, 100),
+            vec![3]
+        );
+        assert!(
+            switch
+                .forward(OTHER_QUERIER_PRESENT_INTERVAL, 0, &pkt, 100)
+                .is_empty()
+        );
+
+        //a later query renews the port, expire() forgets it once the timer runs out
+        switch.receive_igmp(OTHER_QUERIER_PRESENT_INTERVAL, 3, query);
+        assert_eq!(switch.router_ports(OTHER_QUERIER_PRESENT_INTERVAL), vec![3]);
+        switch.expire(2 * OTHER_QUERIER_PRESENT_INTERVAL);
+        assert!(switch.router_ports(0).is_empty());
+    }
+
+    #[test]
+    #[should_panic(expected = "port 4 on a 4-port switch")]
+    fn reports_on_a_missing_port_are_refused() {
+        let mut switch = SnoopingSwitch::new(4, false);
+        switch.receive_igmp(0, 4, IgmpMessage::Report(IpAddress::new(239, 1, 2, 3)));
+    }
+
+    #[test]
+    #[should_panic(expected = "port 9 on a 4-port switch")]
+    fn packets_from_a_missing_port_are_refused() {
+        let pkt = MulticastPacket::new(IpAddress::new(10, 0, 0, 1), IpAddress::new(239, 1, 2, 3));
+        SnoopingSwitch::new(4, false).forward(0, 9, &pkt, 100);
+    }
+
+    const BITS: u8 = 4;
+
+    //two groups with different macs that land on the same hash index
+    fn colliding_pair() -> (IpAddress, IpAddress) {
+        let crc = Crc32::new();
+        let first = IpAddress::new(239, 1, 1, 1);
+        let index = crc.hash_to_index(&MacAddress::from_multicast_ip(&first), BITS);
+        let second = (2..=255)
+            .map(|d| IpAddress::new(239, 1, 1, d))
+            .find(|ip| crc.hash_to_index(&MacAddress::from_multicast_ip(ip), BITS) == index)
+            .expect("16 buckets collide within 255 groups");
+        (first, second)
+    }
+
+    fn passes_hw(sim: &MulticastFilterSimulator, ip: IpAddress) -> bool {
+        sim.hw.check_mac(&MacAddress::from_multicast_ip(&ip))
+    }
+
+    #[test]
+    fn colliding_groups_leave_in_join_order() {
+        let (a, b) = colliding_pair();
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(a);
+        sim.subscribe(b);
+
+        sim.unsubscribe(a);
+        assert!(passes_hw(&sim, b));
+        assert!(!sim.sw.is_subscribed(&a));
+
+        sim.unsubscribe(b);
+        assert!(!passes_hw(&sim, b));
+        assert!(sim.hw.bits.iter().all(|&bit| !bit));
+    }
+
+    #[test]
+    fn colliding_groups_leave_in_reverse_order() {
+        let (a, b) = colliding_pair();
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(a);
+        sim.subscribe(b);
+
+        sim.unsubscribe(b);
+        assert!(passes_hw(&sim, a));
+        sim.unsubscribe(a);
+        assert!(!passes_hw(&sim, a));
+    }
+
+    #[test]
+    fn colliding_groups_interleaved() {
+        let (a, b) = colliding_pair();
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(a);
+        sim.unsubscribe(a);
+        assert!(!passes_hw(&sim, a));
+
+        sim.subscribe(b);
+        sim.subscribe(a);
+        sim.unsubscribe(b);
+        assert!(passes_hw(&sim, a));
+        assert!(sim.process(MulticastPacket::new(IpAddress::new(10, 0, 0, 1), a)));
+        assert!(!sim.process(MulticastPacket::new(IpAddress::new(10, 0, 0, 1), b)));
+    }
+
+    #[test]
+    fn groups_sharing_a_mac_keep_the_bit() {
+        //the 23-bit mapping gives both the same mac
+        let a = IpAddress::new(224, 1, 2, 3);
+        let b = IpAddress::new(239, 129, 2, 3);
+        assert_eq!(
+            MacAddress::from_multicast_ip(&a),
+            MacAddress::from_multicast_ip(&b)
+        );
+
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(a);
+        sim.subscribe(b);
+        sim.unsubscribe(a);
+        assert!(passes_hw(&sim, b));
+        sim.unsubscribe(b);
+        assert!(!passes_hw(&sim, b));
+    }
+
+    #[test]
+    fn repeated_join_and_leave_do_not_skew_counts() {
+        let (a, b) = colliding_pair();
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(a);
+        sim.subscribe(a);
+        sim.subscribe(b);
+        sim.unsubscribe(a);
+        sim.unsubscribe(a);
+        assert!(passes_hw(&sim, b));
+        sim.unsubscribe(b);
+        assert!(!passes_hw(&sim, b));
+    }
+
+    #[test]
+    fn bitmap_matches_a_rebuild_after_random_churn() {
+        let groups: Vec<IpAddress> = (1..=40).map(|d| IpAddress::new(239, 2, 0, d)).collect();
+        let mut rng = Rng::new(45);
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        let mut joined = HashSet::new();
+
+        for _ in 0..2_000 {
+            let group = groups[rng.below(groups.len() as u64) as usize];
+            if rng.below(2) == 0 {
+                sim.subscribe(group);
+                joined.insert(group);
+            } else {
+                sim.unsubscribe(group);
+                joined.remove(&group);
+            }
+
+            let mut fresh = HardwareHashTable::new(BITS);
+            for ip in &joined {
+                fresh.add_mac(&MacAddress::from_multicast_ip(ip));
+            }
+            assert_eq!(sim.hw.bits, fresh.bits);
+        }
+    }
+
+    fn ip(s: &str) -> IpAddress {
+        IpAddress::parse(s).unwrap()
+    }
+
+    #[test]
+    fn ipv6_groups_map_to_33_33() {
+        assert_eq!(
+            MacAddress::from_multicast_ip(&ip("ff02::1:ff00:1")),
+            MacAddress([0x33, 0x33, 0xFF, 0x00, 0x00, 0x01])
+        );
+        assert_eq!(
+            MacAddress::from_multicast_ip(&ip("ff02::1")).to_string(),
+            "33:33:00:00:00:01"
+        );
+        //only the low 32 bits make it into the mac
+        assert_eq!(
+            MacAddress::from_multicast_ip(&ip("ff05::fb")),
+            MacAddress::from_multicast_ip(&ip("ff02::fb"))
+        );
+    }
+
+    #[test]
+    fn solicited_node_keeps_the_low_24_bits() {
+        let group = ip("2001:db8::21b:21ff:fe3c:4d5e").solicited_node();
+        assert_eq!(group, Some(ip("ff02::1:ff3c:4d5e")));
+        assert!(group.unwrap().is_solicited_node());
+        assert_eq!(ip("fe80::1").solicited_node(), Some(ip("ff02::1:ff00:1")));
+
+        assert_eq!(ip("ff02::1").solicited_node(), None);
+        assert_eq!(ip("ff02::1:ff00:1").solicited_node(), None);
+        assert_eq!(IpAddress::new(10, 0, 0, 1).solicited_node(), None);
+
+        assert!(!ip("ff02::1").is_solicited_node());
+        assert!(!ip("ff05::1:ff00:1").is_solicited_node());
+        assert!(!ip("fe80::1").is_solicited_node());
+        assert!(!IpAddress::new(224, 0, 0, 1).is_solicited_node());
+    }
+
+    #[test]
+    fn stats_are_split_by_family() {
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.subscribe(IpAddress::new(224, 0, 0, 251));
+        sim.add_unicast_v6(ip("fe80::21b:21ff:fe3c:4d5e"));
+        //a second address with the same low 24 bits shares the group
+        sim.add_unicast_v6(ip("2001:db8::3c:4d5e"));
+        assert_eq!(sim.sw.groups().len(), 2);
+
+        let v4 = IpAddress::new(192, 168, 1, 1);
+        let v6 = ip("fe80::1");
+        assert!(sim.process(MulticastPacket::new(v4, IpAddress::new(224, 0, 0, 251))));
+        assert!(sim.process(MulticastPacket::new(v6, ip("ff02::1:ff3c:4d5e"))));
+        assert!(sim.process(MulticastPacket::new(v6, ip("ff02::1:ff3c:4d5e"))));
+        //shares the mac of the joined v4 group, so only software can drop it
+        assert!(!sim.process(MulticastPacket::new(v4, IpAddress::new(239, 128, 0, 251))));
+
+        let [four, six] = sim.family_stats;
+        assert_eq!((four.total, four.sw_accepted, four.sw_dropped), (2, 1, 1));
+        assert_eq!((six.total, six.sw_accepted, six.sw_dropped), (2, 2, 0));
+        assert_eq!(sim.stats.total, four.total + six.total);
+        assert_eq!(sim.stats.sw_accepted, four.sw_accepted + six.sw_accepted);
+        assert_eq!(sim.stats.hw_dropped, four.hw_dropped + six.hw_dropped);
+    }
+
+    fn sources(last: &[u8]) -> Vec<IpAddress> {
+        last.iter().map(|&d| IpAddress::new(10, 0, 0, d)).collect()
+    }
+
+    #[test]
+    fn merge_with_an_exclude_keeps_the_common_exclusions() {
+        //exclude {1,2,3} and exclude {2,3,4} leave {2,3} excluded, the include of 3 lets it back in
+        let merged = SourceFilter::merge(&[
+            SourceFilter::exclude(&sources(&[1, 2, 3])),
+            SourceFilter::exclude(&sources(&[2, 3, 4])),
+            SourceFilter::include(&sources(&[3, 5])),
+        ]);
+        assert_eq!(merged, SourceFilter::exclude(&sources(&[2])));
+
+        let merged = SourceFilter::merge(&[
+            SourceFilter::exclude(&sources(&[1, 2])),
+            SourceFilter::exclude(&sources(&[3])),
+        ]);
+        assert_eq!(merged, SourceFilter::any_source());
+
+        let merged = SourceFilter::merge(&[
+            SourceFilter::include(&sources(&[1])),
+            SourceFilter::exclude(&sources(&[1, 2])),
+        ]);
+        assert_eq!(merged, SourceFilter::exclude(&sources(&[2])));
+    }
+
+    #[test]
+    fn merge_of_includes_is_their_union() {
+        let merged = SourceFilter::merge(&[
+            SourceFilter::include(&sources(&[1, 2])),
+            SourceFilter::include(&sources(&[2, 3])),
+            SourceFilter::include(&[]),
+        ]);
+        assert_eq!(merged, SourceFilter::include(&sources(&[1, 2, 3])));
+        assert!(SourceFilter::merge(&[]).is_empty());
+    }
+
+    #[test]
+    fn include_nothing_on_the_last_socket_leaves_the_group() {
+        let group = IpAddress::new(232, 1, 1, 1);
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.set_source_filter(1, group, SourceFilter::include(&sources(&[1])));
+        sim.set_source_filter(2, group, SourceFilter::include(&sources(&[2])));
+        assert_eq!(
+            sim.sw.state(&group),
+            Some(&SourceFilter::include(&sources(&[1, 2])))
+        );
+
+        sim.set_source_filter(1, group, SourceFilter::include(&[]));
+        assert!(passes_hw(&sim, group));
+        assert_eq!(
+            sim.sw.state(&group),
+            Some(&SourceFilter::include(&sources(&[2])))
+        );
+
+        sim.set_source_filter(2, group, SourceFilter::include(&[]));
+        assert!(!sim.sw.is_subscribed(&group));
+        assert!(!passes_hw(&sim, group));
+        assert!(sim.hw.bits.iter().all(|&bit| !bit));
+    }
+
+    #[test]
+    fn blocked_sources_are_counted_apart() {
+        let group = IpAddress::new(232, 1, 1, 1);
+        let mut sim = MulticastFilterSimulator::new(BITS);
+        sim.set_source_filter(1, group, SourceFilter::exclude(&sources(&[1])));
+
+        let (blocked, allowed) = (IpAddress::new(10, 0, 0, 1), IpAddress::new(10, 0, 0, 2));
+        assert!(!sim.process(MulticastPacket::new(blocked, group)));
+        assert!(sim.process(MulticastPacket::new(allowed, group)));
+        assert_eq!(sim.stats.sw_source_dropped, 1);
+        assert_eq!(sim.stats.sw_dropped, 0);
+        assert_eq!(sim.stats.sw_accepted, 1);
+        assert_eq!(sim.family_stats[0].sw_source_dropped, 1);
+    }
+
+    #[test]
+    fn classic_selection_is_the_original_hash() {
+        let crc = Crc32::new();
+        let selection = BitSelection::classic(BITS);
+        for d in 0..=255 {
+            let mac = MacAddress::from_multicast_ip(&IpAddress::new(239, 1, 1, d));
+            assert_eq!(selection.index(&crc, &mac), crc.hash_to_index(&mac, BITS));
+        }
+    }
+
+    #[test]
+    fn rebuilt_table_keeps_its_selection() {
+        let e1000 = NIC_PRESETS.iter().find(|p| p.name == "e1000 mta").unwrap();
+        let mut sim =
+            MulticastFilterSimulator::new_hybrid(1, e1000.size_bits, OverflowPolicy::HashTable);
+        sim.hw = HardwareHashTable::with_selection(*e1000);
+        sim.subscribe(IpAddress::new(224, 0, 0, 251));
+        sim.subscribe(IpAddress::new(239, 1, 1, 1));
+
+        //01:00:5e:01:01:01 takes address bits 47..36, 0x010
+        assert!(sim.hw.bits[0x010]);
+        assert_eq!(sim.hw.bits.iter().filter(|&&b| b).count(), 1);
+    }
+
+    fn hybrid_groups(n: u8) -> Vec<IpAddress> {
+        (1..=n).map(|d| IpAddress::new(239, 3, 0, d)).collect()
+    }
+
+    fn mac_of(ip: &IpAddress) -> MacAddress {
+        MacAddress::from_multicast_ip(ip)
+    }
+
+    #[test]
+    fn seventeenth_mac_spills_into_the_hash_table() {
+        let groups = hybrid_groups(17);
+        let mut sim = MulticastFilterSimulator::new_hybrid(16, BITS, OverflowPolicy::HashTable);
+        for ip in &groups[..16] {
+            sim.subscribe(*ip);
+        }
+        assert!(sim.hw.bits.iter().all(|&bit| !bit));
+
+        sim.subscribe(groups[16]);
+        let perfect = sim.perfect.as_ref().unwrap();
+        assert!(groups[..16].iter().all(|ip| perfect.in_slot(&mac_of(ip))));
+        assert!(!perfect.in_slot(&mac_of(&groups[16])));
+        assert_eq!(perfect.overflow(), vec![mac_of(&groups[16])]);
+        assert!(!perfect.all_multicast());
+        assert!(passes_hw(&sim, groups[16]));
+        assert_eq!(sim.hw.bits.iter().filter(|&&b| b).count(), 1);
+        assert!(sim.hw_accepts(&mac_of(&groups[16])));
+    }
+
+    #[test]
+    fn freed_slot_goes_to_the_oldest_overflowed_mac() {
+        let groups = hybrid_groups(6);
+        let mut sim = MulticastFilterSimulator::new_hybrid(4, BITS, OverflowPolicy::HashTable);
+        for ip in &groups {
+            sim.subscribe(*ip);
+        }
+        assert_eq!(
+            sim.perfect.as_ref().unwrap().overflow(),
+            vec![mac_of(&groups[4]), mac_of(&groups[5])]
+        );
+
+        sim.unsubscribe(groups[1]);
+        let perfect = sim.perfect.as_ref().unwrap();
+        assert!(!perfect.in_slot(&mac_of(&groups[1])));
+        assert!(perfect.in_slot(&mac_of(&groups[4])));
+        assert_eq!(perfect.overflow(), vec![mac_of(&groups[5])]);
+
+        //only the mac still overflowing is left in the hash table, counts included
+        let mut fresh = HardwareHashTable::new(BITS);
+        fresh.add_mac(&mac_of(&groups[5]));
+        assert_eq!(sim.hw.bits, fresh.bits);
+        assert_eq!(sim.hw.refs, fresh.refs);
+    }
+
+    #[test]
+    fn hybrid_counts_follow_the_overflow_through_churn() {
+        let groups: Vec<IpAddress> = (1..=24).map(|d| IpAddress::new(239, 4, 0, d)).collect();
+        let mut rng = Rng::new(46);
+        let mut sim = MulticastFilterSimulator::new_hybrid(4, BITS, OverflowPolicy::HashTable);
+
+        for _ in 0..2_000 {
+            let group = groups[rng.below(groups.len() as u64) as usize];
+            if rng.below(2) == 0 {
+                sim.subscribe(group);
+            } else {
+                sim.unsubscribe(group);
+            }
+
+            let mut fresh = HardwareHashTable::new(BITS);
+            for mac in sim.perfect.as_ref().unwrap().overflow() {
+                fresh.add_mac(&mac);
+            }
+            assert_eq!(sim.hw.refs, fresh.refs);
+            assert_eq!(sim.hw.bits, fresh.bits);
+        }
+    }
+
+    #[test]
+    fn all_multicast_follows_the_overflow() {
+        let groups = hybrid_groups(5);
+        let mut sim = MulticastFilterSimulator::new_hybrid(4, BITS, OverflowPolicy::AllMulticast);
+        let stranger = mac_of(&IpAddress::new(239, 9, 9, 9));
+        for ip in &groups[..4] {
+            sim.subscribe(*ip);
+        }
+        assert!(!sim.perfect.as_ref().unwrap().all_multicast());
+        assert!(!sim.hw_accepts(&stranger));
+
+        sim.subscribe(groups[4]);
+        assert!(sim.perfect.as_ref().unwrap().all_multicast());
+        assert!(sim.hw_accepts(&stranger));
+        //the overflow is not hashed under this policy
+        assert!(sim.hw.bits.iter().all(|&bit| !bit));
+
+        sim.unsubscribe(groups[0]);
+        assert!(!sim.perfect.as_ref().unwrap().all_multicast());
+        assert!(!sim.hw_accepts(&stranger));
+        assert!(groups[1..].iter().all(|ip| sim.hw_accepts(&mac_of(ip))));
+    }
+
+    #[test]
+    fn reprogramming_keeps_the_table_geometry() {
+        let mut sim = MulticastFilterSimulator::new_hybrid(1, 8, OverflowPolicy::HashTable);
+        sim.hw = HardwareHashTable::with_hashes(8, 3, IndexScheme::DoubleHashing);
+        sim.hw.selection = BitSelection {
+            name: "low bits",
+            slice: Slice::Low,
+            ..BitSelection::classic(8)
+        };
+        let selection = sim.hw.selection;
+        for ip in hybrid_groups(4) {
+            sim.subscribe(ip);
+        }
+        sim.unsubscribe(hybrid_groups(1)[0]);
+
+        assert_eq!(sim.hw.size_bits, 8);
+        assert_eq!(sim.hw.bits.len(), 256);
+        assert_eq!(sim.hw.refs.len(), 256);
+        assert_eq!(sim.hw.k, 3);
+        assert_eq!(sim.hw.scheme, IndexScheme::DoubleHashing);
+        assert_eq!(sim.hw.selection, selection);
+        for ip in &hybrid_groups(4)[2..] {
+            assert_eq!(sim.hw.indices(&mac_of(ip)).len(), 3);
+            assert!(passes_hw(&sim, *ip));
+        }
+    }
+
+    #[test]
+    fn bloom_params_stop_at_the_size_cap() {
+        assert_eq!(bloom_params(16, 0.01), (8, 3));
+        let (bits, k) = bloom_params(1_000_000, 1e-9);
+        assert_eq!(bits, bloom::MAX_SIZE_BITS);
+        assert!(k >= 1);
+    }
+
+    #[test]
+    #[should_panic(expected = "target false positive rate")]
+    fn bloom_params_reject_a_rate_of_one() {
+        bloom_params(16, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "target false positive rate")]
+    fn bloom_params_reject_a_rate_of_zero() {
+        bloom_params(16, 0.0);
+    }
+
+    #[test]
+    fn crc_slices_meet_the_bloom_params() {
+        //256 entries with k = 6 needs 48 bits of slices, more than one crc-32 holds
+        let table = HardwareHashTable::with_hashes(8, 6, IndexScheme::CrcSlices);
+        let repeats = (0..=255)
+            .map(|d| {
+                table.indices(&MacAddress::from_multicast_ip(&IpAddress::new(
+                    239, 1, 2, d,
+                )))
+            })
+            .filter(|idx| idx[4] == idx[0] && idx[5] == idx[1])
+            .count();
+        assert!(repeats < 4);
+
+        for n in [16, 64] {
+            for target in [0.1, 0.01, 0.001] {
+                let (bits, k) = bloom_params(n, target);
+                let theory = bloom_fpr(bits, k, n);
+                let measured = measure_fpr(bits, k, IndexScheme::CrcSlices, n);
+                assert!(
+                    (measured - theory).abs() <= theory * 0.2,
+                    "{} groups, {} entries, k = {}: measured {:.3}%, theory {:.3}%",
+                    n,
+                    1 << bits,
+                    k,
+                    measured * 100.0,
+                    theory * 100.0
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn crc_presets_match_check_values() {
+        for params in &PRESETS {
+            let crc = Crc::new(*params);
+            assert_eq!(
+                crc.checksum_bytewise(b"123456789"),
+                params.check,
+                "{}",
+                params.name
+            );
+            assert_eq!(crc.checksum(b"123456789"), params.check, "{}", params.name);
+        }
+    }
+
+    #[test]
+    fn slicing_by_8_matches_bytewise() {
+        let mut rng = Rng::new(7);
+        let data: Vec<u8> = (0..100).map(|_| rng.next_u64() as u8).collect();
+        for params in &PRESETS {
+            let crc = Crc::new(*params);
+            for start in 0..8 {
+                for end in start..data.len() {
+                    let slice = &data[start..end];
+                    assert_eq!(
+                        crc.checksum(slice),
+                        crc.checksum_bytewise(slice),
+                        "{}",
+                        params.name
+                    );
+                }
+            }
+        }
+    }
+
+    fn test_frame(vlan: Option<VlanTag>, payload_len: usize) -> EthernetFrame {
+        EthernetFrame {
+            dst: MacAddress::from_multicast_ip(&IpAddress::new(239, 1, 1, 1)),
+            src: MacAddress([0x02, 0, 0, 0, 0, 1]),
+            vlan,
+            ethertype: ETHERTYPE_IPV4,
+            payload: (0..payload_len).map(|i| i as u8).collect(),
+        }
+    }
+
+    #[test]
+    fn frames_round_trip_through_fcs() {
+        let crc = Crc32::new();
+        let tagged = test_frame(
+            Some(VlanTag {
+                pcp: 3,
+                dei: true,
+                vid: 4000,
+            }),
+            200,
+        );
+        assert_eq!(
+            EthernetFrame::parse(&tagged.to_bytes(&crc), &crc),
+            Ok(tagged)
+        );
+
+        //a short payload comes back padded to the 64 byte minimum
+        let short = test_frame(None, 10);
+        let bytes = short.to_bytes(&crc);
+        assert_eq!(bytes.len(), 64);
+        let parsed = EthernetFrame::parse(&bytes, &crc).unwrap();
+        assert_eq!(parsed.payload.len(), 46);
+        assert_eq!(parsed.payload[..10], short.payload[..]);
+
+        //running the crc over the fcs too leaves the fixed crc-32 residue
+        assert_eq!(!crc.compute(&bytes), 0xDEBB20E3);
+        assert_eq!(
+            EthernetFrame::parse(&bytes[..60], &crc),
+            Err(FrameError::Runt(60))
+        );
+    }
+
+    #[test]
+    fn fcs_catches_every_single_bit_and_short_burst() {
+        let crc = Crc32::new();
+        let clean = test_frame(None, 100).to_bytes(&crc);
+        for bit in 0..clean.len() * 8 {
+            let mut bytes = clean.clone();
+            bytes[bit / 8] ^= 1 << (bit % 8);
+            assert!(EthernetFrame::parse(&bytes, &crc).is_err());
+        }
+
+        let mut rng = Rng::new(50);
+        for len in 2..=32 {
+            for _ in 0..200 {
+                let mut bytes = clean.clone();
+                inject_errors(&mut bytes, ErrorPattern::Burst(len), &mut rng);
+                assert!(EthernetFrame::parse(&bytes, &crc).is_err());
+            }
+        }
+    }
+
+    #[test]
+    fn crc8_catches_every_odd_number_of_flips() {
+        let crc8 = Crc::new(CRC_8_MAXIM_DOW);
+        let clean = test_frame(None, 100).to_bytes(&Crc32::new());
+        let expected = crc8.checksum(&clean);
+        let mut rng = Rng::new(50);
+        for n in [1, 3, 5, 7, 33] {
+            for _ in 0..500 {
+                let mut bytes = clean.clone();
+                inject_errors(&mut bytes, ErrorPattern::Random(n), &mut rng);
+                assert_ne!(crc8.checksum(&bytes), expected);
+            }
+        }
+    }
+
+    #[test]
+    fn error_patterns_can_span_the_whole_frame() {
+        let mut rng = Rng::new(50);
+        let clean = [0x5Au8; 8];
+
+        let mut bytes = clean;
+        inject_errors(&mut bytes, ErrorPattern::Burst(64), &mut rng);
+        assert_eq!(bytes[0] & 1, !clean[0] & 1);
+        assert_eq!(bytes[7] >> 7, !clean[7] >> 7);
+
+        let mut bytes = clean;
+        inject_errors(&mut bytes, ErrorPattern::Random(64), &mut rng);
+        assert_eq!(bytes, [!0x5Au8; 8]);
+    }
+}
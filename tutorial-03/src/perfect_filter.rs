@@ -0,0 +1,74 @@
+use std::fmt;
+
+use crate::MacAddress;
+
+//what the nic does with joined macs that do not fit in the exact-match slots
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OverflowPolicy {
+    //the extra macs go into the crc hash table
+    HashTable,
+    //give up on filtering and accept every multicast frame
+    AllMulticast,
+}
+
+impl fmt::Display for OverflowPolicy {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            OverflowPolicy::HashTable => f.pad("hash"),
+            OverflowPolicy::AllMulticast => f.pad("allmulti"),
+        }
+    }
+}
+
+//the perfect-match stage of a real controller: a handful of mac registers
+//checked before the hash table. like a driver's rx-mode handler it keeps the
+//full list of joined macs and hands the first `slots` of them to the registers
+pub struct PerfectFilter {
+    slots: usize,
+    policy: OverflowPolicy,
+    //joined macs in join order, with how many groups use each
+    macs: Vec<(MacAddress, u32)>,
+}
+
+impl PerfectFilter {
+    pub fn new(slots: usize, policy: OverflowPolicy) -> Self {
+        Self {
+            slots,
+            policy,
+            macs: Vec::new(),
+        }
+    }
+
+    pub fn policy(&self) -> OverflowPolicy {
+        self.policy
+    }
+
+    pub fn add(&mut self, mac: MacAddress) {
+        match self.macs.iter_mut().find(|(m, _)| *m == mac) {
+            Some((_, refs)) => *refs += 1,
+            None => self.macs.push((mac, 1)),
+        }
+    }
+
+    //a freed register is taken over by the oldest overflowed mac
+    pub fn remove(&mut self, mac: MacAddress) {
+        if let Some(pos) = self.macs.iter().position(|(m, _)| *m == mac) {
+            self.macs[pos].1 -= 1;
+            if self.macs[pos].1 == 0 {
+                self.macs.remove(pos);
+            }
+        }
+    }
+
+    pub fn in_slot(&self, mac: &MacAddress) -> bool {
+        self.macs.iter().take(self.slots).any(|(m, _)| m == mac)
+    }
+
+    pub fn overflow(&self) -> Vec<MacAddress> {
+        self.macs.iter().skip(self.slots).map(|&(m, _)| m).collect()
+    }
+
+    pub fn all_multicast(&self) -> bool {
+        self.policy == OverflowPolicy::AllMulticast && self.macs.len() > self.slots
+    }
+}
@@ -0,0 +1,160 @@
+use std::collections::BTreeMap;
+
+use crate::igmp::{
+    GROUP_MEMBERSHIP_INTERVAL, IgmpMessage, LAST_MEMBER_QUERY_INTERVAL,
+    OTHER_QUERIER_PRESENT_INTERVAL, ROBUSTNESS,
+};
+use crate::{IpAddress, MulticastPacket};
+
+//224.0.0.0/24 and ff02::/16 carry control traffic (igmp itself, ospf, nd) and are always flooded
+fn is_link_local(ip: &IpAddress) -> bool {
+    match ip {
+        IpAddress::V4(b) => b[..3] == [224, 0, 0],
+        IpAddress::V6(b) => b[0] == 0xFF && b[1] & 0x0F == 0x02,
+    }
+}
+
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SnoopingStats {
+    pub packets: u64,
+    pub bytes_sent: u64,
+    //what the same traffic costs when every packet goes out of every other port
+    pub bytes_flooded: u64,
+}
+
+impl SnoopingStats {
+    pub fn savings(&self) -> f64 {
+        if self.bytes_flooded == 0 {
+            return 0.0;
+        }
+        1.0 - self.bytes_sent as f64 / self.bytes_flooded as f64
+    }
+}
+
+//a layer 2 switch listening in on igmp: ports where reports arrive become
+//members of the group, ports where queries arrive lead to a router. group
+//traffic goes to member ports plus router ports instead of being flooded
+pub struct SnoopingSwitch {
+    ports: usize,
+    //with fast-leave a leave removes the port at once (one host per port),
+    //otherwise the port waits for the querier's last member queries
+    fast_leave: bool,
+    //group -> port -> membership expiry
+    members: BTreeMap<IpAddress, BTreeMap<usize, u64>>,
+    //port -> expiry of the last query heard on it
+    router_ports: BTreeMap<usize, u64>,
+    pub stats: SnoopingStats,
+}
+
+impl SnoopingSwitch {
+    pub fn new(ports: usize, fast_leave: bool) -> Self {
+        assert!(ports > 0, "a switch needs at least one port");
+        Self {
+            ports,
+            fast_leave,
+            members: BTreeMap::new(),
+            router_ports: BTreeMap::new(),
+            stats: SnoopingStats::default(),
+        }
+    }
+
+    pub fn receive_igmp(&mut self, now: u64, port: usize, msg: IgmpMessage) {
+        assert!(
+            port < self.ports,
+            "port {} on a {}-port switch",
+            port,
+            self.ports
+        );
+        match msg {
+            IgmpMessage::Query { .. } => {
+                self.router_ports
+                    .insert(port, now + OTHER_QUERIER_PRESENT_INTERVAL);
+            }
+            IgmpMessage::Report(group) => {
+                self.members
+                    .entry(group)
+                    .or_default()
+                    .insert(port, now + GROUP_MEMBERSHIP_INTERVAL);
+            }
+            IgmpMessage::Leave(group) => {
+                let Some(ports) = self.members.get_mut(&group) else {
+                    return;
+                };
+                if self.fast_leave {
+                    ports.remove(&port);
+                } else if let Some(expires) = ports.get_mut(&port) {
+                    //a report in answer to the group-specific queries restores the full timer
+                    *expires = (*expires).min(now + ROBUSTNESS * LAST_MEMBER_QUERY_INTERVAL);
+                }
+                if ports.is_empty() {
+                    self.members.remove(&group);
+                }
+            }
+        }
+    }
+
+    //drop memberships and router ports whose timers ran out
+    pub fn expire(&mut self, now: u64) {
+        for ports in self.members.values_mut() {
+            ports.retain(|_, expires| *expires > now);
+        }
+        self.members.retain(|_, ports| !ports.is_empty());
+        self.router_ports.retain(|_, expires| *expires > now);
+    }
+
+    pub fn member_ports(&self, now: u64, group: &IpAddress) -> Vec<usize> {
+        self.members
+            .get(group)
+            .map(|ports| {
+                ports
+                    .iter()
+                    .filter(|&(_, &e)| e > now)
+                    .map(|(&p, _)| p)
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    pub fn router_ports(&self, now: u64) -> Vec<usize> {
+        self.router_ports
+            .iter()
+            .filter(|&(_, &e)| e > now)
+            .map(|(&p, _)| p)
+            .collect()
+    }
+
+    pub fn groups(&self) -> Vec<IpAddress> {
+        self.members.keys().copied().collect()
+    }
+
+    //egress ports for a data packet; unknown groups only go towards the routers
+    pub fn forward(
+        &mut self,
+        now: u64,
+        in_port: usize,
+        pkt: &MulticastPacket,
+        bytes: usize,
+    ) -> Vec<usize> {
+        assert!(
+            in_port < self.ports,
+            "port {} on a {}-port switch",
+            in_port,
+            self.ports
+        );
+        let mut out: Vec<usize> = if is_link_local(&pkt.dst_ip) {
+            (0..self.ports).collect()
+        } else {
+            let mut ports = self.member_ports(now, &pkt.dst_ip);
+            ports.extend(self.router_ports(now));
+            ports.sort_unstable();
+            ports.dedup();
+            ports
+        };
+        out.retain(|&p| p != in_port);
+
+        self.stats.packets += 1;
+        self.stats.bytes_sent += (out.len() * bytes) as u64;
+        self.stats.bytes_flooded += ((self.ports - 1) * bytes) as u64;
+        out
+    }
+}
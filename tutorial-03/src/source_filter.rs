@@ -0,0 +1,91 @@
+use std::collections::BTreeSet;
+use std::fmt;
+
+use crate::IpAddress;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FilterMode {
+    Include,
+    Exclude,
+}
+
+//igmpv3/mldv2 source filter for one group, either a socket's request or the
+//interface state merged from all sockets (rfc 3376 section 3, rfc 3810 section 4)
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SourceFilter {
+    pub mode: FilterMode,
+    pub sources: BTreeSet<IpAddress>,
+}
+
+impl SourceFilter {
+    pub fn include(sources: &[IpAddress]) -> Self {
+        Self {
+            mode: FilterMode::Include,
+            sources: sources.iter().copied().collect(),
+        }
+    }
+
+    pub fn exclude(sources: &[IpAddress]) -> Self {
+        Self {
+            mode: FilterMode::Exclude,
+            sources: sources.iter().copied().collect(),
+        }
+    }
+
+    //what a plain any-source join asks for
+    pub fn any_source() -> Self {
+        Self::exclude(&[])
+    }
+
+    //include {} receives nothing, the same as not being a member
+    pub fn is_empty(&self) -> bool {
+        self.mode == FilterMode::Include && self.sources.is_empty()
+    }
+
+    pub fn allows(&self, src: &IpAddress) -> bool {
+        match self.mode {
+            FilterMode::Include => self.sources.contains(src),
+            FilterMode::Exclude => !self.sources.contains(src),
+        }
+    }
+
+    //rfc 3376 section 3.2: if any socket excludes, the interface excludes the
+    //sources every excluding socket excludes, minus whatever a socket includes;
+    //otherwise it includes the union of the include lists
+    pub fn merge<'a>(filters: impl IntoIterator<Item = &'a SourceFilter>) -> SourceFilter {
+        let mut include = BTreeSet::new();
+        let mut exclude: Option<BTreeSet<IpAddress>> = None;
+        for filter in filters {
+            match filter.mode {
+                FilterMode::Include => include.extend(filter.sources.iter().copied()),
+                FilterMode::Exclude => {
+                    exclude = Some(match exclude {
+                        None => filter.sources.clone(),
+                        Some(common) => common.intersection(&filter.sources).copied().collect(),
+                    })
+                }
+            }
+        }
+
+        match exclude {
+            Some(common) => SourceFilter {
+                mode: FilterMode::Exclude,
+                sources: &common - &include,
+            },
+            None => SourceFilter {
+                mode: FilterMode::Include,
+                sources: include,
+            },
+        }
+    }
+}
+
+impl fmt::Display for SourceFilter {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sources: Vec<String> = self.sources.iter().map(|s| s.to_string()).collect();
+        match self.mode {
+            FilterMode::Include => write!(f, "INCLUDE {{{}}}", sources.join(", ")),
+            FilterMode::Exclude => write!(f, "EXCLUDE {{{}}}", sources.join(", ")),
+        }
+    }
+}